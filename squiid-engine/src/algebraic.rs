@@ -0,0 +1,103 @@
+// evaluation of algebraic (infix) expressions
+// expressions are parsed into postfix tokens with squiid-parser and evaluated as a single command
+
+use crate::{
+    bucket::Bucket,
+    command_mappings::CommandRegistry,
+    engine::Engine,
+    error::{EngineError, ErrorKind},
+    history::Checkpoint,
+    protocol::server_response::MessageAction,
+};
+
+/// Convert an operator symbol produced by the parser to the command that evaluates it.
+/// Any other token is returned unchanged
+pub fn operator_command(token: &str) -> &str {
+    match token {
+        "+" => "add",
+        "-" => "subtract",
+        "*" => "multiply",
+        "/" => "divide",
+        "%" => "mod",
+        "^" => "power",
+        "=" => "invstore",
+        "==" => "eq",
+        ">" => "gt",
+        "<" => "lt",
+        ">=" => "geq",
+        "<=" => "leq",
+        _ => token,
+    }
+}
+
+/// Parse an algebraic expression into the commands and values to evaluate
+pub fn parse<'a>(
+    commands: &CommandRegistry,
+    expression: &'a str,
+) -> Result<Vec<&'a str>, EngineError> {
+    let tokens = squiid_parser::parse(expression.trim())
+        .map_err(|error| EngineError::new(ErrorKind::InvalidExpression, error))?;
+
+    let tokens: Vec<&str> = tokens.into_iter().map(operator_command).collect();
+
+    // reject commands that only make sense in RPN mode before evaluating anything
+    if let Some(token) = tokens.iter().find(|token| {
+        commands
+            .get(token)
+            .is_some_and(|command| !command.info.algebraic)
+    }) {
+        return Err(EngineError::new(
+            ErrorKind::InvalidExpression,
+            format!("{} is invalid in algebraic mode", token),
+        ));
+    }
+
+    Ok(tokens)
+}
+
+/// Evaluate an algebraic expression on top of the stack. The expression is recorded
+/// as a single entry in the undo history, and if any part of it fails the engine is
+/// left as it was before. The value of the expression becomes the previous answer
+pub fn evaluate(
+    engine: &mut Engine,
+    commands: &CommandRegistry,
+    expression: &str,
+) -> Result<MessageAction, EngineError> {
+    let tokens = parse(commands, expression)?;
+
+    // keep changes made outside of the expression out of its history entry
+    engine.synchronize_history();
+    let before = Checkpoint::capture(engine);
+    let depth = engine.stack.len();
+
+    for token in tokens {
+        let result = engine
+            .interrupt
+            .check()
+            .and_then(|_| match commands.get(token) {
+                Some(command) => {
+                    (command.function)(engine).map_err(|error| error.with_command(token))
+                }
+                None => engine.add_item_to_stack(Bucket::from(token.to_string())),
+            });
+
+        if let Err(error) = result {
+            before.restore(engine);
+            return Err(error);
+        }
+    }
+
+    // expressions such as assignments do not leave a value on the stack
+    let result = if engine.stack.len() > depth {
+        engine.stack.last().cloned()
+    } else {
+        None
+    };
+    if let Some(result) = &result {
+        engine.previous_answer = result.clone();
+    }
+
+    engine.record_history(expression.trim());
+
+    Ok(MessageAction::SendResult(result))
+}
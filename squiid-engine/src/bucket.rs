@@ -4,7 +4,12 @@ use std::{collections::HashMap, f64::consts};
 
 use rust_decimal::{prelude::FromPrimitive, Decimal, MathematicalOps};
 use rust_decimal_macros::dec;
-use serde::{de::Visitor, Deserialize, Serialize};
+use serde::{
+    de::{value::MapAccessDeserializer, MapAccess, Visitor},
+    Deserialize, Serialize,
+};
+
+use crate::utils::NUMERIC_REGEX;
 
 /// Types of constants
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -47,6 +52,31 @@ impl ExposedConstant for ConstantTypes {
     }
 }
 
+impl ConstantTypes {
+    /// Every constant type
+    pub const ALL: [ConstantTypes; 12] = [
+        ConstantTypes::PI,
+        ConstantTypes::HalfPI,
+        ConstantTypes::ThirdPI,
+        ConstantTypes::QuarterPI,
+        ConstantTypes::SixthPI,
+        ConstantTypes::EighthPI,
+        ConstantTypes::TwoPI,
+        ConstantTypes::E,
+        ConstantTypes::TAU,
+        ConstantTypes::C,
+        ConstantTypes::G,
+        ConstantTypes::PHI,
+    ];
+
+    /// Get a constant type from its name without the `#` prefix
+    pub fn from_name(name: &str) -> Option<ConstantTypes> {
+        ConstantTypes::ALL
+            .into_iter()
+            .find(|constant_type| &constant_type.name()[1..] == name)
+    }
+}
+
 // TODO: extrapolate constants things into constants file
 /// Build a hashmap of exposed constants
 pub fn build_exposed_constants() -> HashMap<&'static str, ConstantTypes> {
@@ -69,6 +99,10 @@ pub enum BucketTypes {
     Float,
     String,
     Constant(ConstantTypes),
+    /// A list of items, stored as `[a, b, c]`
+    List,
+    /// An unevaluated sequence of RPN tokens, stored as `{ a b c }`
+    Program,
     // TODO: should undefined error out? in trig and stuff
     Undefined,
 }
@@ -115,6 +149,42 @@ impl Bucket {
         }
     }
 
+    /// Create a list Bucket from a Vec of Buckets
+    pub fn from_list(items: Vec<Bucket>) -> Self {
+        let item_strings: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+
+        Bucket {
+            value: Some(format!("[{}]", item_strings.join(", "))),
+            bucket_type: BucketTypes::List,
+        }
+    }
+
+    /// Get the items of a list Bucket. Returns None if the Bucket is not a list
+    pub fn list_items(&self) -> Option<Vec<Bucket>> {
+        if self.bucket_type != BucketTypes::List {
+            return None;
+        }
+
+        parse_list_literal(self.value.as_ref()?)
+    }
+
+    /// Create a program Bucket from a slice of RPN tokens
+    pub fn from_program(tokens: &[String]) -> Self {
+        Bucket {
+            value: Some(format!("{{ {} }}", tokens.join(" "))),
+            bucket_type: BucketTypes::Program,
+        }
+    }
+
+    /// Get the tokens of a program Bucket. Returns None if the Bucket is not a program
+    pub fn program_tokens(&self) -> Option<Vec<String>> {
+        if self.bucket_type != BucketTypes::Program {
+            return None;
+        }
+
+        parse_program_literal(self.value.as_ref()?)
+    }
+
     /// Sine
     pub fn sin(&self) -> Option<Self> {
         match &self.bucket_type {
@@ -137,7 +207,10 @@ impl Bucket {
             BucketTypes::Float => Some(Self::from(
                 Decimal::from_f64(self.value.clone()?.parse::<f64>().unwrap())?.checked_sin()?,
             )),
-            BucketTypes::String | BucketTypes::Undefined => None,
+            BucketTypes::String
+            | BucketTypes::List
+            | BucketTypes::Program
+            | BucketTypes::Undefined => None,
         }
     }
 
@@ -163,7 +236,10 @@ impl Bucket {
             BucketTypes::Float => Some(Self::from(
                 Decimal::from_f64(self.value.clone()?.parse::<f64>().unwrap())?.checked_cos()?,
             )),
-            BucketTypes::String | BucketTypes::Undefined => None,
+            BucketTypes::String
+            | BucketTypes::List
+            | BucketTypes::Program
+            | BucketTypes::Undefined => None,
         }
     }
 
@@ -189,7 +265,10 @@ impl Bucket {
             BucketTypes::Float => Some(Self::from(
                 Decimal::from_f64(self.value.clone()?.parse::<f64>().unwrap())?.checked_tan()?,
             )),
-            BucketTypes::String | BucketTypes::Undefined => None,
+            BucketTypes::String
+            | BucketTypes::List
+            | BucketTypes::Program
+            | BucketTypes::Undefined => None,
         }
     }
 
@@ -232,7 +311,10 @@ impl Bucket {
                 }
                 None => None,
             },
-            BucketTypes::String | BucketTypes::Undefined => None,
+            BucketTypes::String
+            | BucketTypes::List
+            | BucketTypes::Program
+            | BucketTypes::Undefined => None,
         }
     }
 
@@ -277,7 +359,10 @@ impl Bucket {
                 }
                 None => None,
             },
-            BucketTypes::String | BucketTypes::Undefined => None,
+            BucketTypes::String
+            | BucketTypes::List
+            | BucketTypes::Program
+            | BucketTypes::Undefined => None,
         }
     }
 
@@ -322,9 +407,59 @@ impl Bucket {
                 }
                 None => None,
             },
-            BucketTypes::String | BucketTypes::Undefined => None,
+            BucketTypes::String
+            | BucketTypes::List
+            | BucketTypes::Program
+            | BucketTypes::Undefined => None,
+        }
+    }
+}
+
+/// Parse a list literal such as `[1, -3, 2]` into a Vec of Buckets.
+/// Numeric items become floats, everything else becomes a string.
+/// Returns None if the input is not surrounded by square brackets
+pub fn parse_list_literal(literal: &str) -> Option<Vec<Bucket>> {
+    let inner = literal.trim().strip_prefix('[')?.strip_suffix(']')?.trim();
+
+    if inner.is_empty() {
+        return Some(Vec::new());
+    }
+
+    Some(
+        inner
+            .split(',')
+            .map(|item| {
+                let item = item.trim();
+                if NUMERIC_REGEX.is_match(item) {
+                    Bucket::from(item.parse::<f64>().unwrap())
+                } else {
+                    Bucket::from(item)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Parse a program literal such as `{ $k 2 power }` into its tokens.
+/// Returns None if the literal is not surrounded by braces or the braces are unbalanced
+pub fn parse_program_literal(literal: &str) -> Option<Vec<String>> {
+    let inner = literal.trim().strip_prefix('{')?.strip_suffix('}')?;
+
+    // make sure nested programs are closed, so that `{ a } { b }` is not a single program
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return None,
+            '}' => depth -= 1,
+            _ => (),
         }
     }
+    if depth != 0 {
+        return None;
+    }
+
+    Some(inner.split_whitespace().map(String::from).collect())
 }
 
 // implementation of .to_string()
@@ -410,8 +545,8 @@ impl<'de> Deserialize<'de> for Bucket {
     where
         D: serde::Deserializer<'de>,
     {
-        let deserialized_string = deserializer.deserialize_string(BucketVisitor)?;
-        Ok(Self::from(deserialized_string))
+        // buckets are either plain strings or in their typed representation
+        deserializer.deserialize_any(BucketVisitor)
     }
 
     fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
@@ -426,30 +561,222 @@ impl<'de> Deserialize<'de> for Bucket {
 
 struct BucketVisitor;
 impl<'de> Visitor<'de> for BucketVisitor {
-    type Value = String;
+    type Value = Bucket;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a String")
+        formatter.write_str("a String or a typed Bucket")
     }
 
     fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        Ok(v)
+        Ok(Bucket::from(v))
     }
 
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        Ok(v.to_string())
+        Ok(Bucket::from(v.to_string()))
     }
 
     fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        Ok(v.to_string())
+        Ok(Bucket::from(v.to_string()))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let typed = TypedBucket::deserialize(MapAccessDeserializer::new(map))?;
+        Bucket::try_from(typed).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The kind of a Bucket in its typed representation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypedBucketKind {
+    Float,
+    String,
+    Constant,
+    List,
+    Program,
+    Undefined,
+}
+
+/// Representation of a Bucket that keeps its type when serialized,
+/// such as `{"type": "constant", "name": "pi", "value": "3.14..."}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypedBucket {
+    /// The kind of the Bucket
+    #[serde(rename = "type")]
+    pub kind: TypedBucketKind,
+    /// The name of the constant without the `#` prefix. Only set for constants
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The value of the Bucket. None when undefined
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+impl From<&Bucket> for TypedBucket {
+    fn from(bucket: &Bucket) -> Self {
+        let (kind, name) = match bucket.bucket_type {
+            BucketTypes::Float => (TypedBucketKind::Float, None),
+            BucketTypes::String => (TypedBucketKind::String, None),
+            BucketTypes::Constant(constant_type) => (
+                TypedBucketKind::Constant,
+                Some(constant_type.name()[1..].to_string()),
+            ),
+            BucketTypes::List => (TypedBucketKind::List, None),
+            BucketTypes::Program => (TypedBucketKind::Program, None),
+            BucketTypes::Undefined => (TypedBucketKind::Undefined, None),
+        };
+
+        TypedBucket {
+            kind,
+            name,
+            value: bucket.value.clone(),
+        }
+    }
+}
+
+impl TryFrom<TypedBucket> for Bucket {
+    type Error = String;
+
+    fn try_from(typed: TypedBucket) -> Result<Self, Self::Error> {
+        let bucket_type = match typed.kind {
+            TypedBucketKind::Float => BucketTypes::Float,
+            TypedBucketKind::String => BucketTypes::String,
+            TypedBucketKind::Constant => {
+                let name = typed.name.ok_or("constant without a name")?;
+                match ConstantTypes::from_name(&name) {
+                    Some(constant_type) => return Ok(Bucket::from_constant(constant_type)),
+                    None => return Err(format!("unknown constant `{}`", name)),
+                }
+            }
+            TypedBucketKind::List => BucketTypes::List,
+            TypedBucketKind::Program => BucketTypes::Program,
+            TypedBucketKind::Undefined => return Ok(Bucket::new_undefined()),
+        };
+
+        let value = typed.value.ok_or("defined bucket without a value")?;
+
+        // make sure the value can be used by the engine later
+        let invalid = match bucket_type {
+            BucketTypes::Float if value.parse::<f64>().is_err() => Some("float"),
+            BucketTypes::List if parse_list_literal(&value).is_none() => Some("list"),
+            BucketTypes::Program if parse_program_literal(&value).is_none() => Some("program"),
+            _ => None,
+        };
+        if let Some(kind) = invalid {
+            return Err(format!("`{}` is not a valid {}", value, kind));
+        }
+
+        Ok(Bucket {
+            value: Some(value),
+            bucket_type,
+        })
+    }
+}
+
+/// Serde helpers that serialize Buckets, and collections of Buckets, in their typed
+/// representation. Use with `#[serde(with = "crate::bucket::typed")]`
+pub mod typed {
+    use std::collections::HashMap;
+
+    use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Bucket, TypedBucket};
+
+    /// A value that contains Buckets and can be converted to and from a typed representation
+    pub trait Typed: Sized {
+        /// The value with every Bucket replaced by a TypedBucket
+        type Repr: Serialize + DeserializeOwned;
+
+        /// Convert the value to its typed representation
+        fn to_typed(&self) -> Self::Repr;
+
+        /// Convert the typed representation back to the value
+        fn from_typed(repr: Self::Repr) -> Result<Self, String>;
+    }
+
+    impl Typed for Bucket {
+        type Repr = TypedBucket;
+
+        fn to_typed(&self) -> Self::Repr {
+            TypedBucket::from(self)
+        }
+
+        fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+            Bucket::try_from(repr)
+        }
+    }
+
+    impl<T: Typed> Typed for Option<T> {
+        type Repr = Option<T::Repr>;
+
+        fn to_typed(&self) -> Self::Repr {
+            self.as_ref().map(T::to_typed)
+        }
+
+        fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+            repr.map(T::from_typed).transpose()
+        }
+    }
+
+    impl<T: Typed> Typed for Vec<T> {
+        type Repr = Vec<T::Repr>;
+
+        fn to_typed(&self) -> Self::Repr {
+            self.iter().map(T::to_typed).collect()
+        }
+
+        fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+            repr.into_iter().map(T::from_typed).collect()
+        }
+    }
+
+    impl<T: Typed> Typed for (String, T) {
+        type Repr = (String, T::Repr);
+
+        fn to_typed(&self) -> Self::Repr {
+            (self.0.clone(), self.1.to_typed())
+        }
+
+        fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+            Ok((repr.0, T::from_typed(repr.1)?))
+        }
+    }
+
+    impl<T: Typed> Typed for HashMap<String, T> {
+        type Repr = HashMap<String, T::Repr>;
+
+        fn to_typed(&self) -> Self::Repr {
+            self.iter()
+                .map(|(key, value)| (key.clone(), value.to_typed()))
+                .collect()
+        }
+
+        fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+            repr.into_iter()
+                .map(|(key, value)| Ok((key, T::from_typed(value)?)))
+                .collect()
+        }
+    }
+
+    pub fn serialize<T: Typed, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        value.to_typed().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Typed, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        T::from_typed(T::Repr::deserialize(deserializer)?).map_err(serde::de::Error::custom)
     }
 }
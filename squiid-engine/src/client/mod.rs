@@ -0,0 +1,237 @@
+#[cfg(feature = "nng")]
+pub mod nng;
+#[cfg(all(feature = "unix-socket", unix))]
+pub mod unix_socket;
+
+use std::{fmt, time::Duration};
+
+use serde_json::Value;
+
+use crate::{
+    bucket::Bucket,
+    error::EngineError,
+    protocol::{
+        client_request::{
+            ClientRequestMessage, ConfigurationActionType, ConfigurationPayload, RequestPayload,
+            RequestType,
+        },
+        server_response::{ResponsePayload, ServerResponseMessage},
+    },
+};
+
+/// Sends a request to the server and receives its response
+pub trait Transport {
+    /// Send a serialized request and wait for the serialized response. If a timeout is
+    /// given, stop waiting with `ClientError::Timeout` once it has passed
+    fn request(&self, request: &str, timeout: Option<Duration>) -> Result<String, ClientError>;
+}
+
+/// An error returned by a client request
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent, or its response could not be received
+    Transport(String),
+    /// The server did not respond before the timeout of the client
+    Timeout,
+    /// The response could not be deserialized
+    InvalidResponse(String),
+    /// The server responded with a payload that the request does not produce
+    UnexpectedResponse(ResponsePayload),
+    /// The server answered the request with an error
+    Engine(EngineError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Transport(message) => {
+                write!(f, "could not communicate with the server: {}", message)
+            }
+            ClientError::Timeout => write!(f, "the server did not respond in time"),
+            ClientError::InvalidResponse(message) => {
+                write!(f, "the server sent an invalid response: {}", message)
+            }
+            ClientError::UnexpectedResponse(payload) => {
+                write!(f, "the server sent an unexpected response: {:?}", payload)
+            }
+            ClientError::Engine(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<EngineError> for ClientError {
+    fn from(error: EngineError) -> Self {
+        ClientError::Engine(error)
+    }
+}
+
+/// A typed client for the JSON protocol of the server
+pub struct Client<T: Transport> {
+    transport: T,
+    /// The token sent with every request, if the server requires one
+    token: Option<String>,
+    /// The session every request is evaluated in, or None for the default session
+    session: Option<String>,
+    /// How long a request may take, both to evaluate and to be answered
+    timeout: Option<Duration>,
+}
+
+impl<T: Transport> Client<T> {
+    /// Create a client that sends requests over a transport
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            token: None,
+            session: None,
+            timeout: None,
+        }
+    }
+
+    /// Authenticate every request with the token of the server
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Evaluate every request in the session with the given ID
+    pub fn with_session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// Stop evaluating a request, and stop waiting for its response, after the given time
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send a request and return the payload of its response. Error responses are
+    /// returned as `ClientError::Engine`
+    pub fn request(
+        &self,
+        request_type: RequestType,
+        payload: RequestPayload,
+    ) -> Result<ResponsePayload, ClientError> {
+        let mut request = ClientRequestMessage::new(request_type, payload);
+        if let Some(token) = &self.token {
+            request = request.with_token(token.as_str());
+        }
+        if let Some(session) = &self.session {
+            request = request.with_session(session.as_str());
+        }
+        if let Some(timeout) = self.timeout {
+            // a timeout of 0 would let the request run for any amount of time
+            request = request.with_timeout((timeout.as_millis() as u64).max(1));
+        }
+
+        let request = serde_json::to_string(&request)
+            .map_err(|err| ClientError::Transport(err.to_string()))?;
+        let response = self.transport.request(&request, self.timeout)?;
+        let response: ServerResponseMessage = serde_json::from_str(&response)
+            .map_err(|err| ClientError::InvalidResponse(err.to_string()))?;
+
+        match response.payload {
+            ResponsePayload::Error(error) => Err(ClientError::Engine(error)),
+            payload => Ok(payload),
+        }
+    }
+
+    /// Evaluate a command or push an item to the stack, and return the response
+    pub fn exec(&self, command: &str) -> Result<ResponsePayload, ClientError> {
+        self.request(RequestType::Input, RequestPayload::Input(command.into()))
+    }
+
+    /// Push an item to the stack, or evaluate a command that changes it, and return
+    /// the stack
+    pub fn push(&self, item: &str) -> Result<Vec<Bucket>, ClientError> {
+        expect_stack(self.exec(item)?)
+    }
+
+    /// Evaluate a list of commands in order as one entry in the undo history, and
+    /// return the stack
+    pub fn batch(&self, commands: &[&str]) -> Result<Vec<Bucket>, ClientError> {
+        expect_stack(self.request(
+            RequestType::Batch,
+            RequestPayload::Batch(commands.iter().map(|command| command.to_string()).collect()),
+        )?)
+    }
+
+    /// Get the stack
+    pub fn stack(&self) -> Result<Vec<Bucket>, ClientError> {
+        self.push("refresh")
+    }
+
+    /// Get the names of every available command
+    pub fn commands(&self) -> Result<Vec<String>, ClientError> {
+        match self.exec("commands")? {
+            ResponsePayload::Commands(commands) => Ok(commands),
+            payload => Err(ClientError::UnexpectedResponse(payload)),
+        }
+    }
+
+    /// Evaluate an algebraic expression as one command, and return its value
+    pub fn eval_algebraic(&self, expression: &str) -> Result<Option<Bucket>, ClientError> {
+        match self.request(
+            RequestType::Algebraic,
+            RequestPayload::Input(expression.into()),
+        )? {
+            ResponsePayload::Result(result) => Ok(result),
+            payload => Err(ClientError::UnexpectedResponse(payload)),
+        }
+    }
+
+    /// Send a configuration request and return its value
+    pub fn configuration(&self, payload: ConfigurationPayload) -> Result<Value, ClientError> {
+        match self.request(
+            RequestType::Configuration,
+            RequestPayload::Configuration(payload),
+        )? {
+            ResponsePayload::Configuration(value) => Ok(value),
+            payload => Err(ClientError::UnexpectedResponse(payload)),
+        }
+    }
+
+    /// Get a key from the config
+    pub fn get_config(&self, section: &str, key: &str) -> Result<Value, ClientError> {
+        self.configuration(ConfigurationPayload::new(
+            ConfigurationActionType::GetKey,
+            Some(section.into()),
+            Some(key.into()),
+            None,
+        ))
+    }
+
+    /// Set a key in the config
+    pub fn set_config(
+        &self,
+        section: &str,
+        key: &str,
+        value: toml::Value,
+    ) -> Result<(), ClientError> {
+        self.configuration(ConfigurationPayload::new(
+            ConfigurationActionType::SetKey,
+            Some(section.into()),
+            Some(key.into()),
+            Some(value),
+        ))
+        .map(|_| ())
+    }
+
+    /// Quit the session of the client, which stops the server for the default session
+    pub fn quit(&self) -> Result<(), ClientError> {
+        match self.exec("quit")? {
+            ResponsePayload::QuitSig(_) => Ok(()),
+            payload => Err(ClientError::UnexpectedResponse(payload)),
+        }
+    }
+}
+
+/// Get the stack from a response that should contain it
+fn expect_stack(payload: ResponsePayload) -> Result<Vec<Bucket>, ClientError> {
+    match payload {
+        ResponsePayload::Stack(stack) => Ok(stack),
+        payload => Err(ClientError::UnexpectedResponse(payload)),
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use nng::{
+    options::{Options, RecvTimeout},
+    Protocol, Socket,
+};
+
+use super::{ClientError, Transport};
+
+/// Sends requests to a server that uses the nng backend
+pub struct NanoMsgTransport {
+    socket: Socket,
+}
+
+impl NanoMsgTransport {
+    /// Connect to a server at an nng address such as `tcp://127.0.0.1:33242`
+    pub fn connect(address: &str) -> Result<Self, ClientError> {
+        let socket = Socket::new(Protocol::Req0).map_err(transport_error)?;
+        socket.dial(address).map_err(transport_error)?;
+        Ok(Self { socket })
+    }
+}
+
+impl Transport for NanoMsgTransport {
+    fn request(&self, request: &str, timeout: Option<Duration>) -> Result<String, ClientError> {
+        self.socket
+            .set_opt::<RecvTimeout>(timeout)
+            .map_err(transport_error)?;
+        self.socket
+            .send(request.as_bytes())
+            .map_err(|(_, err)| transport_error(err))?;
+
+        // a new request replaces one that timed out, so a late response is never received
+        let response = match self.socket.recv() {
+            Ok(response) => response,
+            Err(nng::Error::TimedOut) => return Err(ClientError::Timeout),
+            Err(err) => return Err(transport_error(err)),
+        };
+        String::from_utf8(response.to_vec())
+            .map_err(|err| ClientError::InvalidResponse(err.to_string()))
+    }
+}
+
+/// Convert an nng error to a client error
+fn transport_error(err: nng::Error) -> ClientError {
+    ClientError::Transport(err.to_string())
+}
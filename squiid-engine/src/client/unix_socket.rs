@@ -0,0 +1,84 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use super::{ClientError, Transport};
+
+/// Sends requests to a server that uses the Unix socket backend
+pub struct UnixSocketTransport {
+    path: PathBuf,
+    /// The connection to the server. It is opened again after a request fails, so that
+    /// a late response is not read as the response to the next request
+    connection: Mutex<Option<BufReader<UnixStream>>>,
+}
+
+impl UnixSocketTransport {
+    /// Connect to the socket file at an address, which may start with `unix://`
+    pub fn connect(address: &str) -> Result<Self, ClientError> {
+        let path = PathBuf::from(address.strip_prefix("unix://").unwrap_or(address));
+        let connection = UnixStream::connect(&path).map_err(transport_error)?;
+        Ok(Self {
+            path,
+            connection: Mutex::new(Some(BufReader::new(connection))),
+        })
+    }
+
+    /// Write a request line and read the response line
+    fn exchange(
+        connection: &mut BufReader<UnixStream>,
+        request: &str,
+        timeout: Option<Duration>,
+    ) -> Result<String, ClientError> {
+        connection
+            .get_ref()
+            .set_read_timeout(timeout)
+            .map_err(transport_error)?;
+        writeln!(connection.get_mut(), "{}", request).map_err(transport_error)?;
+
+        let mut response = String::new();
+        match connection.read_line(&mut response) {
+            Ok(0) => Err(ClientError::Transport(
+                "the server closed the connection".to_string(),
+            )),
+            Ok(_) => Ok(response.trim_end().to_string()),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                Err(ClientError::Timeout)
+            }
+            Err(err) => Err(transport_error(err)),
+        }
+    }
+}
+
+impl Transport for UnixSocketTransport {
+    fn request(&self, request: &str, timeout: Option<Duration>) -> Result<String, ClientError> {
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let stream = match connection.take() {
+            Some(stream) => stream,
+            None => BufReader::new(UnixStream::connect(&self.path).map_err(transport_error)?),
+        };
+        let stream = connection.insert(stream);
+
+        let response = Self::exchange(stream, request, timeout);
+        if response.is_err() {
+            *connection = None;
+        }
+        response
+    }
+}
+
+/// Convert an IO error to a client error
+fn transport_error(err: io::Error) -> ClientError {
+    ClientError::Transport(err.to_string())
+}
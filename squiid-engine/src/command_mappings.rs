@@ -1,79 +1,909 @@
-use std::{borrow::BorrowMut, collections::HashMap};
+use std::collections::HashMap;
 
-use crate::{engine::Engine, protocol::server_response::MessageAction};
+use serde::{Deserialize, Serialize};
 
-/// Insert a function and reference name into a hashmap
-macro_rules! function_map_entry {
-    ($function_map:expr,$name:expr,$func_name:ident) => {
-        $function_map.insert(
-            String::from($name),
-            Box::new(|engine: &mut Engine| engine.borrow_mut().$func_name()) as Box<EngineFunction>,
+use crate::{engine::Engine, error::EngineError, protocol::server_response::MessageAction};
+
+/// Insert a command, its metadata and the engine method that evaluates it into a registry.
+///
+/// Usage: `command_entry!(registry, name, method, category, [operand types], description)`,
+/// optionally followed by `aliases = [...]` and/or `algebraic = false`
+macro_rules! command_entry {
+    (
+        $registry:expr,
+        $name:literal,
+        $func_name:ident,
+        $category:ident,
+        [$($operand:ident),*],
+        $description:literal
+        $(, aliases = [$($alias:literal),*])?
+        $(, algebraic = $algebraic:literal)?
+    ) => {
+        $registry.register(
+            CommandInfo {
+                name: String::from($name),
+                operand_types: vec![$(OperandType::$operand),*],
+                category: CommandCategory::$category,
+                description: String::from($description),
+                aliases: vec![$($(String::from($alias)),*)?],
+                algebraic: command_entry!(@algebraic $($algebraic)?),
+            },
+            Box::new(|engine: &mut Engine| engine.$func_name()),
         )
     };
+    (@algebraic) => {
+        true
+    };
+    (@algebraic $algebraic:literal) => {
+        $algebraic
+    };
+}
+
+/// The function signature of every command
+pub type EngineFunction = dyn Fn(&mut Engine) -> Result<MessageAction, EngineError> + Send + Sync;
+
+/// Types of operands that a command can take from the stack
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperandType {
+    /// A number or a constant
+    Number,
+    /// A string, such as a variable name
+    String,
+    /// A list such as `[1, 2, 3]`
+    List,
+    /// A program such as `{ $k 2 power }`
+    Program,
+    /// Any type of item
+    Any,
+}
+
+/// Categories used to group commands in help screens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandCategory {
+    Arithmetic,
+    Functions,
+    Trigonometry,
+    Logarithms,
+    Logic,
+    Polynomials,
+    ContinuedFractions,
+    Modular,
+    Statistics,
+    Series,
+    Stack,
+    Variables,
+    History,
+    System,
+}
+
+/// Metadata describing a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandInfo {
+    /// The name used to run the command
+    pub name: String,
+    /// The types of the operands taken from the stack, ordered from the bottom of the stack
+    /// to the top. Commands that take a count, such as `ndrop`, only list the count
+    pub operand_types: Vec<OperandType>,
+    /// The category of the command
+    pub category: CommandCategory,
+    /// A short description of the command
+    pub description: String,
+    /// Alternative names that also run the command
+    pub aliases: Vec<String>,
+    /// Whether the command can be used in algebraic mode
+    pub algebraic: bool,
+}
+
+impl CommandInfo {
+    /// The number of operands the command takes from the stack
+    pub fn arity(&self) -> usize {
+        self.operand_types.len()
+    }
+}
+
+/// A command and the function that evaluates it
+pub struct Command {
+    /// Metadata describing the command
+    pub info: CommandInfo,
+    /// The function that evaluates the command
+    pub function: Box<EngineFunction>,
+}
+
+/// Registry of every available command. Commands can be looked up by name or by alias
+#[derive(Default)]
+pub struct CommandRegistry {
+    /// Commands indexed by name
+    commands: HashMap<String, Command>,
+    /// Map of aliases to command names
+    aliases: HashMap<String, String>,
+}
+
+impl CommandRegistry {
+    /// Add a command to the registry
+    pub fn register(&mut self, info: CommandInfo, function: Box<EngineFunction>) {
+        for alias in &info.aliases {
+            self.aliases.insert(alias.clone(), info.name.clone());
+        }
+        self.commands
+            .insert(info.name.clone(), Command { info, function });
+    }
+
+    /// Get a command by name or alias
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        match self.aliases.get(name) {
+            Some(command_name) => self.commands.get(command_name),
+            None => self.commands.get(name),
+        }
+    }
+
+    /// Test if a name or alias belongs to a command
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The names of every command, not including aliases
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.commands.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Every name and alias that can be used to run a command
+    pub fn names_and_aliases(&self) -> Vec<String> {
+        let mut names = self.names();
+        names.extend(self.aliases.keys().cloned());
+        names
+    }
+
+    /// The metadata of every command, sorted by name
+    pub fn info(&self) -> Vec<CommandInfo> {
+        self.names()
+            .iter()
+            .map(|name| self.commands[name].info.clone())
+            .collect()
+    }
 }
 
-type EngineFunction = dyn Fn(&mut Engine) -> Result<MessageAction, String>;
-pub type CommandsMap = HashMap<String, Box<dyn Fn(&mut Engine) -> Result<MessageAction, String>>>;
-
-/// Create a map of every available function and it's respective command
-pub fn create_function_map() -> HashMap<String, Box<EngineFunction>> {
-    let mut function_map = HashMap::new();
-
-    // Insert string keys and function objects into the hashmap
-
-    function_map_entry!(function_map, "add", add);
-    function_map_entry!(function_map, "subtract", subtract);
-    function_map_entry!(function_map, "divide", divide);
-    function_map_entry!(function_map, "multiply", multiply);
-    function_map_entry!(function_map, "power", power);
-    function_map_entry!(function_map, "sqrt", sqrt);
-    function_map_entry!(function_map, "mod", modulo);
-    function_map_entry!(function_map, "sin", sin);
-    function_map_entry!(function_map, "cos", cos);
-    function_map_entry!(function_map, "tan", tan);
-    function_map_entry!(function_map, "sec", sec);
-    function_map_entry!(function_map, "csc", csc);
-    function_map_entry!(function_map, "cot", cot);
-    function_map_entry!(function_map, "asin", asin);
-    function_map_entry!(function_map, "acos", acos);
-    function_map_entry!(function_map, "atan", atan);
-    function_map_entry!(function_map, "log", log);
-    function_map_entry!(function_map, "blog", blog);
-    function_map_entry!(function_map, "ln", ln);
-    function_map_entry!(function_map, "abs", abs);
-    function_map_entry!(function_map, "eq", eq);
-    function_map_entry!(function_map, "gt", gt);
-    function_map_entry!(function_map, "lt", lt);
-    function_map_entry!(function_map, "leq", geq);
-    function_map_entry!(function_map, "geq", leq);
-    function_map_entry!(function_map, "round", round);
-    function_map_entry!(function_map, "invert", invert);
-    function_map_entry!(function_map, "chs", chs);
-    function_map_entry!(function_map, "drop", drop);
-    function_map_entry!(function_map, "swap", swap);
-    function_map_entry!(function_map, "dup", dup);
-    function_map_entry!(function_map, "rolldown", roll_down);
-    function_map_entry!(function_map, "rollup", roll_up);
-    function_map_entry!(function_map, "store", store);
-    function_map_entry!(function_map, "purge", purge);
-    function_map_entry!(function_map, "invstore", invstore);
-    function_map_entry!(function_map, "clear", clear);
-    function_map_entry!(function_map, "clear", clear);
-    function_map_entry!(function_map, "undo", undo);
-    function_map_entry!(function_map, "redo", redo);
-    function_map_entry!(function_map, "commands", list_commands);
-    function_map_entry!(function_map, "quit", quit);
-    function_map_entry!(
-        function_map,
+/// Create a registry of every available command
+pub fn create_command_registry() -> CommandRegistry {
+    let mut registry = CommandRegistry::default();
+
+    // arithmetic
+    command_entry!(
+        registry,
+        "add",
+        add,
+        Arithmetic,
+        [Number, Number],
+        "Add two numbers"
+    );
+    command_entry!(
+        registry,
+        "subtract",
+        subtract,
+        Arithmetic,
+        [Number, Number],
+        "Subtract the top number from the second number"
+    );
+    command_entry!(
+        registry,
+        "divide",
+        divide,
+        Arithmetic,
+        [Number, Number],
+        "Divide the second number by the top number"
+    );
+    command_entry!(
+        registry,
+        "multiply",
+        multiply,
+        Arithmetic,
+        [Number, Number],
+        "Multiply two numbers"
+    );
+    command_entry!(
+        registry,
+        "power",
+        power,
+        Arithmetic,
+        [Number, Number],
+        "Raise the second number to the power of the top number"
+    );
+    command_entry!(
+        registry,
+        "mod",
+        modulo,
+        Arithmetic,
+        [Number, Number],
+        "Remainder of dividing the second number by the top number"
+    );
+    command_entry!(
+        registry,
+        "chs",
+        chs,
+        Arithmetic,
+        [Number],
+        "Change the sign of a number"
+    );
+    command_entry!(
+        registry,
+        "invert",
+        invert,
+        Arithmetic,
+        [Number],
+        "Divide 1 by a number",
+        algebraic = false
+    );
+
+    // functions
+    command_entry!(
+        registry,
+        "sqrt",
+        sqrt,
+        Functions,
+        [Number],
+        "Square root of a number"
+    );
+    command_entry!(
+        registry,
+        "abs",
+        abs,
+        Functions,
+        [Number],
+        "Absolute value of a number"
+    );
+    command_entry!(
+        registry,
+        "round",
+        round,
+        Functions,
+        [Number],
+        "Round a number to the nearest integer"
+    );
+
+    // trigonometry
+    command_entry!(
+        registry,
+        "sin",
+        sin,
+        Trigonometry,
+        [Number],
+        "Sine of an angle"
+    );
+    command_entry!(
+        registry,
+        "cos",
+        cos,
+        Trigonometry,
+        [Number],
+        "Cosine of an angle"
+    );
+    command_entry!(
+        registry,
+        "tan",
+        tan,
+        Trigonometry,
+        [Number],
+        "Tangent of an angle"
+    );
+    command_entry!(
+        registry,
+        "sec",
+        sec,
+        Trigonometry,
+        [Number],
+        "Secant of an angle"
+    );
+    command_entry!(
+        registry,
+        "csc",
+        csc,
+        Trigonometry,
+        [Number],
+        "Cosecant of an angle"
+    );
+    command_entry!(
+        registry,
+        "cot",
+        cot,
+        Trigonometry,
+        [Number],
+        "Cotangent of an angle"
+    );
+    command_entry!(
+        registry,
+        "asin",
+        asin,
+        Trigonometry,
+        [Number],
+        "Arcsine of a number"
+    );
+    command_entry!(
+        registry,
+        "acos",
+        acos,
+        Trigonometry,
+        [Number],
+        "Arccosine of a number"
+    );
+    command_entry!(
+        registry,
+        "atan",
+        atan,
+        Trigonometry,
+        [Number],
+        "Arctangent of a number"
+    );
+
+    // logarithms
+    command_entry!(
+        registry,
+        "log",
+        log,
+        Logarithms,
+        [Number],
+        "Base 10 logarithm of a number"
+    );
+    command_entry!(
+        registry,
+        "blog",
+        blog,
+        Logarithms,
+        [Number, Number],
+        "Logarithm of the second number in the base of the top number"
+    );
+    command_entry!(
+        registry,
+        "ln",
+        ln,
+        Logarithms,
+        [Number],
+        "Natural logarithm of a number"
+    );
+
+    // logic
+    command_entry!(
+        registry,
+        "eq",
+        eq,
+        Logic,
+        [Any, Any],
+        "1 if two items are equal, otherwise 0"
+    );
+    command_entry!(
+        registry,
+        "gt",
+        gt,
+        Logic,
+        [Number, Number],
+        "1 if the second number is greater than the top number, otherwise 0"
+    );
+    command_entry!(
+        registry,
+        "lt",
+        lt,
+        Logic,
+        [Number, Number],
+        "1 if the second number is less than the top number, otherwise 0"
+    );
+    command_entry!(
+        registry,
+        "geq",
+        geq,
+        Logic,
+        [Number, Number],
+        "1 if the second number is greater than or equal to the top number, otherwise 0"
+    );
+    command_entry!(
+        registry,
+        "leq",
+        leq,
+        Logic,
+        [Number, Number],
+        "1 if the second number is less than or equal to the top number, otherwise 0"
+    );
+
+    // polynomials
+    command_entry!(
+        registry,
+        "polyroots",
+        polyroots,
+        Polynomials,
+        [List],
+        "Every real and complex root of a polynomial"
+    );
+    command_entry!(
+        registry,
+        "polyval",
+        polyval,
+        Polynomials,
+        [List, Number],
+        "Evaluate a polynomial at a value of x"
+    );
+    command_entry!(
+        registry,
+        "polyder",
+        polyder,
+        Polynomials,
+        [List],
+        "Derivative of a polynomial"
+    );
+    command_entry!(
+        registry,
+        "polyint",
+        polyint,
+        Polynomials,
+        [List],
+        "Integral of a polynomial"
+    );
+    command_entry!(
+        registry,
+        "polymul",
+        polymul,
+        Polynomials,
+        [List, List],
+        "Multiply two polynomials"
+    );
+    command_entry!(
+        registry,
+        "polydiv",
+        polydiv,
+        Polynomials,
+        [List, List],
+        "Divide two polynomials, pushing the quotient and remainder"
+    );
+    command_entry!(
+        registry,
+        "polyfit",
+        polyfit,
+        Polynomials,
+        [List, List, Number],
+        "Least squares polynomial fit of x and y lists"
+    );
+
+    // continued fractions
+    command_entry!(
+        registry,
+        "tocf",
+        tocf,
+        ContinuedFractions,
+        [Number],
+        "Expand a number into a list of continued fraction terms"
+    );
+    command_entry!(
+        registry,
+        "fromcf",
+        fromcf,
+        ContinuedFractions,
+        [List],
+        "Evaluate a list of continued fraction terms"
+    );
+    command_entry!(
+        registry,
+        "approx",
+        approx,
+        ContinuedFractions,
+        [Number, Number],
+        "Closest fraction with a bounded denominator, pushing the numerator and denominator"
+    );
+
+    // modular arithmetic
+    command_entry!(
+        registry,
+        "setmod",
+        setmod,
+        Modular,
+        [Number],
+        "Enable modular arithmetic with the given modulus"
+    );
+    command_entry!(
+        registry,
+        "unsetmod",
+        unsetmod,
+        Modular,
+        [],
+        "Disable modular arithmetic"
+    );
+    command_entry!(
+        registry,
+        "getmod",
+        getmod,
+        Modular,
+        [],
+        "Send the active modulus to the client"
+    );
+
+    // statistics
+    command_entry!(
+        registry,
+        "statadd",
+        statadd,
+        Statistics,
+        [Number, Number],
+        "Add an (x, y) data point"
+    );
+    command_entry!(
+        registry,
+        "statdel",
+        statdel,
+        Statistics,
+        [Number, Number],
+        "Remove an (x, y) data point"
+    );
+    command_entry!(
+        registry,
+        "statclear",
+        statclear,
+        Statistics,
+        [],
+        "Remove every data point"
+    );
+    command_entry!(
+        registry,
+        "statlist",
+        statlist,
+        Statistics,
+        [],
+        "Send the data points to the client"
+    );
+    command_entry!(
+        registry,
+        "reglin",
+        reglin,
+        Statistics,
+        [],
+        "Linear regression y = a + b*x, pushing a and b"
+    );
+    command_entry!(
+        registry,
+        "regexp",
+        regexp,
+        Statistics,
+        [],
+        "Exponential regression y = a * e^(b*x), pushing a and b"
+    );
+    command_entry!(
+        registry,
+        "reglog",
+        reglog,
+        Statistics,
+        [],
+        "Logarithmic regression y = a + b*ln(x), pushing a and b"
+    );
+    command_entry!(
+        registry,
+        "regpow",
+        regpow,
+        Statistics,
+        [],
+        "Power regression y = a * x^b, pushing a and b"
+    );
+    command_entry!(
+        registry,
+        "rsquared",
+        rsquared,
+        Statistics,
+        [],
+        "Coefficient of determination of the current regression"
+    );
+    command_entry!(
+        registry,
+        "predict",
+        predict,
+        Statistics,
+        [Number],
+        "Predict y for a value of x using the current regression"
+    );
+
+    // series
+    command_entry!(
+        registry,
+        "sum",
+        sum,
+        Series,
+        [Program, String, Number, Number],
+        "Sum of a program over a range of integers"
+    );
+    command_entry!(
+        registry,
+        "prod",
+        prod,
+        Series,
+        [Program, String, Number, Number],
+        "Product of a program over a range of integers"
+    );
+
+    // stack manipulation
+    command_entry!(
+        registry,
+        "drop",
+        drop,
+        Stack,
+        [Any],
+        "Remove the top item",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "swap",
+        swap,
+        Stack,
+        [Any, Any],
+        "Swap the top two items",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "dup",
+        dup,
+        Stack,
+        [Any],
+        "Duplicate the top item",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "rolldown",
+        roll_down,
+        Stack,
+        [],
+        "Rotate every item down one place",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "rollup",
+        roll_up,
+        Stack,
+        [],
+        "Rotate every item up one place",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "over",
+        over,
+        Stack,
+        [Any, Any],
+        "Copy the second item to the top",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "rot",
+        rot,
+        Stack,
+        [Any, Any, Any],
+        "Move the third item to the top",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "nip",
+        nip,
+        Stack,
+        [Any, Any],
+        "Remove the second item",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "tuck",
+        tuck,
+        Stack,
+        [Any, Any],
+        "Copy the top item below the second item",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "pick",
+        pick,
+        Stack,
+        [Number],
+        "Copy the nth item to the top",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "nroll",
+        nroll,
+        Stack,
+        [Number],
+        "Move the nth item to the top",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "ndup",
+        ndup,
+        Stack,
+        [Number],
+        "Duplicate the top n items",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "ndrop",
+        ndrop,
+        Stack,
+        [Number],
+        "Remove the top n items",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "depth",
+        depth,
+        Stack,
+        [],
+        "Push the number of items on the stack",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "reverse",
+        reverse,
+        Stack,
+        [Number],
+        "Reverse the order of the top n items",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "keep",
+        keep,
+        Stack,
+        [Number],
+        "Remove every item except the top n items",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "clear",
+        clear,
+        Stack,
+        [],
+        "Remove every item",
+        algebraic = false
+    );
+
+    // variables
+    command_entry!(
+        registry,
+        "store",
+        store,
+        Variables,
+        [Any, String],
+        "Store the second item in the variable named by the top item"
+    );
+    command_entry!(
+        registry,
+        "invstore",
+        invstore,
+        Variables,
+        [String, Any],
+        "Store the top item in the variable named by the second item"
+    );
+    command_entry!(
+        registry,
+        "purge",
+        purge,
+        Variables,
+        [String],
+        "Delete a variable"
+    );
+
+    // history
+    command_entry!(
+        registry,
+        "undo",
+        undo,
+        History,
+        [],
+        "Undo the last command",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "redo",
+        redo,
+        History,
+        [],
+        "Redo the last undone command",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "branch",
+        branch,
+        History,
+        [],
+        "Redo into the previous branch of the undo history",
+        algebraic = false
+    );
+    command_entry!(
+        registry,
+        "checkpoint",
+        checkpoint,
+        History,
+        [String],
+        "Save the stack, variables and previous answer under a name"
+    );
+    command_entry!(
+        registry,
+        "restore",
+        restore,
+        History,
+        [String],
+        "Replace the stack, variables and previous answer with a checkpoint"
+    );
+    command_entry!(
+        registry,
+        "history",
+        history,
+        History,
+        [],
+        "Send a summary of the undo history to the client"
+    );
+
+    // system
+    command_entry!(
+        registry,
+        "commands",
+        list_commands,
+        System,
+        [],
+        "Send the name of every command to the client"
+    );
+    command_entry!(
+        registry,
+        "commandinfo",
+        command_info,
+        System,
+        [],
+        "Send the metadata of every command to the client"
+    );
+    command_entry!(
+        registry,
         "update_previous_answer",
-        update_previous_answer
+        update_previous_answer,
+        System,
+        [],
+        "Set the previous answer to the top item"
+    );
+    command_entry!(
+        registry,
+        "save",
+        save,
+        System,
+        [String],
+        "Save the full engine state to a file"
+    );
+    command_entry!(
+        registry,
+        "load",
+        load,
+        System,
+        [String],
+        "Replace the full engine state with a saved file"
     );
+    command_entry!(registry, "quit", quit, System, [], "Stop the server");
 
     // manually insert refresh since it doesn't use an engine method
-    function_map.insert(
-        String::from("refresh"),
+    registry.register(
+        CommandInfo {
+            name: String::from("refresh"),
+            operand_types: Vec::new(),
+            category: CommandCategory::System,
+            description: String::from("Send the stack to the client"),
+            aliases: Vec::new(),
+            algebraic: true,
+        },
         Box::new(|_engine: &mut Engine| Ok(MessageAction::SendStack)),
     );
 
-    function_map
+    registry
 }
@@ -1,5 +1,8 @@
 use directories::{BaseDirs, ProjectDirs};
-use std::{fs, io::Write, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use toml::Value;
 
 use crate::protocol::server_response::ConfigValue;
@@ -17,6 +20,15 @@ impl From<Value> for Config {
     }
 }
 
+impl Default for Config {
+    /// The default config that is shipped with Squiid
+    fn default() -> Self {
+        Self {
+            config: toml::from_str(include_str!("config.toml")).unwrap(),
+        }
+    }
+}
+
 impl Config {
     /// Get a section/key from the config
     pub fn get_key(&self, section: &str, key: &str) -> Result<ConfigValue, String> {
@@ -138,13 +150,12 @@ impl Config {
 
 /// Initialize the user config
 /// Tests if the user config exists, and if not, it is created
-pub fn init_config() {
-    let config_path = determine_config_path();
-    let config_exists = config_path.exists();
-
-    if !config_exists {
-        copy_default_config(config_path.clone());
+pub fn init_config() -> Result<(), String> {
+    let config_path = determine_config_path()?;
+    if !config_path.exists() {
+        copy_default_config(&config_path)?;
     }
+    Ok(())
 }
 
 // TODO: document this somewhere
@@ -157,80 +168,84 @@ pub fn init_config() {
 /// Windows: `C:\Users\<NAME>\AppData\Roaming\ImaginaryInfinity\Squiid\config`
 ///
 /// Anything else: See Linux
-pub fn determine_config_path() -> PathBuf {
+pub fn determine_config_path() -> Result<PathBuf, String> {
     // try to determine correct config path
     let config_directory =
         if let Some(proj_dirs) = ProjectDirs::from("net", "ImaginaryInfinity", "Squiid") {
-            let mut config_directory = proj_dirs.config_dir().to_path_buf();
-            config_directory.push("config.toml");
-            config_directory
+            proj_dirs.config_dir().join("config.toml")
 
         // couldn't determine config path, default to home directory .config folder
         } else {
-            let home_dir = BaseDirs::new().unwrap().home_dir().to_owned();
-            let config_directory: PathBuf = [
-                home_dir.to_str().unwrap(),
-                ".config",
-                "squiid",
-                "config.toml",
-            ]
-            .iter()
-            .collect();
-            config_directory
+            let base_dirs =
+                BaseDirs::new().ok_or("could not determine the home directory of the user")?;
+            base_dirs
+                .home_dir()
+                .join(".config")
+                .join("squiid")
+                .join("config.toml")
         };
 
-    let _ = fs::create_dir_all(config_directory.parent().unwrap());
-
-    config_directory
-}
-
-/// Copy default config file.
-/// Returns true if the config file exists, false if not
-fn copy_default_config(config_path: PathBuf) -> bool {
-    let mut file = fs::File::create(config_path.clone()).unwrap();
-    let _ = file.write_all(include_bytes!("config.toml"));
+    if let Some(parent) = config_directory.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
 
-    config_path.exists()
+    Ok(config_directory)
 }
 
-/// Read the config at the given path
-fn read_config(config_path: PathBuf) -> Option<Config> {
-    if config_path.exists() {
-        let contents = fs::read_to_string(config_path).unwrap();
-        let data: Value = toml::from_str(&contents).unwrap();
-        Some(Config { config: data })
-    } else {
-        None
-    }
+/// Copy the default config file to the given path
+fn copy_default_config(config_path: &Path) -> Result<(), String> {
+    fs::write(config_path, include_bytes!("config.toml")).map_err(|error| {
+        format!(
+            "could not create config file {}: {}",
+            config_path.display(),
+            error
+        )
+    })
 }
 
 /// Write config file to a given path
-fn write_config(config: Config, config_path: PathBuf) {
-    let config_string = toml::to_string_pretty(&config.config).unwrap();
-
-    let mut user_config_file = fs::File::create(config_path).unwrap();
-    user_config_file
-        .write_all(config_string.as_bytes())
-        .unwrap();
+fn write_config(config: &Config, config_path: &Path) -> Result<(), String> {
+    let config_string = toml::to_string_pretty(&config.config)
+        .map_err(|error| format!("could not serialize the config: {}", error))?;
+    fs::write(config_path, config_string).map_err(|error| {
+        format!(
+            "could not write config file {}: {}",
+            config_path.display(),
+            error
+        )
+    })
 }
 
 /// Function to read the user config file and update it with any new values
 /// that may have been added to the system config file
-pub fn read_user_config() -> Option<Config> {
-    let config_path = determine_config_path();
-    let mut user_config = match read_config(config_path.clone()) {
-        Some(config) => config,
-        None => return None,
-    };
-
-    let system_config: Value = toml::from_str(include_str!("config.toml")).unwrap();
+pub fn read_user_config() -> Result<Config, String> {
+    let config_path = determine_config_path()?;
+    let user_config = read_config_file(&config_path)?;
+    write_config(&user_config, &config_path)?;
+    Ok(user_config)
+}
 
-    // update the system config with the user's currently chosen config setup
-    update_toml_values(&mut user_config.config, &system_config);
+/// Read the config file at the given path without creating or modifying any files.
+/// Keys that are missing from the file are set to their default values
+pub fn read_config_file(config_path: &Path) -> Result<Config, String> {
+    let contents = fs::read_to_string(config_path).map_err(|error| {
+        format!(
+            "could not read config file {}: {}",
+            config_path.display(),
+            error
+        )
+    })?;
+    let mut data: Value = toml::from_str(&contents).map_err(|error| {
+        format!(
+            "could not parse config file {}: {}",
+            config_path.display(),
+            error
+        )
+    })?;
 
-    write_config(user_config.clone(), config_path);
+    update_toml_values(&mut data, &Config::default().config);
 
-    Some(user_config)
+    Ok(Config { config: data })
 }
 
 /// Recursive function to update TOML values
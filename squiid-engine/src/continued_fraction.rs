@@ -0,0 +1,112 @@
+// continued fraction helpers used by the tocf, fromcf and approx engine commands
+// values are handled as exact rationals built from the mantissa and scale of a Decimal
+
+use rust_decimal::Decimal;
+
+/// Maximum number of terms produced when expanding a value
+const MAX_TERMS: usize = 32;
+
+/// Relative error at which an expansion is considered to match its value.
+/// This hides the floating point noise of values such as 0.1 + 0.2
+const TOLERANCE: f64 = 1e-12;
+
+/// Convert a Decimal to an exact (numerator, denominator) pair with a positive denominator
+fn to_rational(value: Decimal) -> (i128, i128) {
+    (value.mantissa(), 10_i128.pow(value.scale()))
+}
+
+/// Expand a value into the terms of its simple continued fraction
+pub fn expand(value: Decimal) -> Vec<i128> {
+    let target = value.to_string().parse::<f64>().unwrap_or(0.0);
+    let (mut numerator, mut denominator) = to_rational(value);
+
+    // convergents h/k, seeded with 1/0 and 0/1
+    let (mut h_previous, mut h) = (0_i128, 1_i128);
+    let (mut k_previous, mut k) = (1_i128, 0_i128);
+
+    let mut terms = Vec::new();
+    while denominator != 0 && terms.len() < MAX_TERMS {
+        let term = numerator.div_euclid(denominator);
+        (numerator, denominator) = (denominator, numerator - term * denominator);
+
+        let next_h = term
+            .checked_mul(h)
+            .and_then(|value| value.checked_add(h_previous));
+        let next_k = term
+            .checked_mul(k)
+            .and_then(|value| value.checked_add(k_previous));
+        let (Some(next_h), Some(next_k)) = (next_h, next_k) else {
+            break;
+        };
+        (h_previous, h) = (h, next_h);
+        (k_previous, k) = (k, next_k);
+        terms.push(term);
+
+        if (h as f64 / k as f64 - target).abs() <= TOLERANCE * target.abs().max(1.0) {
+            break;
+        }
+    }
+
+    terms
+}
+
+/// Evaluate the terms of a simple continued fraction.
+/// Returns None if a term after the first is 0 or the calculation overflows
+pub fn evaluate(terms: &[Decimal]) -> Option<Decimal> {
+    let (last, rest) = terms.split_last()?;
+
+    let mut value = *last;
+    for term in rest.iter().rev() {
+        value = term.checked_add(Decimal::ONE.checked_div(value)?)?;
+    }
+
+    Some(value)
+}
+
+/// Find the fraction closest to a value whose denominator is no larger than the given bound.
+/// Returns a (numerator, denominator) pair
+pub fn best_approximation(value: Decimal, max_denominator: i128) -> (i128, i128) {
+    let (numerator, denominator) = to_rational(value);
+    if denominator <= max_denominator {
+        let divisor = gcd(numerator, denominator);
+        return (numerator / divisor, denominator / divisor);
+    }
+
+    // walk the convergents until the next one would exceed the bound
+    let (mut p0, mut q0, mut p1, mut q1) = (0_i128, 1_i128, 1_i128, 0_i128);
+    let (mut n, mut d) = (numerator, denominator);
+    while d != 0 {
+        let term = n.div_euclid(d);
+        let q2 = q0 + term * q1;
+        if q2 > max_denominator {
+            break;
+        }
+        (p0, q0, p1, q1) = (p1, q1, p0 + term * p1, q2);
+        (n, d) = (d, n - term * d);
+    }
+
+    // the best approximation is either the last convergent or the largest semiconvergent
+    let k = (max_denominator - q0) / q1;
+    let semiconvergent = (p0 + k * p1, q0 + k * q1);
+    let convergent = (p1, q1);
+
+    let distance = |(p, q): (i128, i128)| {
+        let p = Decimal::try_from_i128_with_scale(p, 0).ok()?;
+        let q = Decimal::try_from_i128_with_scale(q, 0).ok()?;
+        Some((p.checked_div(q)? - value).abs())
+    };
+    if distance(convergent) <= distance(semiconvergent) || distance(semiconvergent).is_none() {
+        convergent
+    } else {
+        semiconvergent
+    }
+}
+
+/// Greatest common divisor of two integers
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
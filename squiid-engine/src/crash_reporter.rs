@@ -3,11 +3,17 @@ use std::{
     fmt,
     fs::File,
     io::Write,
-    panic::{self, PanicInfo},
+    panic::{self, PanicHookInfo},
+    sync::Once,
+    thread,
 };
 
 use crate::config_handler;
 
+/// The name of the threads that answer requests. Panics on these threads are reported
+/// to the client instead of stopping the process
+pub const SERVER_THREAD_NAME: &str = "squiid-server";
+
 #[derive(Debug)]
 struct EnvironmentDetails<'a> {
     version: &'a str,
@@ -37,7 +43,24 @@ impl fmt::Display for EnvironmentDetails<'_> {
     }
 }
 
-pub fn crash_report(panic_info: &PanicInfo, write_dump_file: bool) {
+/// Write a crash report for panics on server threads, and pass every other panic to the
+/// hook that was set before. The process keeps running, so that an application embedding
+/// the engine is not stopped by it
+pub fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            if thread::current().name() == Some(SERVER_THREAD_NAME) {
+                crash_report(panic_info, true);
+            } else {
+                previous_hook(panic_info);
+            }
+        }));
+    });
+}
+
+pub fn crash_report(panic_info: &PanicHookInfo, write_dump_file: bool) {
     let backtrace = backtrace::Backtrace::new();
 
     // create environment struct
@@ -51,7 +74,7 @@ pub fn crash_report(panic_info: &PanicInfo, write_dump_file: bool) {
     };
 
     // print crash report for user
-    println!(
+    eprintln!(
         "{}\n\n{}\n\n{}\n\n{}\n\n{}\n{:?}\n{}",
         "-".repeat(70).yellow(),
         "---------- Crash Report Information ----------".red(),
@@ -64,9 +87,7 @@ pub fn crash_report(panic_info: &PanicInfo, write_dump_file: bool) {
 
     // determine the config directory to write the crash to
     if write_dump_file {
-        let result = panic::catch_unwind(config_handler::determine_config_path);
-
-        let config_directory = match result {
+        let config_directory = match config_handler::determine_config_path() {
             Ok(value) => value.parent().map(|path| path.to_path_buf()),
             Err(_) => match std::env::current_dir() {
                 Ok(value) => Some(value),
@@ -92,7 +113,7 @@ pub fn crash_report(panic_info: &PanicInfo, write_dump_file: bool) {
                 );
                 let write_result = file_unwrapped.write_all(crash_string.as_bytes());
                 if write_result.is_ok() {
-                    println!(
+                    eprintln!(
                         "Crash report written to: {}",
                         config_path_unwrapped.to_string_lossy()
                     );
@@ -101,5 +122,5 @@ pub fn crash_report(panic_info: &PanicInfo, write_dump_file: bool) {
         }
     }
 
-    println!("\n\nPlease report this issue at https://gitlab.com/ImaginaryInfinity/squiid-calculator/squiid/-/issues/new?issuable_template=Bug%20Report")
+    eprintln!("\n\nPlease report this issue at https://gitlab.com/ImaginaryInfinity/squiid-calculator/squiid/-/issues/new?issuable_template=Bug%20Report")
 }
@@ -1,52 +1,83 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
 
-use rust_decimal::{prelude::ToPrimitive, Decimal, MathematicalOps};
+use rust_decimal::{
+    prelude::{FromPrimitive, ToPrimitive},
+    Decimal, MathematicalOps,
+};
 use rust_decimal_macros::dec;
 
 use crate::{
-    bucket::{build_exposed_constants, Bucket, BucketTypes, ConstantTypes},
-    config_handler,
-    protocol::server_response::MessageAction,
+    bucket::{
+        build_exposed_constants, parse_list_literal, parse_program_literal, Bucket, BucketTypes,
+        ConstantTypes,
+    },
+    command_mappings::{self, CommandRegistry},
+    config_handler, continued_fraction,
+    error::{EngineError, ErrorKind},
+    history::{Changes, Checkpoint, History},
+    interrupt::Interrupt,
+    modular, polynomial,
+    protocol::server_response::{ConfigValue, MessageAction},
+    session::{self, Session},
+    statistics::{RegressionModel, Statistics},
     utils::{ID_REGEX, NUMERIC_REGEX},
 };
 
+/// Maximum number of iterations allowed in a `sum` or `prod` range
+const MAX_RANGE_ITERATIONS: i64 = 1_000_000;
+
+/// Number of commands kept in the undo history if `undo_depth` is not configured
+const DEFAULT_UNDO_DEPTH: usize = 100;
+
+/// Number of seconds a request may take to evaluate if `request_timeout` is not configured
+const DEFAULT_REQUEST_TIMEOUT: u64 = 60;
+
 /// Evaluation engine struct
 pub struct Engine {
     /// The stack of bucket items
     pub stack: Vec<Bucket>,
     /// Hashmap of set variables
     pub variables: HashMap<String, Bucket>,
-    /// History vecdeque for undo support
-    pub undo_history: VecDeque<Vec<Bucket>>,
-    /// Variables vecdeque for undo support
-    pub undo_variable_history: VecDeque<HashMap<String, Bucket>>,
-    /// Offset pointer to the current index of the undo history.
-    /// Index will be calculated by history.len() - pointer - 1
-    pub undo_state_pointer: u8,
+    /// Undo and redo history
+    pub history: History,
+    /// Named copies of the engine state
+    pub checkpoints: HashMap<String, Checkpoint>,
     /// Previous answer
     pub previous_answer: Bucket,
+    /// Accumulated two-variable statistics data
+    pub statistics: Statistics,
+    /// Active modulus of modular arithmetic mode, if enabled
+    pub modulus: Option<u64>,
+    /// Tokens of the program that is currently being entered between `{` and `}`
+    pub program_recording: Vec<String>,
+    /// Brace nesting depth of the program being entered. 0 when no program is being entered
+    pub program_depth: usize,
     /// Configuration struct
     pub config: config_handler::Config,
+    /// Cancellation flag and deadline of the running command
+    pub interrupt: Interrupt,
+    /// Directory that the `save` and `load` commands use for session files
+    pub data_directory: PathBuf,
 }
 
 /// Evaluation engine implementation
 impl Engine {
-    /// Helper to construct a new engine object
+    /// Helper to construct a new engine object with the user's config file.
+    /// The config file is created if it does not exist yet
+    ///
+    /// # Panics
+    /// If the user's config file can not be read. Use `EngineBuilder` to
+    /// construct an engine without touching the filesystem
     pub fn new() -> Engine {
-        config_handler::init_config();
-        Engine {
-            stack: Vec::new(),
-            variables: HashMap::new(),
-            undo_history: VecDeque::new(),
-            undo_variable_history: VecDeque::new(),
-            undo_state_pointer: 0,
-            previous_answer: Bucket::from(0),
-            config: config_handler::read_user_config().unwrap(),
-        }
+        EngineBuilder::new().user_config().build().unwrap()
     }
 
     /// Add item to stack
-    pub fn add_item_to_stack(&mut self, item: Bucket) -> Result<MessageAction, String> {
+    pub fn add_item_to_stack(&mut self, item: Bucket) -> Result<MessageAction, EngineError> {
         // Convert item to string
         let mut item_string = item.to_string();
 
@@ -64,7 +95,12 @@ impl Engine {
 
             match unresolved_var {
                 Some(value) => item_string = value.to_string(),
-                None => return Err(format!("reference to undefined variable: {}", item_string)),
+                None => {
+                    return Err(EngineError::new(
+                        ErrorKind::UndefinedVariable,
+                        format!("reference to undefined variable: {}", item_string),
+                    ))
+                }
             }
         }
 
@@ -82,6 +118,10 @@ impl Engine {
                 // test all other options
                 if exposed_constants.contains_key(item_string.as_str()) {
                     Bucket::from_constant(*exposed_constants.get(item_string.as_str()).unwrap())
+                } else if let Some(items) = parse_list_literal(&item_string) {
+                    Bucket::from_list(items)
+                } else if let Some(tokens) = parse_program_literal(&item_string) {
+                    Bucket::from_program(&tokens)
                 } else if NUMERIC_REGEX.is_match(&item_string) {
                     Bucket::from(item_string.parse::<f64>().unwrap())
                 } else {
@@ -97,18 +137,22 @@ impl Engine {
     }
 
     /// Get operands from stack as float
-    pub fn get_operands_as_f(&mut self, number: i32) -> Result<Vec<f64>, String> {
+    pub fn get_operands_as_f(&mut self, number: i32) -> Result<Vec<f64>, EngineError> {
         // Make sure there are actually enough items on the stack
         if self.stack.len() as i32 >= number {
             // Create vector to store operands
             let mut operands = Vec::new();
             // check that all items are of expected type
             let requested_operands = &self.stack[self.stack.len() - number as usize..];
-            for item in requested_operands {
+            for (index, item) in requested_operands.iter().enumerate() {
                 match item.bucket_type {
-                    BucketTypes::String | BucketTypes::Undefined => {
-                        return Err(String::from(
+                    BucketTypes::String
+                    | BucketTypes::List
+                    | BucketTypes::Program
+                    | BucketTypes::Undefined => {
+                        return Err(EngineError::type_mismatch(
                             "The operation cannot be performed on these operands",
+                            number as usize - index,
                         ));
                     }
                     _ => (),
@@ -123,30 +167,39 @@ impl Engine {
                     BucketTypes::Float | BucketTypes::Constant(_) => {
                         operand.value.unwrap().parse::<f64>().unwrap()
                     }
-                    _ => return Err(String::from("you should never get this error")),
+                    _ => {
+                        return Err(EngineError::new(
+                            ErrorKind::Internal,
+                            "you should never get this error",
+                        ))
+                    }
                 });
             }
             // Make the new vector's order match the stack
             operands.reverse();
             Ok(operands)
         } else {
-            Err(String::from("Not enough items on stack for operation"))
+            Err(EngineError::stack_underflow(number as usize))
         }
     }
 
     /// Get operands as a decimal object
-    pub fn get_operands_as_dec(&mut self, number: i32) -> Result<Vec<Decimal>, String> {
+    pub fn get_operands_as_dec(&mut self, number: i32) -> Result<Vec<Decimal>, EngineError> {
         // Make sure there are actually enough items on the stack
         if self.stack.len() as i32 >= number {
             // Create vector to store operands
             let mut operands = Vec::new();
             // check that all items are of expected type
             let requested_operands = &self.stack[self.stack.len() - number as usize..];
-            for item in requested_operands {
+            for (index, item) in requested_operands.iter().enumerate() {
                 match item.bucket_type {
-                    BucketTypes::String | BucketTypes::Undefined => {
-                        return Err(String::from(
+                    BucketTypes::String
+                    | BucketTypes::List
+                    | BucketTypes::Program
+                    | BucketTypes::Undefined => {
+                        return Err(EngineError::type_mismatch(
                             "The operation cannot be performed on these operands",
+                            number as usize - index,
                         ));
                     }
                     _ => (),
@@ -172,11 +225,20 @@ impl Engine {
                     | BucketTypes::Constant(ConstantTypes::PHI) => {
                         match Decimal::from_str_exact(&operand.value.unwrap()) {
                             Ok(value) => value,
-                            Err(e) => return Err(e.to_string()),
+                            Err(e) => {
+                                return Err(EngineError::new(ErrorKind::Overflow, e.to_string())
+                                    .at([operands.len() + 1]))
+                            }
                         }
                     }
-                    BucketTypes::String | BucketTypes::Undefined => {
-                        return Err(String::from("you should never get this error"))
+                    BucketTypes::String
+                    | BucketTypes::List
+                    | BucketTypes::Program
+                    | BucketTypes::Undefined => {
+                        return Err(EngineError::new(
+                            ErrorKind::Internal,
+                            "you should never get this error",
+                        ))
                     }
                 });
             }
@@ -184,12 +246,12 @@ impl Engine {
             operands.reverse();
             Ok(operands)
         } else {
-            Err(String::from("Not enough items on stack for operation"))
+            Err(EngineError::stack_underflow(number as usize))
         }
     }
 
     /// Get operands as a string
-    pub fn get_operands_as_string(&mut self, number: i32) -> Result<Vec<String>, String> {
+    pub fn get_operands_as_string(&mut self, number: i32) -> Result<Vec<String>, EngineError> {
         // Make sure there are actually enough items on the stack
         if self.stack.len() as i32 >= number {
             // Create vector to store operands
@@ -206,12 +268,12 @@ impl Engine {
             operands.reverse();
             Ok(operands)
         } else {
-            Err(String::from("Not enough items on stack for operation"))
+            Err(EngineError::stack_underflow(number as usize))
         }
     }
 
     /// Get the raw Buckets from the stack
-    pub fn get_operands_raw(&mut self, number: i32) -> Result<Vec<Bucket>, String> {
+    pub fn get_operands_raw(&mut self, number: i32) -> Result<Vec<Bucket>, EngineError> {
         if self.stack.len() as i32 >= number {
             // Create vector to store operands
             let mut operands = Vec::new();
@@ -226,23 +288,118 @@ impl Engine {
             operands.reverse();
             Ok(operands)
         } else {
-            Err(String::from("Not enough items on stack for operation"))
+            Err(EngineError::stack_underflow(number as usize))
         }
     }
 
+    /// Get a list from the stack as floats without removing it.
+    /// A depth of 0 is the top of the stack
+    pub fn peek_list_as_f(&self, depth: usize) -> Result<Vec<f64>, EngineError> {
+        if self.stack.len() <= depth {
+            return Err(EngineError::stack_underflow(depth + 1));
+        }
+
+        let item = &self.stack[self.stack.len() - depth - 1];
+        let items = match item.list_items() {
+            Some(items) => items,
+            None => {
+                return Err(EngineError::type_mismatch(
+                    "The operation requires a list operand",
+                    depth + 1,
+                ))
+            }
+        };
+
+        items
+            .iter()
+            .map(|element| match element.bucket_type {
+                BucketTypes::Float => Ok(element.to_string().parse::<f64>().unwrap()),
+                _ => Err(EngineError::type_mismatch(
+                    format!("list item `{}` is not a number", element.to_string()),
+                    depth + 1,
+                )),
+            })
+            .collect()
+    }
+
+    /// Get a number from the stack as a float without removing it.
+    /// A depth of 0 is the top of the stack
+    pub fn peek_as_f(&self, depth: usize) -> Result<f64, EngineError> {
+        if self.stack.len() <= depth {
+            return Err(EngineError::stack_underflow(depth + 1));
+        }
+
+        let item = &self.stack[self.stack.len() - depth - 1];
+        match item.bucket_type {
+            BucketTypes::Float | BucketTypes::Constant(_) => {
+                Ok(item.to_string().parse::<f64>().unwrap())
+            }
+            _ => Err(EngineError::type_mismatch(
+                "The operation cannot be performed on these operands",
+                depth + 1,
+            )),
+        }
+    }
+
+    /// Get a count argument from the top of the stack without removing it.
+    /// The count must be a non-negative integer no larger than the number of items below it
+    pub fn peek_count(&self) -> Result<usize, EngineError> {
+        let count = self.peek_as_f(0)?;
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(EngineError::domain("count must be a non-negative integer").at([1]));
+        }
+
+        let count = count as usize;
+        if count > self.stack.len() - 1 {
+            return Err(EngineError::stack_underflow(count + 1));
+        }
+        Ok(count)
+    }
+
     /// Update the previous answer variable
     /// TODO: document that this function needs to be called a lot
-    pub fn update_previous_answer(&mut self) -> Result<MessageAction, String> {
+    pub fn update_previous_answer(&mut self) -> Result<MessageAction, EngineError> {
         if !self.stack.is_empty() {
             self.previous_answer = self.stack.last().unwrap().clone();
             Ok(MessageAction::SendPrevAnswer)
         } else {
-            Err(String::from("stack is empty"))
+            Err(EngineError::new(ErrorKind::StackUnderflow, "stack is empty").at([1]))
+        }
+    }
+
+    /// Perform a binary operation on integer operands in modular arithmetic mode
+    fn modular_operation(
+        &mut self,
+        modulus: u64,
+        operation: fn(i128, i128, u64) -> Result<u64, String>,
+    ) -> Result<MessageAction, EngineError> {
+        let operands = self.get_operands_as_dec(2)?;
+
+        let mut integers = Vec::new();
+        for operand in operands {
+            match operand.to_i128() {
+                Some(value) if operand.fract() == dec!(0.0) => integers.push(value),
+                _ => {
+                    return Err(
+                        EngineError::domain("modular arithmetic requires integer operands")
+                            .at([1, 2]),
+                    )
+                }
+            }
         }
+
+        let result = operation(integers[0], integers[1], modulus)
+            .map_err(|error| EngineError::domain(error).at([1, 2]))?;
+        let _ = self.add_item_to_stack(result.into());
+        Ok(MessageAction::SendStack)
     }
 
     /// Add
-    pub fn add(&mut self) -> Result<MessageAction, String> {
+    pub fn add(&mut self) -> Result<MessageAction, EngineError> {
+        if let Some(modulus) = self.modulus {
+            return self.modular_operation(modulus, modular::add);
+        }
+
         let operands = match self.get_operands_as_dec(2) {
             Ok(content) => content,
             Err(error) => return Err(error),
@@ -255,7 +412,11 @@ impl Engine {
     }
 
     /// Subtract
-    pub fn subtract(&mut self) -> Result<MessageAction, String> {
+    pub fn subtract(&mut self) -> Result<MessageAction, EngineError> {
+        if let Some(modulus) = self.modulus {
+            return self.modular_operation(modulus, modular::subtract);
+        }
+
         // Get operands
         let operands = match self.get_operands_as_dec(2) {
             Ok(content) => content,
@@ -269,7 +430,11 @@ impl Engine {
     }
 
     /// Multiply
-    pub fn multiply(&mut self) -> Result<MessageAction, String> {
+    pub fn multiply(&mut self) -> Result<MessageAction, EngineError> {
+        if let Some(modulus) = self.modulus {
+            return self.modular_operation(modulus, modular::multiply);
+        }
+
         // Get operands
         let operands = match self.get_operands_as_dec(2) {
             Ok(content) => content,
@@ -296,7 +461,11 @@ impl Engine {
     }
 
     /// Divide
-    pub fn divide(&mut self) -> Result<MessageAction, String> {
+    pub fn divide(&mut self) -> Result<MessageAction, EngineError> {
+        if let Some(modulus) = self.modulus {
+            return self.modular_operation(modulus, modular::divide);
+        }
+
         // Get operands
         let operands = match self.get_operands_as_dec(2) {
             Ok(content) => content,
@@ -304,7 +473,7 @@ impl Engine {
         };
 
         if operands[1] == dec!(0.0) {
-            return Err("cannot divide by 0".to_string());
+            return Err(EngineError::new(ErrorKind::DivideByZero, "cannot divide by 0").at([1]));
         }
 
         // check for pi/x in order to replace with constants
@@ -339,7 +508,11 @@ impl Engine {
     }
 
     /// Power
-    pub fn power(&mut self) -> Result<MessageAction, String> {
+    pub fn power(&mut self) -> Result<MessageAction, EngineError> {
+        if let Some(modulus) = self.modulus {
+            return self.modular_operation(modulus, modular::power);
+        }
+
         // Get operands
         let operands = match self.get_operands_as_dec(2) {
             Ok(content) => content,
@@ -355,7 +528,13 @@ impl Engine {
             // is not a decimal
             match base.checked_powd(exponent) {
                 Some(value) => value.to_f64().unwrap(),
-                None => return Err("overflow when raising to a power".to_string()),
+                None => {
+                    return Err(EngineError::new(
+                        ErrorKind::Overflow,
+                        "overflow when raising to a power",
+                    )
+                    .at([1, 2]))
+                }
             }
         } else {
             // is a decimal
@@ -368,7 +547,7 @@ impl Engine {
     }
 
     /// Square root
-    pub fn sqrt(&mut self) -> Result<MessageAction, String> {
+    pub fn sqrt(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_dec(1) {
             Ok(content) => content,
@@ -378,14 +557,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].sqrt() {
             Some(value) => value,
-            None => return Err("Error calculating sqrt".to_string()),
+            None => return Err(EngineError::domain("Error calculating sqrt").at([1])),
         };
         let _ = self.add_item_to_stack(result.into());
         Ok(MessageAction::SendStack)
     }
 
     /// Modulo
-    pub fn modulo(&mut self) -> Result<MessageAction, String> {
+    pub fn modulo(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(2) {
             Ok(content) => content,
@@ -399,7 +578,7 @@ impl Engine {
     }
 
     /// Sine
-    pub fn sin(&mut self) -> Result<MessageAction, String> {
+    pub fn sin(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -409,14 +588,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].sin() {
             Some(value) => value,
-            None => return Err("could not sin operand".to_string()),
+            None => return Err(EngineError::type_mismatch("could not sin operand", 1)),
         };
         let _ = self.add_item_to_stack(result);
         Ok(MessageAction::SendStack)
     }
 
     /// Cosine
-    pub fn cos(&mut self) -> Result<MessageAction, String> {
+    pub fn cos(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -426,14 +605,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].cos() {
             Some(value) => value,
-            None => return Err("could not cos operand".to_string()),
+            None => return Err(EngineError::type_mismatch("could not cos operand", 1)),
         };
         let _ = self.add_item_to_stack(result);
         Ok(MessageAction::SendStack)
     }
 
     /// Tangent
-    pub fn tan(&mut self) -> Result<MessageAction, String> {
+    pub fn tan(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -442,14 +621,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].tan() {
             Some(value) => value,
-            None => return Err("could not tan operand".to_string()),
+            None => return Err(EngineError::type_mismatch("could not tan operand", 1)),
         };
         let _ = self.add_item_to_stack(result);
         Ok(MessageAction::SendStack)
     }
 
     /// Secant
-    pub fn sec(&mut self) -> Result<MessageAction, String> {
+    pub fn sec(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -459,14 +638,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].sec() {
             Some(value) => value,
-            None => return Err("could not sec operand".to_string()),
+            None => return Err(EngineError::type_mismatch("could not sec operand", 1)),
         };
         let _ = self.add_item_to_stack(result);
         Ok(MessageAction::SendStack)
     }
 
     /// Cosecant
-    pub fn csc(&mut self) -> Result<MessageAction, String> {
+    pub fn csc(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -476,14 +655,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].csc() {
             Some(value) => value,
-            None => return Err("could not csc operand".to_string()),
+            None => return Err(EngineError::type_mismatch("could not csc operand", 1)),
         };
         let _ = self.add_item_to_stack(result);
         Ok(MessageAction::SendStack)
     }
 
     /// Cotangent
-    pub fn cot(&mut self) -> Result<MessageAction, String> {
+    pub fn cot(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -493,14 +672,14 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].cot() {
             Some(value) => value,
-            None => return Err("could not sine operand".to_string()),
+            None => return Err(EngineError::type_mismatch("could not sine operand", 1)),
         };
         let _ = self.add_item_to_stack(result);
         Ok(MessageAction::SendStack)
     }
 
     /// Asin
-    pub fn asin(&mut self) -> Result<MessageAction, String> {
+    pub fn asin(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -513,7 +692,7 @@ impl Engine {
     }
 
     /// Acos
-    pub fn acos(&mut self) -> Result<MessageAction, String> {
+    pub fn acos(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -526,7 +705,7 @@ impl Engine {
     }
 
     /// Atan
-    pub fn atan(&mut self) -> Result<MessageAction, String> {
+    pub fn atan(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -539,7 +718,7 @@ impl Engine {
     }
 
     /// Change sign
-    pub fn chs(&mut self) -> Result<MessageAction, String> {
+    pub fn chs(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -553,7 +732,7 @@ impl Engine {
     }
 
     /// Logarithm
-    pub fn log(&mut self) -> Result<MessageAction, String> {
+    pub fn log(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_dec(1) {
             Ok(content) => content,
@@ -563,14 +742,18 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].checked_log10() {
             Some(value) => value,
-            None => return Err("cannot take log10 of 0 or negative numbers".to_string()),
+            None => {
+                return Err(
+                    EngineError::domain("cannot take log10 of 0 or negative numbers").at([1]),
+                )
+            }
         };
         let _ = self.add_item_to_stack(result.into());
         Ok(MessageAction::SendStack)
     }
 
     /// Logarithm with custom base using the change of base formula
-    pub fn blog(&mut self) -> Result<MessageAction, String> {
+    pub fn blog(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_dec(2) {
             Ok(content) => content,
@@ -582,16 +765,27 @@ impl Engine {
 
         let top_log = match operands[0].checked_log10() {
             Some(value) => value,
-            None => return Err("cannot take log of 0 or negative numbers".to_string()),
+            None => {
+                return Err(EngineError::domain("cannot take log of 0 or negative numbers").at([2]))
+            }
         };
         let bottom_log = match operands[1].checked_log10() {
             Some(value) => value,
-            None => return Err("cannot take log with base of 0 or negative numbers".to_string()),
+            None => {
+                return Err(EngineError::domain(
+                    "cannot take log with base of 0 or negative numbers",
+                )
+                .at([1]))
+            }
         };
 
         let result = match top_log.checked_div(bottom_log) {
             Some(value) => value,
-            None => return Err("cannot divide by zero".to_string()),
+            None => {
+                return Err(
+                    EngineError::new(ErrorKind::DivideByZero, "cannot divide by zero").at([1]),
+                )
+            }
         };
 
         // Put result on stack
@@ -600,7 +794,7 @@ impl Engine {
     }
 
     /// Natural logarihm
-    pub fn ln(&mut self) -> Result<MessageAction, String> {
+    pub fn ln(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_dec(1) {
             Ok(content) => content,
@@ -610,14 +804,18 @@ impl Engine {
         // Put result on stack
         let result = match operands[0].checked_ln() {
             Some(value) => value,
-            None => return Err("cannot take log10 of 0 or negative numbers".to_string()),
+            None => {
+                return Err(
+                    EngineError::domain("cannot take log10 of 0 or negative numbers").at([1]),
+                )
+            }
         };
         let _ = self.add_item_to_stack(result.into());
         Ok(MessageAction::SendStack)
     }
 
     /// Absolute value
-    pub fn abs(&mut self) -> Result<MessageAction, String> {
+    pub fn abs(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -630,7 +828,7 @@ impl Engine {
     }
 
     /// Equal to
-    pub fn eq(&mut self) -> Result<MessageAction, String> {
+    pub fn eq(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         // TODO: maybe make this work with strings
         let operands = match self.get_operands_as_f(2) {
@@ -645,7 +843,7 @@ impl Engine {
     }
 
     /// Greater than
-    pub fn gt(&mut self) -> Result<MessageAction, String> {
+    pub fn gt(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(2) {
             Ok(content) => content,
@@ -659,7 +857,7 @@ impl Engine {
     }
 
     /// Less than
-    pub fn lt(&mut self) -> Result<MessageAction, String> {
+    pub fn lt(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(2) {
             Ok(content) => content,
@@ -673,7 +871,7 @@ impl Engine {
     }
 
     /// Greater than or equal to
-    pub fn geq(&mut self) -> Result<MessageAction, String> {
+    pub fn geq(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(2) {
             Ok(content) => content,
@@ -687,7 +885,7 @@ impl Engine {
     }
 
     /// Less than or equal to
-    pub fn leq(&mut self) -> Result<MessageAction, String> {
+    pub fn leq(&mut self) -> Result<MessageAction, EngineError> {
         // Get operands
         let operands = match self.get_operands_as_f(2) {
             Ok(content) => content,
@@ -701,7 +899,7 @@ impl Engine {
     }
 
     /// Round to nearest int
-    pub fn round(&mut self) -> Result<MessageAction, String> {
+    pub fn round(&mut self) -> Result<MessageAction, EngineError> {
         // Get operand
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -714,7 +912,7 @@ impl Engine {
     }
 
     /// Calculate 1/x
-    pub fn invert(&mut self) -> Result<MessageAction, String> {
+    pub fn invert(&mut self) -> Result<MessageAction, EngineError> {
         // Get operand
         let operands = match self.get_operands_as_f(1) {
             Ok(content) => content,
@@ -726,15 +924,509 @@ impl Engine {
         Ok(MessageAction::SendStack)
     }
 
+    /// Find all real and complex roots of a polynomial
+    pub fn polyroots(&mut self) -> Result<MessageAction, EngineError> {
+        let coefficients = self.peek_list_as_f(0)?;
+
+        let roots = match polynomial::roots(&coefficients, &self.interrupt)? {
+            Some(value) => value,
+            None => {
+                return Err(
+                    EngineError::domain("cannot find the roots of a constant polynomial").at([1]),
+                )
+            }
+        };
+        self.stack.pop();
+
+        // real roots are pushed as numbers, complex roots as strings like 1+2i
+        let root_buckets = roots
+            .iter()
+            .map(|root| {
+                if root.is_real() {
+                    Bucket::from(root.re)
+                } else {
+                    Bucket::from(root.to_string())
+                }
+            })
+            .collect();
+
+        let _ = self.add_item_to_stack(Bucket::from_list(root_buckets));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Evaluate a polynomial at a given x
+    pub fn polyval(&mut self) -> Result<MessageAction, EngineError> {
+        let x = self.peek_as_f(0)?;
+        let coefficients = self.peek_list_as_f(1)?;
+        self.stack.truncate(self.stack.len() - 2);
+
+        let result = polynomial::evaluate(&coefficients, x);
+        let _ = self.add_item_to_stack(result.into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Differentiate a polynomial
+    pub fn polyder(&mut self) -> Result<MessageAction, EngineError> {
+        let coefficients = self.peek_list_as_f(0)?;
+        self.stack.pop();
+
+        let result = polynomial::derivative(&coefficients);
+        let _ = self.add_item_to_stack(Bucket::from_list(
+            result.into_iter().map(Bucket::from).collect(),
+        ));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Integrate a polynomial, using 0 as the integration constant
+    pub fn polyint(&mut self) -> Result<MessageAction, EngineError> {
+        let coefficients = self.peek_list_as_f(0)?;
+        self.stack.pop();
+
+        let result = polynomial::integral(&coefficients);
+        let _ = self.add_item_to_stack(Bucket::from_list(
+            result.into_iter().map(Bucket::from).collect(),
+        ));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Multiply two polynomials
+    pub fn polymul(&mut self) -> Result<MessageAction, EngineError> {
+        let right = self.peek_list_as_f(0)?;
+        let left = self.peek_list_as_f(1)?;
+        self.stack.truncate(self.stack.len() - 2);
+
+        let result = polynomial::multiply(&left, &right);
+        let _ = self.add_item_to_stack(Bucket::from_list(
+            result.into_iter().map(Bucket::from).collect(),
+        ));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Divide two polynomials, pushing the quotient and then the remainder
+    pub fn polydiv(&mut self) -> Result<MessageAction, EngineError> {
+        let divisor = self.peek_list_as_f(0)?;
+        let dividend = self.peek_list_as_f(1)?;
+
+        let (quotient, remainder) = match polynomial::divide(&dividend, &divisor) {
+            Some(value) => value,
+            None => {
+                return Err(EngineError::new(
+                    ErrorKind::DivideByZero,
+                    "cannot divide by the zero polynomial",
+                )
+                .at([1]))
+            }
+        };
+        self.stack.truncate(self.stack.len() - 2);
+
+        let _ = self.add_item_to_stack(Bucket::from_list(
+            quotient.into_iter().map(Bucket::from).collect(),
+        ));
+        let _ = self.add_item_to_stack(Bucket::from_list(
+            remainder.into_iter().map(Bucket::from).collect(),
+        ));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Least-squares polynomial fit of x and y data lists with a given degree
+    pub fn polyfit(&mut self) -> Result<MessageAction, EngineError> {
+        let degree = self.peek_as_f(0)?;
+        let y_values = self.peek_list_as_f(1)?;
+        let x_values = self.peek_list_as_f(2)?;
+
+        if degree < 0.0 || degree.fract() != 0.0 {
+            return Err(
+                EngineError::domain("polynomial degree must be a non-negative integer").at([1]),
+            );
+        }
+        if x_values.len() != y_values.len() {
+            return Err(EngineError::domain("x and y data must be the same length").at([2, 3]));
+        }
+
+        let coefficients =
+            match polynomial::fit(&x_values, &y_values, degree as usize, &self.interrupt)? {
+                Some(value) => value,
+                None => {
+                    return Err(EngineError::domain(format!(
+                        "need more than {} distinct data points for a degree {} fit",
+                        degree, degree
+                    ))
+                    .at([2, 3]))
+                }
+            };
+        self.stack.truncate(self.stack.len() - 3);
+
+        let _ = self.add_item_to_stack(Bucket::from_list(
+            coefficients.into_iter().map(Bucket::from).collect(),
+        ));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Expand a value into a list of its continued fraction terms
+    pub fn tocf(&mut self) -> Result<MessageAction, EngineError> {
+        let operands = self.get_operands_as_dec(1)?;
+
+        let terms = continued_fraction::expand(operands[0])
+            .iter()
+            .map(|term| Bucket::from(Decimal::from(*term)))
+            .collect();
+        let _ = self.add_item_to_stack(Bucket::from_list(terms));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Evaluate a list of continued fraction terms
+    pub fn fromcf(&mut self) -> Result<MessageAction, EngineError> {
+        let terms = self.peek_list_as_f(0)?;
+        if terms.is_empty() {
+            return Err(
+                EngineError::domain("continued fraction must have at least one term").at([1]),
+            );
+        }
+
+        let mut decimal_terms = Vec::new();
+        for term in terms {
+            match Decimal::from_f64(term) {
+                Some(value) if term.fract() == 0.0 => decimal_terms.push(value),
+                _ => {
+                    return Err(
+                        EngineError::domain("continued fraction terms must be integers").at([1]),
+                    )
+                }
+            }
+        }
+
+        let result = match continued_fraction::evaluate(&decimal_terms) {
+            Some(value) => value,
+            None => {
+                return Err(EngineError::domain("continued fraction cannot be evaluated").at([1]))
+            }
+        };
+        self.stack.pop();
+        let _ = self.add_item_to_stack(result.normalize().into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Best rational approximation with a bounded denominator.
+    /// Pushes the numerator, then the denominator
+    pub fn approx(&mut self) -> Result<MessageAction, EngineError> {
+        let operands = self.get_operands_as_dec(2)?;
+
+        let max_denominator = match operands[1].to_i128() {
+            Some(value) if operands[1].fract() == dec!(0.0) && value >= 1 => value,
+            _ => {
+                return Err(
+                    EngineError::domain("maximum denominator must be a positive integer").at([1]),
+                )
+            }
+        };
+
+        let (numerator, denominator) =
+            continued_fraction::best_approximation(operands[0], max_denominator);
+        let _ = self.add_item_to_stack(Decimal::from(numerator).into());
+        let _ = self.add_item_to_stack(Decimal::from(denominator).into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Enable modular arithmetic mode with the given modulus
+    pub fn setmod(&mut self) -> Result<MessageAction, EngineError> {
+        let operands = self.get_operands_as_dec(1)?;
+
+        match operands[0].to_u64() {
+            Some(modulus) if operands[0].fract() == dec!(0.0) && modulus >= 2 => {
+                self.modulus = Some(modulus);
+                Ok(MessageAction::SendStack)
+            }
+            _ => Err(EngineError::domain("modulus must be an integer greater than 1").at([1])),
+        }
+    }
+
+    /// Disable modular arithmetic mode
+    pub fn unsetmod(&mut self) -> Result<MessageAction, EngineError> {
+        self.modulus = None;
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Send the active modulus to the client
+    pub fn getmod(&mut self) -> Result<MessageAction, EngineError> {
+        Ok(MessageAction::SendModulus)
+    }
+
+    /// Add an (x, y) pair to the statistics data
+    pub fn statadd(&mut self) -> Result<MessageAction, EngineError> {
+        let operands = self.get_operands_as_f(2)?;
+
+        self.statistics.add_point(operands[0], operands[1]);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Remove an (x, y) pair from the statistics data
+    pub fn statdel(&mut self) -> Result<MessageAction, EngineError> {
+        let x = self.peek_as_f(1)?;
+        let y = self.peek_as_f(0)?;
+
+        if !self.statistics.remove_point(x, y) {
+            return Err(
+                EngineError::domain(format!("data point ({}, {}) does not exist", x, y)).at([1, 2]),
+            );
+        }
+        self.stack.truncate(self.stack.len() - 2);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Remove all statistics data
+    pub fn statclear(&mut self) -> Result<MessageAction, EngineError> {
+        self.statistics.clear();
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Send the statistics data to the client
+    pub fn statlist(&mut self) -> Result<MessageAction, EngineError> {
+        Ok(MessageAction::SendStatistics)
+    }
+
+    /// Fit a regression model and push its two coefficients to the stack
+    fn push_regression(&mut self, model: RegressionModel) -> Result<MessageAction, EngineError> {
+        let regression = self
+            .statistics
+            .regression(model)
+            .map_err(EngineError::domain)?;
+
+        self.statistics.model = model;
+        let _ = self.add_item_to_stack(regression.a.into());
+        let _ = self.add_item_to_stack(regression.b.into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Linear regression, y = a + b*x
+    pub fn reglin(&mut self) -> Result<MessageAction, EngineError> {
+        self.push_regression(RegressionModel::Linear)
+    }
+
+    /// Exponential regression, y = a * e^(b*x)
+    pub fn regexp(&mut self) -> Result<MessageAction, EngineError> {
+        self.push_regression(RegressionModel::Exponential)
+    }
+
+    /// Logarithmic regression, y = a + b*ln(x)
+    pub fn reglog(&mut self) -> Result<MessageAction, EngineError> {
+        self.push_regression(RegressionModel::Logarithmic)
+    }
+
+    /// Power regression, y = a * x^b
+    pub fn regpow(&mut self) -> Result<MessageAction, EngineError> {
+        self.push_regression(RegressionModel::Power)
+    }
+
+    /// Coefficient of determination of the current regression model
+    pub fn rsquared(&mut self) -> Result<MessageAction, EngineError> {
+        let regression = self
+            .statistics
+            .regression(self.statistics.model)
+            .map_err(EngineError::domain)?;
+
+        let _ = self.add_item_to_stack(regression.r_squared.into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Predict a y value from an x value with the current regression model
+    pub fn predict(&mut self) -> Result<MessageAction, EngineError> {
+        let x = self.peek_as_f(0)?;
+        let regression = self
+            .statistics
+            .regression(self.statistics.model)
+            .map_err(EngineError::domain)?;
+        self.stack.pop();
+
+        let _ = self.add_item_to_stack(regression.predict(x).into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Record a token of a program that is being entered between `{` and `}`.
+    /// Returns the complete program literal once the outermost closing brace is recorded
+    pub fn record_program_token(&mut self, token: &str) -> Option<String> {
+        match token {
+            "{" => self.program_depth += 1,
+            "}" => self.program_depth = self.program_depth.saturating_sub(1),
+            _ => (),
+        }
+        self.program_recording.push(token.to_string());
+
+        if self.program_depth == 0 {
+            Some(
+                self.program_recording
+                    .drain(..)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Evaluate the tokens of a program. Bare references to `variable` are
+    /// treated as recalls of that variable
+    fn run_program(
+        &mut self,
+        tokens: &[String],
+        variable: &str,
+        commands: &CommandRegistry,
+    ) -> Result<(), EngineError> {
+        let mut nested_program = Vec::new();
+        let mut depth = 0;
+
+        for token in tokens {
+            // stop long-running programs that were cancelled or have timed out
+            self.interrupt.check()?;
+
+            // nested programs are pushed to the stack without being evaluated
+            if depth > 0 || token == "{" {
+                match token.as_str() {
+                    "{" => depth += 1,
+                    "}" => depth -= 1,
+                    _ => (),
+                }
+                nested_program.push(token.clone());
+
+                if depth == 0 {
+                    self.add_item_to_stack(Bucket::from(nested_program.join(" ")))?;
+                    nested_program.clear();
+                }
+                continue;
+            }
+
+            let token = if token == variable {
+                format!("${}", variable)
+            } else {
+                token.clone()
+            };
+
+            match commands.get(&token) {
+                Some(command) => (command.function)(self)?,
+                None => self.add_item_to_stack(Bucket::from(token))?,
+            };
+        }
+
+        if depth > 0 {
+            return Err(EngineError::new(
+                ErrorKind::InvalidProgram,
+                "program contains an unclosed `{`",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Evaluate a program for every integer in a range, combining the results with
+    /// add or multiply. The stack is left untouched if an error occurs
+    fn range_operation(&mut self, product: bool) -> Result<MessageAction, EngineError> {
+        let saved_stack = self.stack.clone();
+
+        let result = self.evaluate_range(product);
+        if result.is_err() {
+            self.stack = saved_stack;
+        }
+        result
+    }
+
+    /// Implementation of `range_operation`
+    fn evaluate_range(&mut self, product: bool) -> Result<MessageAction, EngineError> {
+        if self.stack.len() < 4 {
+            return Err(EngineError::stack_underflow(4));
+        }
+
+        let bounds = self.get_operands_as_dec(2)?;
+        let (start, end) = match (bounds[0].to_i64(), bounds[1].to_i64()) {
+            (Some(start), Some(end))
+                if bounds[0].fract() == dec!(0.0) && bounds[1].fract() == dec!(0.0) =>
+            {
+                (start, end)
+            }
+            _ => return Err(EngineError::domain("range bounds must be integers").at([1, 2])),
+        };
+        if end.saturating_sub(start) >= MAX_RANGE_ITERATIONS {
+            return Err(EngineError::domain(format!(
+                "range cannot contain more than {} values",
+                MAX_RANGE_ITERATIONS
+            ))
+            .at([1, 2]));
+        }
+
+        let variable = self.stack.pop().unwrap().to_string();
+        if !ID_REGEX.is_match(&variable) {
+            return Err(EngineError::new(
+                ErrorKind::InvalidName,
+                format!("Cannot bind non-variable object `{}`", variable),
+            )
+            .at([3]));
+        }
+
+        let tokens = match self.stack.pop().unwrap().program_tokens() {
+            Some(tokens) => tokens,
+            None => {
+                return Err(EngineError::type_mismatch(
+                    "The operation requires a program operand",
+                    4,
+                ))
+            }
+        };
+
+        // the variable is only bound while the program is evaluated
+        let saved_variable = self.variables.get(&variable).cloned();
+        let commands = command_mappings::create_command_registry();
+
+        let _ = self.add_item_to_stack(Bucket::from(if product { 1 } else { 0 }));
+        let mut result = Ok(MessageAction::SendStack);
+        for k in start..=end {
+            self.variables.insert(variable.clone(), Bucket::from(k));
+
+            let depth = self.stack.len();
+            result = self
+                .run_program(&tokens, &variable, &commands)
+                .and_then(|_| {
+                    if self.stack.len() != depth + 1 {
+                        return Err(EngineError::new(
+                            ErrorKind::InvalidProgram,
+                            "program must leave exactly one item on the stack",
+                        ));
+                    }
+                    if product {
+                        self.multiply()
+                    } else {
+                        self.add()
+                    }
+                });
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        match saved_variable {
+            Some(value) => self.variables.insert(variable, value),
+            None => self.variables.remove(&variable),
+        };
+
+        result
+    }
+
+    /// Summation of a program over a range of integers
+    pub fn sum(&mut self) -> Result<MessageAction, EngineError> {
+        self.range_operation(false)
+    }
+
+    /// Product of a program over a range of integers
+    pub fn prod(&mut self) -> Result<MessageAction, EngineError> {
+        self.range_operation(true)
+    }
+
     /// Drop last item from stack
-    pub fn drop(&mut self) -> Result<MessageAction, String> {
+    pub fn drop(&mut self) -> Result<MessageAction, EngineError> {
         // Remove last item from stack
         self.stack.pop();
         Ok(MessageAction::SendStack)
     }
 
     /// Swap last two items on stack
-    pub fn swap(&mut self) -> Result<MessageAction, String> {
+    pub fn swap(&mut self) -> Result<MessageAction, EngineError> {
         // Get last two values from stack
         let operands = match self.get_operands_raw(2) {
             Ok(content) => content,
@@ -748,7 +1440,7 @@ impl Engine {
     }
 
     /// Duplicate the last item of the stack
-    pub fn dup(&mut self) -> Result<MessageAction, String> {
+    pub fn dup(&mut self) -> Result<MessageAction, EngineError> {
         // Get the last value from the stack
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -762,29 +1454,140 @@ impl Engine {
     }
 
     /// Roll down
-    pub fn roll_down(&mut self) -> Result<MessageAction, String> {
+    pub fn roll_down(&mut self) -> Result<MessageAction, EngineError> {
         if !self.stack.is_empty() {
             // Rotate stack right
             self.stack.rotate_right(1);
             Ok(MessageAction::SendStack)
         } else {
-            Err(String::from("Cannot roll empty stack"))
+            Err(EngineError::new(ErrorKind::StackUnderflow, "Cannot roll empty stack").at([1]))
         }
     }
 
     /// Roll up
-    pub fn roll_up(&mut self) -> Result<MessageAction, String> {
+    pub fn roll_up(&mut self) -> Result<MessageAction, EngineError> {
         if !self.stack.is_empty() {
             // Rotate stack left
             self.stack.rotate_left(1);
             Ok(MessageAction::SendStack)
         } else {
-            Err(String::from("Cannot roll empty stack"))
+            Err(EngineError::new(ErrorKind::StackUnderflow, "Cannot roll empty stack").at([1]))
+        }
+    }
+
+    /// Copy the second item of the stack to the top
+    pub fn over(&mut self) -> Result<MessageAction, EngineError> {
+        if self.stack.len() < 2 {
+            return Err(EngineError::stack_underflow(2));
+        }
+
+        let _ = self.add_item_to_stack(self.stack[self.stack.len() - 2].clone());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Move the third item of the stack to the top
+    pub fn rot(&mut self) -> Result<MessageAction, EngineError> {
+        if self.stack.len() < 3 {
+            return Err(EngineError::stack_underflow(3));
+        }
+
+        let item = self.stack.remove(self.stack.len() - 3);
+        self.stack.push(item);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Remove the second item of the stack
+    pub fn nip(&mut self) -> Result<MessageAction, EngineError> {
+        if self.stack.len() < 2 {
+            return Err(EngineError::stack_underflow(2));
         }
+
+        self.stack.remove(self.stack.len() - 2);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Copy the top item of the stack below the second item
+    pub fn tuck(&mut self) -> Result<MessageAction, EngineError> {
+        if self.stack.len() < 2 {
+            return Err(EngineError::stack_underflow(2));
+        }
+
+        let item = self.stack[self.stack.len() - 1].clone();
+        self.stack.insert(self.stack.len() - 2, item);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Copy the nth item of the stack to the top. 1 is the top item
+    pub fn pick(&mut self) -> Result<MessageAction, EngineError> {
+        let count = self.peek_count()?;
+        if count == 0 {
+            return Err(EngineError::domain("count must be at least 1").at([1]));
+        }
+        self.stack.pop();
+
+        let _ = self.add_item_to_stack(self.stack[self.stack.len() - count].clone());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Move the nth item of the stack to the top. 1 is the top item
+    pub fn nroll(&mut self) -> Result<MessageAction, EngineError> {
+        let count = self.peek_count()?;
+        if count == 0 {
+            return Err(EngineError::domain("count must be at least 1").at([1]));
+        }
+        self.stack.pop();
+
+        let item = self.stack.remove(self.stack.len() - count);
+        self.stack.push(item);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Duplicate the top n items of the stack
+    pub fn ndup(&mut self) -> Result<MessageAction, EngineError> {
+        let count = self.peek_count()?;
+        self.stack.pop();
+
+        let items = self.stack[self.stack.len() - count..].to_vec();
+        self.stack.extend(items);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Drop the top n items of the stack
+    pub fn ndrop(&mut self) -> Result<MessageAction, EngineError> {
+        let count = self.peek_count()?;
+        self.stack.pop();
+
+        self.stack.truncate(self.stack.len() - count);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Push the number of items on the stack
+    pub fn depth(&mut self) -> Result<MessageAction, EngineError> {
+        let _ = self.add_item_to_stack((self.stack.len() as u64).into());
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Reverse the order of the top n items of the stack
+    pub fn reverse(&mut self) -> Result<MessageAction, EngineError> {
+        let count = self.peek_count()?;
+        self.stack.pop();
+
+        let length = self.stack.len();
+        self.stack[length - count..].reverse();
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Keep the top n items of the stack and remove everything below them
+    pub fn keep(&mut self) -> Result<MessageAction, EngineError> {
+        let count = self.peek_count()?;
+        self.stack.pop();
+
+        self.stack.drain(..self.stack.len() - count);
+        Ok(MessageAction::SendStack)
     }
 
     /// Store value in variable
-    pub fn store(&mut self) -> Result<MessageAction, String> {
+    pub fn store(&mut self) -> Result<MessageAction, EngineError> {
         // Get 2 operands from stack
         let operands = match self.get_operands_raw(2) {
             Ok(content) => content,
@@ -798,13 +1601,17 @@ impl Engine {
             self.variables.insert(varname, operands[0].clone());
         } else {
             // Error if attempted to store in name which is not a valid ID
-            return Err(format!("Cannot store in non-variable object `{}`", varname));
+            return Err(EngineError::new(
+                ErrorKind::InvalidName,
+                format!("Cannot store in non-variable object `{}`", varname),
+            )
+            .at([1]));
         }
         Ok(MessageAction::SendStack)
     }
 
     /// Delete variable
-    pub fn purge(&mut self) -> Result<MessageAction, String> {
+    pub fn purge(&mut self) -> Result<MessageAction, EngineError> {
         // Get operand from stack
         let operands = match self.get_operands_raw(1) {
             Ok(content) => content,
@@ -817,17 +1624,25 @@ impl Engine {
                 // Remove variable from hashmap
                 self.variables.remove(&varname);
             } else {
-                return Err(format!("Variable `{}` does not exist", varname));
+                return Err(EngineError::new(
+                    ErrorKind::UndefinedVariable,
+                    format!("Variable `{}` does not exist", varname),
+                )
+                .at([1]));
             }
         } else {
             // Error if attempted to purge name which is not a valid ID
-            return Err(format!("Cannot delete non-variable object `{}`", varname));
+            return Err(EngineError::new(
+                ErrorKind::InvalidName,
+                format!("Cannot delete non-variable object `{}`", varname),
+            )
+            .at([1]));
         }
         Ok(MessageAction::SendStack)
     }
 
     /// Store value in variable, with inverted argument order
-    pub fn invstore(&mut self) -> Result<MessageAction, String> {
+    pub fn invstore(&mut self) -> Result<MessageAction, EngineError> {
         match self.swap() {
             Ok(_) => {}
             Err(error) => return Err(error),
@@ -836,55 +1651,213 @@ impl Engine {
     }
 
     /// Clear stack
-    pub fn clear(&mut self) -> Result<MessageAction, String> {
+    pub fn clear(&mut self) -> Result<MessageAction, EngineError> {
         self.stack = Vec::new();
         Ok(MessageAction::SendStack)
     }
 
-    /// Update stack and variables from the undo history
-    fn update_engine_from_history(&mut self) {
-        self.stack =
-            self.undo_history[self.undo_history.len() - self.undo_state_pointer as usize].clone();
-        self.variables = self.undo_variable_history
-            [self.undo_variable_history.len() - self.undo_state_pointer as usize]
-            .clone();
+    /// The maximum number of commands kept in the undo history, or None if unlimited
+    pub fn undo_depth(&self) -> Option<usize> {
+        match self.config.get_key("system", "undo_depth") {
+            Ok(ConfigValue::Value(toml::Value::Integer(0))) => None,
+            Ok(ConfigValue::Value(toml::Value::Integer(depth))) if depth > 0 => {
+                Some(depth as usize)
+            }
+            _ => Some(DEFAULT_UNDO_DEPTH),
+        }
+    }
+
+    /// Whether the session is saved when quitting and restored on start
+    pub fn persist_session(&self) -> bool {
+        !matches!(
+            self.config.get_key("system", "persist_session"),
+            Ok(ConfigValue::Value(toml::Value::Boolean(false)))
+        )
+    }
+
+    /// The maximum time a request may take to evaluate, or None if unlimited
+    pub fn request_timeout(&self) -> Option<Duration> {
+        match self.config.get_key("system", "request_timeout") {
+            Ok(ConfigValue::Value(toml::Value::Integer(seconds))) if seconds > 0 => {
+                Some(Duration::from_secs(seconds as u64))
+            }
+            Ok(ConfigValue::Value(toml::Value::Integer(_))) => None,
+            _ => Some(Duration::from_secs(DEFAULT_REQUEST_TIMEOUT)),
+        }
+    }
+
+    /// Update the history to the current state without recording an entry
+    pub fn synchronize_history(&mut self) {
+        let mut history = std::mem::take(&mut self.history);
+        history.synchronize(self);
+        self.history = history;
+    }
+
+    /// Take the changes made to the engine since they were last taken
+    pub fn take_changes(&mut self) -> Changes {
+        let mut history = std::mem::take(&mut self.history);
+        let changes = history.take_changes(self);
+        self.history = history;
+        changes
+    }
+
+    /// Record the changes made by a command in the history
+    pub fn record_history(&mut self, command: &str) {
+        let mut history = std::mem::take(&mut self.history);
+        history.record(command, self, self.undo_depth());
+        self.history = history;
     }
 
     /// Undo last operation
-    pub fn undo(&mut self) -> Result<MessageAction, String> {
-        if self.undo_state_pointer < self.undo_history.len() as u8 {
-            if self.undo_state_pointer == 0 {
-                // add current stack and variables to hsitory and increment pointer by 1
-                self.undo_history.push_back(self.stack.clone());
-                self.undo_variable_history.push_back(self.variables.clone());
-                self.undo_state_pointer += 1;
-            }
-            self.undo_state_pointer += 1;
-            self.update_engine_from_history();
+    pub fn undo(&mut self) -> Result<MessageAction, EngineError> {
+        let mut history = std::mem::take(&mut self.history);
+        let undone = history.undo(self);
+        self.history = history;
+
+        if undone {
             Ok(MessageAction::SendStack)
         } else {
-            Err(String::from("Cannot undo further"))
+            Err(EngineError::new(
+                ErrorKind::HistoryExhausted,
+                "Cannot undo further",
+            ))
         }
     }
 
     /// Redo the last undo
-    pub fn redo(&mut self) -> Result<MessageAction, String> {
-        if self.undo_state_pointer > 1 {
-            self.undo_state_pointer -= 1;
-            self.update_engine_from_history();
+    pub fn redo(&mut self) -> Result<MessageAction, EngineError> {
+        let mut history = std::mem::take(&mut self.history);
+        let redone = history.redo(self);
+        self.history = history;
+
+        if redone {
             Ok(MessageAction::SendStack)
         } else {
-            Err(String::from("Cannot redo further"))
+            Err(EngineError::new(
+                ErrorKind::HistoryExhausted,
+                "Cannot redo further",
+            ))
         }
     }
 
+    /// Redo into the branch of the history that was created before the one redo would move to
+    pub fn branch(&mut self) -> Result<MessageAction, EngineError> {
+        let mut history = std::mem::take(&mut self.history);
+        let redone = history.redo_previous_branch(self);
+        self.history = history;
+
+        if redone {
+            Ok(MessageAction::SendStack)
+        } else {
+            Err(EngineError::new(
+                ErrorKind::HistoryExhausted,
+                "There is no branch to redo",
+            ))
+        }
+    }
+
+    /// Save the engine state under the name on top of the stack
+    pub fn checkpoint(&mut self) -> Result<MessageAction, EngineError> {
+        let name = self.checkpoint_name()?;
+
+        self.stack.pop();
+        self.checkpoints.insert(name, Checkpoint::capture(self));
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Replace the engine state with the checkpoint named on top of the stack
+    pub fn restore(&mut self) -> Result<MessageAction, EngineError> {
+        let name = self.checkpoint_name()?;
+
+        match self.checkpoints.remove(&name) {
+            Some(checkpoint) => {
+                checkpoint.restore(self);
+                self.checkpoints.insert(name, checkpoint);
+                Ok(MessageAction::SendStack)
+            }
+            None => Err(EngineError::new(
+                ErrorKind::UndefinedCheckpoint,
+                format!("Checkpoint `{}` does not exist", name),
+            )
+            .at([1])),
+        }
+    }
+
+    /// Get the checkpoint name on top of the stack without removing it
+    fn checkpoint_name(&self) -> Result<String, EngineError> {
+        let name = match self.stack.last() {
+            Some(item) => item.to_string(),
+            None => return Err(EngineError::stack_underflow(1)),
+        };
+
+        if ID_REGEX.is_match(&name) {
+            Ok(name)
+        } else {
+            Err(EngineError::new(
+                ErrorKind::InvalidName,
+                format!(
+                    "Cannot use non-variable object `{}` as a checkpoint name",
+                    name
+                ),
+            )
+            .at([1]))
+        }
+    }
+
+    /// Send a summary of the undo history to the client
+    pub fn history(&mut self) -> Result<MessageAction, EngineError> {
+        Ok(MessageAction::SendHistory)
+    }
+
     /// Send a list of commands to the client
-    pub fn list_commands(&mut self) -> Result<MessageAction, String> {
+    pub fn list_commands(&mut self) -> Result<MessageAction, EngineError> {
         Ok(MessageAction::SendCommands)
     }
 
+    /// Send the metadata of every command to the client
+    pub fn command_info(&mut self) -> Result<MessageAction, EngineError> {
+        Ok(MessageAction::SendCommandInfo)
+    }
+
+    /// Save the full engine state to the file named on top of the stack
+    pub fn save(&mut self) -> Result<MessageAction, EngineError> {
+        let path = self.session_path()?;
+
+        let name = self.stack.pop().unwrap();
+        if let Err(error) = Session::capture(self, "save").write(&path) {
+            self.stack.push(name);
+            return Err(error.at([1]));
+        }
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Replace the full engine state with the file named on top of the stack
+    pub fn load(&mut self) -> Result<MessageAction, EngineError> {
+        let path = self.session_path()?;
+
+        Session::read(&path)
+            .map_err(|error| error.at([1]))?
+            .restore(self);
+        Ok(MessageAction::SendStack)
+    }
+
+    /// Get the path of the session file named on top of the stack without removing it
+    fn session_path(&self) -> Result<PathBuf, EngineError> {
+        match self.stack.last() {
+            Some(item) if item.bucket_type == BucketTypes::String => {
+                session::resolve_path(&self.data_directory, &item.to_string())
+                    .map_err(|error| error.at([1]))
+            }
+            Some(_) => Err(EngineError::type_mismatch(
+                "The session file name must be a string",
+                1,
+            )),
+            None => Err(EngineError::stack_underflow(1)),
+        }
+    }
+
     // send quit code
-    pub fn quit(&mut self) -> Result<MessageAction, String> {
+    pub fn quit(&mut self) -> Result<MessageAction, EngineError> {
         Ok(MessageAction::Quit)
     }
 }
@@ -894,3 +1867,98 @@ impl Default for Engine {
         Self::new()
     }
 }
+
+/// Where an `EngineBuilder` gets the config of the engine from
+#[derive(Debug, Clone)]
+enum ConfigSource {
+    /// The default config, without reading any files
+    Default,
+    /// The user's config file, which is created if it does not exist
+    User,
+    /// A config file at a path, which is only read
+    Path(PathBuf),
+    /// An in-memory config
+    Config(config_handler::Config),
+}
+
+/// Builder for an engine. Unless `user_config` is called, building an
+/// engine never creates or modifies any files
+#[derive(Debug, Clone)]
+pub struct EngineBuilder {
+    config: ConfigSource,
+    data_directory: Option<PathBuf>,
+}
+
+impl EngineBuilder {
+    /// Create a builder for an engine with the default config
+    pub fn new() -> EngineBuilder {
+        EngineBuilder {
+            config: ConfigSource::Default,
+            data_directory: None,
+        }
+    }
+
+    /// Use the default config
+    pub fn default_config(mut self) -> EngineBuilder {
+        self.config = ConfigSource::Default;
+        self
+    }
+
+    /// Use the user's config file, creating it and adding any new keys to it if required
+    pub fn user_config(mut self) -> EngineBuilder {
+        self.config = ConfigSource::User;
+        self
+    }
+
+    /// Read the config from a file. Keys missing from the file use their default value
+    pub fn config_path(mut self, path: impl Into<PathBuf>) -> EngineBuilder {
+        self.config = ConfigSource::Path(path.into());
+        self
+    }
+
+    /// Use an in-memory config
+    pub fn config(mut self, config: config_handler::Config) -> EngineBuilder {
+        self.config = ConfigSource::Config(config);
+        self
+    }
+
+    /// Save and load session files in a directory instead of the Squiid data directory
+    pub fn data_directory(mut self, path: impl Into<PathBuf>) -> EngineBuilder {
+        self.data_directory = Some(path.into());
+        self
+    }
+
+    /// Construct the engine
+    pub fn build(self) -> Result<Engine, EngineError> {
+        let config = match self.config {
+            ConfigSource::Default => config_handler::Config::default(),
+            ConfigSource::User => config_handler::init_config()
+                .and_then(|_| config_handler::read_user_config())
+                .map_err(|error| EngineError::new(ErrorKind::Configuration, error))?,
+            ConfigSource::Path(path) => config_handler::read_config_file(&path)
+                .map_err(|error| EngineError::new(ErrorKind::Configuration, error))?,
+            ConfigSource::Config(config) => config,
+        };
+
+        Ok(Engine {
+            stack: Vec::new(),
+            variables: HashMap::new(),
+            history: History::default(),
+            checkpoints: HashMap::new(),
+            previous_answer: Bucket::from(0),
+            statistics: Statistics::default(),
+            modulus: None,
+            program_recording: Vec::new(),
+            program_depth: 0,
+            config,
+            interrupt: Interrupt::default(),
+            data_directory: self.data_directory.unwrap_or_else(session::data_directory),
+        })
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
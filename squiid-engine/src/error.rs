@@ -0,0 +1,129 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable kinds of errors produced by the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// There are not enough items on the stack for the operation
+    StackUnderflow,
+    /// An operand is not of a type that the operation accepts
+    TypeMismatch,
+    /// An operand is outside the domain of the operation
+    DomainError,
+    /// Division by zero was attempted
+    DivideByZero,
+    /// A referenced variable has not been set
+    UndefinedVariable,
+    /// The result of the operation is too large to be represented
+    Overflow,
+    /// A variable name is not valid
+    InvalidName,
+    /// A program could not be evaluated
+    InvalidProgram,
+    /// An algebraic expression could not be parsed
+    InvalidExpression,
+    /// There is no more history to undo or redo
+    HistoryExhausted,
+    /// A referenced checkpoint has not been created
+    UndefinedCheckpoint,
+    /// The command was cancelled by the client
+    Cancelled,
+    /// The command took longer than the request timeout
+    TimedOut,
+    /// A referenced session does not exist or has expired
+    UndefinedSession,
+    /// The server already has the maximum number of sessions open
+    SessionLimit,
+    /// The session is evaluating another request, and waiting for it would leave
+    /// the server without a free worker
+    SessionBusy,
+    /// A file could not be read or written
+    Io,
+    /// A configuration request could not be completed
+    Configuration,
+    /// A request sent to the server could not be understood
+    InvalidRequest,
+    /// A request did not include the token that the server requires
+    Unauthorized,
+    /// An error that should never occur
+    Internal,
+}
+
+/// An error produced while evaluating a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineError {
+    /// The kind of error
+    pub code: ErrorKind,
+    /// A human-readable description of the error
+    pub message: String,
+    /// The command that caused the error, if any
+    pub command: Option<String>,
+    /// The positions of the offending operands, counted from the top of the stack as it
+    /// was when the command was run. The top of the stack is position 1
+    pub operands: Vec<usize>,
+    /// The position of the failing command in a batch request, counted from 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+}
+
+impl EngineError {
+    /// Construct a new error without a command or operand positions
+    pub fn new(code: ErrorKind, message: impl Into<String>) -> EngineError {
+        EngineError {
+            code,
+            message: message.into(),
+            command: None,
+            operands: Vec::new(),
+            index: None,
+        }
+    }
+
+    /// Construct an error for an operation that requires more items than are on the stack
+    pub fn stack_underflow(required: usize) -> EngineError {
+        EngineError::new(
+            ErrorKind::StackUnderflow,
+            "Not enough items on stack for operation",
+        )
+        .at(1..=required)
+    }
+
+    /// Construct an error for an operand with an invalid type
+    pub fn type_mismatch(message: impl Into<String>, position: usize) -> EngineError {
+        EngineError::new(ErrorKind::TypeMismatch, message).at([position])
+    }
+
+    /// Construct an error for an operand outside the domain of an operation
+    pub fn domain(message: impl Into<String>) -> EngineError {
+        EngineError::new(ErrorKind::DomainError, message)
+    }
+
+    /// Set the positions of the offending operands
+    pub fn at(mut self, operands: impl IntoIterator<Item = usize>) -> EngineError {
+        self.operands = operands.into_iter().collect();
+        self
+    }
+
+    /// Set the command that caused the error, unless one is already set
+    pub fn with_command(mut self, command: &str) -> EngineError {
+        if self.command.is_none() {
+            self.command = Some(command.to_string());
+        }
+        self
+    }
+
+    /// Set the position of the failing command in a batch request
+    pub fn at_index(mut self, index: usize) -> EngineError {
+        self.index = Some(index);
+        self
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for EngineError {}
@@ -1,25 +1,58 @@
-use std::{ffi::CStr, os::raw::c_char, thread};
+use std::{
+    ffi::CStr,
+    os::raw::c_char,
+    sync::{Mutex, PoisonError},
+    thread,
+};
 
-use crate::{start_server, DEFAULT_ADDRESS};
+use crate::{
+    ipc::nng::NanoMsg, server::ShutdownHandle, start_server_with_shutdown, DEFAULT_ADDRESS,
+};
+
+/// Stops the server that was last started with `start_server_exposed`
+static SHUTDOWN: Mutex<Option<ShutdownHandle>> = Mutex::new(None);
 
 #[no_mangle]
 pub extern "C" fn start_server_exposed(address: *const c_char, blocking: bool) {
     let address_to_bind = if address.is_null() {
         DEFAULT_ADDRESS
     } else {
-        unsafe { CStr::from_ptr(address) }
-            .to_str()
-            .expect("Input is not valid UTF-8")
+        match unsafe { CStr::from_ptr(address) }.to_str() {
+            Ok(address) => address,
+            Err(_) => {
+                eprintln!("the address is not valid UTF-8");
+                return;
+            }
+        }
     };
 
+    let shutdown = ShutdownHandle::new();
+    *SHUTDOWN.lock().unwrap_or_else(PoisonError::into_inner) = Some(shutdown.clone());
+
+    // errors are printed instead of stopping the host application
+    let serve = move || {
+        if let Err(err) =
+            start_server_with_shutdown::<NanoMsg>(Some(address_to_bind), None, shutdown)
+        {
+            eprintln!("{:#}", err);
+        }
+    };
     if blocking {
-        start_server(Some(address_to_bind))
+        serve()
     } else {
-        thread::spawn(|| {
-            std::panic::set_hook(Box::new(|panic_info| {
-                eprintln!("panic occurred in thread: {:?}", panic_info);
-            }));
-            start_server(Some(address_to_bind));
-        });
+        thread::spawn(serve);
+    }
+}
+
+/// Stop the server started with `start_server_exposed`. The running requests are
+/// cancelled and the default session is saved
+#[no_mangle]
+pub extern "C" fn stop_server_exposed() {
+    if let Some(shutdown) = SHUTDOWN
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+    {
+        shutdown.shutdown();
     }
 }
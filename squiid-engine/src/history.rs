@@ -0,0 +1,682 @@
+// delta-based undo history and checkpoints
+// each entry only stores what a command changed, so recording a command costs
+// memory proportional to the size of the change rather than the size of the stack.
+// entries form a tree so that undone branches are kept when a new command is recorded
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bucket::{typed, typed::Typed, Bucket},
+    engine::Engine,
+    statistics::{RegressionModel, Statistics},
+};
+
+/// The items of a sequence that changed, stored after the common prefix of the old and new sequence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SequenceDelta<T> {
+    /// Number of leading items shared by the old and new sequence
+    prefix: usize,
+    /// Items after the prefix before the change
+    before: Vec<T>,
+    /// Items after the prefix after the change
+    after: Vec<T>,
+}
+
+impl<T: Clone + PartialEq> SequenceDelta<T> {
+    /// Compute the delta between two sequences. Returns None if they are equal
+    fn between(old: &[T], new: &[T]) -> Option<SequenceDelta<T>> {
+        let prefix = old
+            .iter()
+            .zip(new)
+            .take_while(|(old_item, new_item)| old_item == new_item)
+            .count();
+        if prefix == old.len() && prefix == new.len() {
+            return None;
+        }
+
+        Some(SequenceDelta {
+            prefix,
+            before: old[prefix..].to_vec(),
+            after: new[prefix..].to_vec(),
+        })
+    }
+
+    /// Turn the new sequence back into the old sequence
+    fn revert(&self, items: &mut Vec<T>) {
+        items.truncate(self.prefix);
+        items.extend_from_slice(&self.before);
+    }
+
+    /// Turn the old sequence into the new sequence
+    fn apply(&self, items: &mut Vec<T>) {
+        items.truncate(self.prefix);
+        items.extend_from_slice(&self.after);
+    }
+}
+
+impl<T: Typed> Typed for SequenceDelta<T> {
+    type Repr = SequenceDelta<T::Repr>;
+
+    fn to_typed(&self) -> Self::Repr {
+        SequenceDelta {
+            prefix: self.prefix,
+            before: self.before.to_typed(),
+            after: self.after.to_typed(),
+        }
+    }
+
+    fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+        Ok(SequenceDelta {
+            prefix: repr.prefix,
+            before: Vec::from_typed(repr.before)?,
+            after: Vec::from_typed(repr.after)?,
+        })
+    }
+}
+
+/// A value before and after a change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Change<T> {
+    before: T,
+    after: T,
+}
+
+impl<T: Clone + PartialEq> Change<T> {
+    /// Compute the change between two values. Returns None if they are equal
+    fn between(old: &T, new: &T) -> Option<Change<T>> {
+        if old == new {
+            None
+        } else {
+            Some(Change {
+                before: old.clone(),
+                after: new.clone(),
+            })
+        }
+    }
+}
+
+impl<T: Typed> Typed for Change<T> {
+    type Repr = Change<T::Repr>;
+
+    fn to_typed(&self) -> Self::Repr {
+        Change {
+            before: self.before.to_typed(),
+            after: self.after.to_typed(),
+        }
+    }
+
+    fn from_typed(repr: Self::Repr) -> Result<Self, String> {
+        Ok(Change {
+            before: T::from_typed(repr.before)?,
+            after: T::from_typed(repr.after)?,
+        })
+    }
+}
+
+/// The changes made to the engine by a single command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The command that made the changes
+    pub command: String,
+    #[serde(with = "typed")]
+    stack: Option<SequenceDelta<Bucket>>,
+    #[serde(with = "typed")]
+    variables: Vec<(String, Change<Option<Bucket>>)>,
+    #[serde(with = "typed")]
+    previous_answer: Option<Change<Bucket>>,
+    statistics: Option<SequenceDelta<(f64, f64)>>,
+    regression_model: Option<Change<RegressionModel>>,
+    modulus: Option<Change<Option<u64>>>,
+}
+
+impl HistoryEntry {
+    /// Test if the entry does not change anything
+    fn is_empty(&self) -> bool {
+        self.stack.is_none()
+            && self.variables.is_empty()
+            && self.previous_answer.is_none()
+            && self.statistics.is_none()
+            && self.regression_model.is_none()
+            && self.modulus.is_none()
+    }
+
+    /// Apply the changes of the entry to a state
+    fn apply(&self, state: State) {
+        if let Some(delta) = &self.stack {
+            delta.apply(state.stack);
+        }
+        for (name, change) in &self.variables {
+            set_variable(state.variables, name, &change.after);
+        }
+        if let Some(change) = &self.previous_answer {
+            state.previous_answer.clone_from(&change.after);
+        }
+        if let Some(delta) = &self.statistics {
+            delta.apply(state.statistics);
+        }
+        if let Some(change) = &self.regression_model {
+            *state.regression_model = change.after;
+        }
+        if let Some(change) = &self.modulus {
+            *state.modulus = change.after;
+        }
+    }
+
+    /// Revert the changes of the entry from a state
+    fn revert(&self, state: State) {
+        if let Some(delta) = &self.stack {
+            delta.revert(state.stack);
+        }
+        for (name, change) in &self.variables {
+            set_variable(state.variables, name, &change.before);
+        }
+        if let Some(change) = &self.previous_answer {
+            state.previous_answer.clone_from(&change.before);
+        }
+        if let Some(delta) = &self.statistics {
+            delta.revert(state.statistics);
+        }
+        if let Some(change) = &self.regression_model {
+            *state.regression_model = change.before;
+        }
+        if let Some(change) = &self.modulus {
+            *state.modulus = change.before;
+        }
+    }
+}
+
+/// Mutable references to the parts of the engine state that are tracked by the history
+struct State<'a> {
+    stack: &'a mut Vec<Bucket>,
+    variables: &'a mut HashMap<String, Bucket>,
+    previous_answer: &'a mut Bucket,
+    statistics: &'a mut Vec<(f64, f64)>,
+    regression_model: &'a mut RegressionModel,
+    modulus: &'a mut Option<u64>,
+}
+
+impl<'a> From<&'a mut Engine> for State<'a> {
+    fn from(engine: &'a mut Engine) -> Self {
+        State {
+            stack: &mut engine.stack,
+            variables: &mut engine.variables,
+            previous_answer: &mut engine.previous_answer,
+            statistics: &mut engine.statistics.points,
+            regression_model: &mut engine.statistics.model,
+            modulus: &mut engine.modulus,
+        }
+    }
+}
+
+/// The engine state after the most recently recorded command, used to compute deltas
+#[derive(Debug, Clone)]
+struct Snapshot {
+    stack: Vec<Bucket>,
+    variables: HashMap<String, Bucket>,
+    previous_answer: Bucket,
+    statistics: Vec<(f64, f64)>,
+    regression_model: RegressionModel,
+    modulus: Option<u64>,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Snapshot {
+            stack: Vec::new(),
+            variables: HashMap::new(),
+            previous_answer: Bucket::from(0),
+            statistics: Vec::new(),
+            regression_model: RegressionModel::default(),
+            modulus: None,
+        }
+    }
+}
+
+impl<'a> From<&'a mut Snapshot> for State<'a> {
+    fn from(snapshot: &'a mut Snapshot) -> Self {
+        State {
+            stack: &mut snapshot.stack,
+            variables: &mut snapshot.variables,
+            previous_answer: &mut snapshot.previous_answer,
+            statistics: &mut snapshot.statistics,
+            regression_model: &mut snapshot.regression_model,
+            modulus: &mut snapshot.modulus,
+        }
+    }
+}
+
+impl Snapshot {
+    /// Compute the changes between the snapshot and the current state of the engine
+    fn diff(&self, command: &str, engine: &Engine) -> HistoryEntry {
+        let mut variables: Vec<(String, Change<Option<Bucket>>)> = engine
+            .variables
+            .iter()
+            .filter(|(name, value)| self.variables.get(*name) != Some(*value))
+            .map(|(name, value)| {
+                (
+                    name.clone(),
+                    Change {
+                        before: self.variables.get(name).cloned(),
+                        after: Some(value.clone()),
+                    },
+                )
+            })
+            .collect();
+        variables.extend(
+            self.variables
+                .iter()
+                .filter(|(name, _)| !engine.variables.contains_key(*name))
+                .map(|(name, value)| {
+                    (
+                        name.clone(),
+                        Change {
+                            before: Some(value.clone()),
+                            after: None,
+                        },
+                    )
+                }),
+        );
+
+        HistoryEntry {
+            command: command.to_string(),
+            stack: SequenceDelta::between(&self.stack, &engine.stack),
+            variables,
+            previous_answer: Change::between(&self.previous_answer, &engine.previous_answer),
+            statistics: SequenceDelta::between(&self.statistics, &engine.statistics.points),
+            regression_model: Change::between(&self.regression_model, &engine.statistics.model),
+            modulus: Change::between(&self.modulus, &engine.modulus),
+        }
+    }
+}
+
+/// The parts of the engine state that changed since the changes were last taken
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    /// Whether the stack changed
+    pub stack: bool,
+    /// The variables that changed, with their value before the first change
+    variables: HashMap<String, Option<Bucket>>,
+}
+
+impl Changes {
+    /// Note the changes of an entry that was applied, or reverted if `reverted` is set
+    fn note(&mut self, entry: &HistoryEntry, reverted: bool) {
+        self.stack |= entry.stack.is_some();
+        for (name, change) in &entry.variables {
+            let before = if reverted {
+                &change.after
+            } else {
+                &change.before
+            };
+            self.variables
+                .entry(name.clone())
+                .or_insert_with(|| before.clone());
+        }
+    }
+
+    /// The names of the variables that changed, in alphabetical order
+    pub fn variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.variables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// Set or remove a variable
+fn set_variable(variables: &mut HashMap<String, Bucket>, name: &str, value: &Option<Bucket>) {
+    match value {
+        Some(value) => variables.insert(name.to_string(), value.clone()),
+        None => variables.remove(name),
+    };
+}
+
+/// Summary of the undo history that is sent to the client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistorySummary {
+    /// The commands on the current branch of the history, from oldest to newest
+    pub commands: Vec<String>,
+    /// The number of commands at the end of `commands` that have been undone and can be redone
+    pub undone: usize,
+    /// The number of branches that can be redone from the current point
+    pub branches: usize,
+    /// The maximum number of commands kept in the history, or None if unlimited
+    pub depth: Option<usize>,
+}
+
+/// A node of the history tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    /// The changes made since the parent node. None for the root node
+    entry: Option<HistoryEntry>,
+    /// The parent node. None for the root node
+    parent: Option<usize>,
+    /// Child nodes, from oldest to newest
+    children: Vec<usize>,
+    /// The child that redo moves to. This is the most recently created or visited child
+    redo_child: Option<usize>,
+}
+
+impl Node {
+    fn new(entry: Option<HistoryEntry>, parent: Option<usize>) -> Node {
+        Node {
+            entry,
+            parent,
+            children: Vec::new(),
+            redo_child: None,
+        }
+    }
+}
+
+/// Tree-shaped undo and redo history of the engine.
+/// Recording a command after an undo starts a new branch instead of discarding the undone commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    /// Every node of the tree by id
+    nodes: HashMap<usize, Node>,
+    /// The id of the root node, which represents the oldest state that can be restored
+    root: usize,
+    /// The id of the node matching the current state of the engine
+    current: usize,
+    /// The id of the next node to be created
+    next_id: usize,
+    /// State of the engine at the current node. Not saved, as it is rebuilt from the
+    /// engine state by `synchronize`
+    #[serde(skip)]
+    snapshot: Snapshot,
+    /// The changes made to the snapshot since they were last taken
+    #[serde(skip)]
+    changes: Changes,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            nodes: HashMap::from([(0, Node::new(None, None))]),
+            root: 0,
+            current: 0,
+            next_id: 1,
+            snapshot: Snapshot::default(),
+            changes: Changes::default(),
+        }
+    }
+}
+
+impl History {
+    /// The number of recorded entries on every branch
+    pub fn len(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Test if nothing has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entries between the root and the current state, from oldest to newest
+    pub fn entries(&self) -> Vec<&HistoryEntry> {
+        let mut entries = Vec::new();
+        let mut id = self.current;
+        while let Some(node) = self.nodes.get(&id) {
+            if let Some(entry) = &node.entry {
+                entries.push(entry);
+            }
+            match node.parent {
+                Some(parent) => id = parent,
+                None => break,
+            }
+        }
+        entries.reverse();
+        entries
+    }
+
+    /// The entries that redo would apply, from the next entry onwards
+    fn redo_entries(&self) -> Vec<&HistoryEntry> {
+        let mut entries = Vec::new();
+        let mut id = self.nodes[&self.current].redo_child;
+        while let Some(child) = id {
+            let node = &self.nodes[&child];
+            entries.extend(&node.entry);
+            id = node.redo_child;
+        }
+        entries
+    }
+
+    /// The number of entries that have been undone and can be redone on the current branch
+    pub fn undone(&self) -> usize {
+        self.redo_entries().len()
+    }
+
+    /// The number of branches that can be redone from the current state
+    pub fn branches(&self) -> usize {
+        self.nodes[&self.current].children.len()
+    }
+
+    /// Update the snapshot to the current state of the engine without recording an entry.
+    /// This keeps changes made outside of commands out of the next entry
+    pub fn synchronize(&mut self, engine: &Engine) {
+        let entry = self.snapshot.diff("", engine);
+        if !entry.is_empty() {
+            self.apply(&entry);
+        }
+    }
+
+    /// Take the changes made to the engine since they were last taken, including
+    /// changes that have not been recorded. Variables that were changed back to their
+    /// previous value are left out
+    pub fn take_changes(&mut self, engine: &Engine) -> Changes {
+        self.synchronize(engine);
+        let mut changes = std::mem::take(&mut self.changes);
+        changes
+            .variables
+            .retain(|name, before| engine.variables.get(name) != before.as_ref());
+        changes
+    }
+
+    /// Continue from the state of the history that this one replaces, so that the
+    /// differences between their states are part of the changes
+    pub fn replace(&mut self, previous: History) {
+        self.snapshot = previous.snapshot;
+        self.changes = previous.changes;
+    }
+
+    /// Apply an entry to the snapshot
+    fn apply(&mut self, entry: &HistoryEntry) {
+        entry.apply((&mut self.snapshot).into());
+        self.changes.note(entry, false);
+    }
+
+    /// Revert an entry from the snapshot
+    fn revert(&mut self, entry: &HistoryEntry) {
+        entry.revert((&mut self.snapshot).into());
+        self.changes.note(entry, true);
+    }
+
+    /// Record the changes made by a command since the last entry, keeping at most
+    /// `depth` entries. Returns false if the command did not change anything
+    pub fn record(&mut self, command: &str, engine: &Engine, depth: Option<usize>) -> bool {
+        let entry = self.snapshot.diff(command, engine);
+        if entry.is_empty() {
+            return false;
+        }
+        self.apply(&entry);
+
+        // a new entry starts a new branch, keeping anything that was undone
+        let id = self.next_id;
+        self.next_id += 1;
+        self.nodes
+            .insert(id, Node::new(Some(entry), Some(self.current)));
+        let parent = self.nodes.get_mut(&self.current).unwrap();
+        parent.children.push(id);
+        parent.redo_child = Some(id);
+        self.current = id;
+
+        if let Some(depth) = depth {
+            while self.len() > depth {
+                self.prune();
+            }
+        }
+        true
+    }
+
+    /// Remove the oldest part of the tree.
+    /// Branches that the current state is not part of are removed first
+    fn prune(&mut self) {
+        let root = &self.nodes[&self.root];
+        let current_branch = self.branch_containing(self.current);
+        let removable_branch = root
+            .children
+            .iter()
+            .find(|child| Some(**child) != current_branch)
+            .copied();
+
+        match (removable_branch, current_branch) {
+            (Some(branch), _) => self.remove_subtree(branch),
+            // the oldest entry becomes the new root and can no longer be undone
+            (None, Some(branch)) => {
+                self.nodes.remove(&self.root);
+                let new_root = self.nodes.get_mut(&branch).unwrap();
+                new_root.entry = None;
+                new_root.parent = None;
+                self.root = branch;
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// The child of the root whose subtree contains a node, if any
+    fn branch_containing(&self, mut id: usize) -> Option<usize> {
+        loop {
+            match self.nodes[&id].parent {
+                Some(parent) if parent == self.root => return Some(id),
+                Some(parent) => id = parent,
+                None => return None,
+            }
+        }
+    }
+
+    /// Remove a node and all of its descendants
+    fn remove_subtree(&mut self, id: usize) {
+        if let Some(parent) = self.nodes[&id].parent {
+            let parent = self.nodes.get_mut(&parent).unwrap();
+            parent.children.retain(|child| *child != id);
+            if parent.redo_child == Some(id) {
+                parent.redo_child = parent.children.last().copied();
+            }
+        }
+
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            if let Some(node) = self.nodes.remove(&id) {
+                pending.extend(node.children);
+            }
+        }
+    }
+
+    /// Revert the most recent entry. Returns false if there is nothing to undo
+    pub fn undo(&mut self, engine: &mut Engine) -> bool {
+        // changes made outside of commands would otherwise break the deltas
+        self.synchronize(engine);
+
+        let node = &self.nodes[&self.current];
+        let (Some(entry), Some(parent)) = (node.entry.clone(), node.parent) else {
+            return false;
+        };
+        self.revert(&entry);
+        entry.revert(engine.into());
+        self.nodes.get_mut(&parent).unwrap().redo_child = Some(self.current);
+        self.current = parent;
+        true
+    }
+
+    /// Apply the most recently undone entry again. Returns false if there is nothing to redo
+    pub fn redo(&mut self, engine: &mut Engine) -> bool {
+        match self.nodes[&self.current].redo_child {
+            Some(child) => self.move_to_child(child, engine),
+            None => false,
+        }
+    }
+
+    /// Redo into the branch that was created before the one redo would move to,
+    /// wrapping around to the newest branch. Returns false if there is nothing to redo
+    pub fn redo_previous_branch(&mut self, engine: &mut Engine) -> bool {
+        let node = &self.nodes[&self.current];
+        let Some(redo_child) = node.redo_child else {
+            return false;
+        };
+
+        let index = node
+            .children
+            .iter()
+            .position(|child| *child == redo_child)
+            .unwrap_or(0);
+        let child = node.children[(index + node.children.len() - 1) % node.children.len()];
+        self.move_to_child(child, engine)
+    }
+
+    /// Apply the entry of a child of the current node
+    fn move_to_child(&mut self, child: usize, engine: &mut Engine) -> bool {
+        self.synchronize(engine);
+
+        let Some(entry) = self.nodes[&child].entry.clone() else {
+            return false;
+        };
+        self.apply(&entry);
+        entry.apply(engine.into());
+        self.nodes.get_mut(&self.current).unwrap().redo_child = Some(child);
+        self.current = child;
+        true
+    }
+
+    /// Summarize the history for the client
+    pub fn summary(&self, depth: Option<usize>) -> HistorySummary {
+        let redo_entries = self.redo_entries();
+        HistorySummary {
+            commands: self
+                .entries()
+                .into_iter()
+                .chain(redo_entries.iter().copied())
+                .map(|entry| entry.command.clone())
+                .collect(),
+            undone: redo_entries.len(),
+            branches: self.branches(),
+            depth,
+        }
+    }
+}
+
+/// A named copy of the full engine state, created by the `checkpoint` command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(with = "typed")]
+    pub stack: Vec<Bucket>,
+    #[serde(with = "typed")]
+    pub variables: HashMap<String, Bucket>,
+    #[serde(with = "typed")]
+    pub previous_answer: Bucket,
+    pub statistics: Statistics,
+    pub modulus: Option<u64>,
+}
+
+impl Checkpoint {
+    /// Copy the state of an engine
+    pub fn capture(engine: &Engine) -> Checkpoint {
+        Checkpoint {
+            stack: engine.stack.clone(),
+            variables: engine.variables.clone(),
+            previous_answer: engine.previous_answer.clone(),
+            statistics: engine.statistics.clone(),
+            modulus: engine.modulus,
+        }
+    }
+
+    /// Replace the state of an engine with the checkpoint
+    pub fn restore(&self, engine: &mut Engine) {
+        engine.stack.clone_from(&self.stack);
+        engine.variables.clone_from(&self.variables);
+        engine.previous_answer = self.previous_answer.clone();
+        engine.statistics.clone_from(&self.statistics);
+        engine.modulus = self.modulus;
+    }
+}
@@ -0,0 +1,54 @@
+// stopping a running command from another thread
+// commands that evaluate programs check the interrupt between tokens, and iterative
+// numeric commands check it between iterations
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::error::{EngineError, ErrorKind};
+
+/// Cancellation flag and deadline of the command that an engine is running.
+/// Clones share the cancellation flag, so a clone can cancel the command from another thread
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    /// Set when the running command should stop
+    cancelled: Arc<AtomicBool>,
+    /// The time after which the running command should stop, if any
+    deadline: Option<Instant>,
+}
+
+impl Interrupt {
+    /// Prepare for a new command that may run for at most `timeout`
+    pub fn reset(&mut self, timeout: Option<Duration>) {
+        self.cancelled.store(false, Ordering::SeqCst);
+        self.deadline = timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Stop the running command
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Return an error if the running command has been cancelled or has exceeded its deadline
+    pub fn check(&self) -> Result<(), EngineError> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(EngineError::new(
+                ErrorKind::Cancelled,
+                "The command was cancelled",
+            ));
+        }
+
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(EngineError::new(
+                ErrorKind::TimedOut,
+                "The command took too long to evaluate",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use tungstenite::{
+    handshake::derive_accept_key,
+    protocol::{Role, WebSocketConfig},
+    Message, WebSocket,
+};
+
+use crate::protocol::event::EventMessage;
+
+use super::{
+    json_lines::{JsonLinesWorker, Reply, RequestQueue, Stream},
+    IPCBackend,
+};
+
+/// The browser frontend served at `/`
+const FRONTEND: &str = include_str!("http/index.html");
+
+/// The largest request body or WebSocket message that is accepted, in bytes
+const MAX_BODY_LENGTH: usize = 1 << 20;
+
+/// The largest request line and headers that are accepted, in bytes
+const MAX_HEADER_LENGTH: u64 = 16 << 10;
+
+/// The number of connections that are served at the same time. Further connections
+/// are answered with an error
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a WebSocket connection that is waiting for a message sends its responses
+/// and events
+const WEBSOCKET_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Answers requests sent with HTTP POST or over a WebSocket, and serves a browser frontend
+pub struct Http {
+    requests: RequestQueue,
+    /// The WebSocket connections that events are published to
+    sockets: Sockets,
+}
+
+/// The WebSocket connections that have sent a request with the token of the server
+type Sockets = Arc<Mutex<Vec<Arc<Subscriber>>>>;
+
+/// A WebSocket connection that receives the events of the session it uses
+struct Subscriber {
+    /// The session of the last request that included the token of the server
+    session: Mutex<Option<String>>,
+    /// Outgoing messages of the connection
+    sender: Sender<String>,
+}
+
+impl Subscriber {
+    /// Test if the connection receives an event
+    fn receives(&self, event: &EventMessage) -> bool {
+        *self.session.lock().unwrap_or_else(PoisonError::into_inner) == event.session
+    }
+}
+
+impl IPCBackend for Http {
+    type Worker = JsonLinesWorker;
+
+    // any web page that the user visits can send requests to the server
+    const REQUIRES_TOKEN: bool = true;
+
+    fn new() -> Self {
+        Self {
+            requests: RequestQueue::new(),
+            sockets: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn bind_and_listen(&self, address: &str) -> Result<(), anyhow::Error> {
+        let listener = TcpListener::bind(address.strip_prefix("http://").unwrap_or(address))?;
+        let hosts = Arc::new(allowed_hosts(listener.local_addr()?));
+        let requests = self.requests.clone();
+        let sockets = self.sockets.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        // answer every connection on its own thread
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    let _ = write_response(
+                        &stream,
+                        "503 Service Unavailable",
+                        "text/plain",
+                        b"too many connections",
+                    );
+                    continue;
+                }
+
+                let connection = Connection(connections.clone());
+                let requests = requests.clone();
+                let sockets = sockets.clone();
+                let hosts = hosts.clone();
+                thread::spawn(move || {
+                    let _ = handle_connection(stream, &hosts, &requests, &sockets);
+                    drop(connection);
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn worker(&self) -> Result<Self::Worker, anyhow::Error> {
+        Ok(self.requests.worker())
+    }
+
+    fn bind_publisher(&self, _address: &str) -> Result<(), anyhow::Error> {
+        Err(anyhow!(
+            "the http backend publishes events on its WebSocket connections"
+        ))
+    }
+
+    fn publish(&self, event: &EventMessage) -> Result<(), anyhow::Error> {
+        let json = serde_json::to_string(event)?;
+
+        // forget connections that were closed
+        self.sockets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|socket| !socket.receives(event) || socket.sender.send(json.clone()).is_ok());
+        Ok(())
+    }
+
+    fn has_subscribers(&self) -> bool {
+        !self
+            .sockets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    fn close(&self) {
+        self.requests.close();
+    }
+}
+
+/// Passes each response written by a worker to the connection of its request
+struct MessageWriter {
+    buffer: Vec<u8>,
+    sender: Sender<String>,
+}
+
+impl MessageWriter {
+    /// Create a reply that sends the responses of requests to a connection
+    fn reply(sender: Sender<String>) -> Reply {
+        Arc::new(Mutex::new(Self {
+            buffer: Vec::new(),
+            sender,
+        }))
+    }
+
+    /// Create a reply for a single request, and the receiver its response is sent to
+    fn channel() -> (Reply, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self::reply(sender), receiver)
+    }
+}
+
+impl Write for MessageWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        // the response was written as a line
+        let message = String::from_utf8_lossy(&self.buffer).trim_end().to_string();
+        self.buffer.clear();
+        self.sender
+            .send(message)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+/// Counts a connection as open until it is dropped
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The parts of an HTTP request that the backend uses
+struct HttpRequest {
+    method: String,
+    path: String,
+    /// Headers with lowercase names
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Read a request from the start of a connection
+    fn read(reader: &mut impl BufRead) -> Result<Self, anyhow::Error> {
+        // a line that does not end before the limit is too long
+        let mut head = io::Read::take(&mut *reader, MAX_HEADER_LENGTH);
+        let mut read_line = |line: &mut String| -> Result<(), anyhow::Error> {
+            line.clear();
+            if head.read_line(line)? > 0 && !line.ends_with('\n') && head.limit() == 0 {
+                return Err(anyhow!("the request headers are too large"));
+            }
+            Ok(())
+        };
+
+        let mut line = String::new();
+        read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return Err(anyhow!("invalid request line"));
+        };
+        let (method, path) = (method.to_string(), path.to_string());
+
+        let mut headers = Vec::new();
+        loop {
+            read_line(&mut line)?;
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let mut request = Self {
+            method,
+            path,
+            headers,
+            body: Vec::new(),
+        };
+        let length = match request.header("content-length") {
+            Some(length) => length.parse()?,
+            None => 0,
+        };
+        if length > MAX_BODY_LENGTH {
+            return Err(anyhow!("the request body is too large"));
+        }
+        request.body.resize(length, 0);
+        reader.read_exact(&mut request.body)?;
+        Ok(request)
+    }
+
+    /// Get the value of a header by its lowercase name
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The Host headers that requests to a listening address may use, or None if any host
+/// is accepted because the address listens on every interface
+fn allowed_hosts(address: SocketAddr) -> Option<Vec<String>> {
+    if address.ip().is_unspecified() {
+        return None;
+    }
+
+    let mut hosts = vec![address.to_string()];
+    if address.ip().is_loopback() {
+        hosts.push(format!("localhost:{}", address.port()));
+    }
+    Some(hosts)
+}
+
+/// Answer the request of a connection, or serve its WebSocket until it is closed
+fn handle_connection(
+    stream: TcpStream,
+    hosts: &Option<Vec<String>>,
+    requests: &RequestQueue,
+    sockets: &Sockets,
+) -> Result<(), anyhow::Error> {
+    // a client that does not send its request does not hold on to the connection
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let Ok(request) = HttpRequest::read(&mut reader) else {
+        return write_response(&stream, "400 Bad Request", "text/plain", b"bad request");
+    };
+
+    // a host name that resolves to the server, such as in a DNS rebinding attack,
+    // does not give a web page access to it
+    let host = match (request.header("host"), hosts) {
+        (Some(host), None) => host,
+        (Some(host), Some(hosts))
+            if hosts
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(host)) =>
+        {
+            host
+        }
+        _ => {
+            return write_response(
+                &stream,
+                "421 Misdirected Request",
+                "text/plain",
+                b"unknown host",
+            )
+        }
+    };
+
+    // browsers send the origin of the page that made the request, which must be the
+    // frontend of the server
+    if let Some(origin) = request.header("origin") {
+        if !origin.eq_ignore_ascii_case(&format!("http://{}", host)) {
+            return write_response(&stream, "403 Forbidden", "text/plain", b"forbidden origin");
+        }
+    }
+
+    // the query string is only read by the frontend
+    let path = request.path.split('?').next().unwrap_or_default();
+    match (request.method.as_str(), path) {
+        ("GET", "/") => write_response(
+            &stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            FRONTEND.as_bytes(),
+        ),
+        ("GET", "/ws") => {
+            let Some(key) = request.header("sec-websocket-key") else {
+                return write_response(
+                    &stream,
+                    "426 Upgrade Required",
+                    "text/plain",
+                    b"websocket connection required",
+                );
+            };
+
+            // bytes that were read past the request belong to the first message
+            let buffered = reader.buffer().to_vec();
+            serve_websocket(stream, key, buffered, requests, sockets)
+        }
+        ("POST", "/") => {
+            let (reply, response) = MessageWriter::channel();
+            let body = String::from_utf8_lossy(&request.body).into_owned();
+
+            // the reply is dropped without a response if the server quits first
+            let _ = requests.push(body, reply);
+            match response.recv() {
+                Ok(response) => {
+                    write_response(&stream, "200 OK", "application/json", response.as_bytes())
+                }
+                Err(_) => write_response(
+                    &stream,
+                    "503 Service Unavailable",
+                    "text/plain",
+                    b"the server is quitting",
+                ),
+            }
+        }
+        (_, "/" | "/ws") => write_response(
+            &stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed",
+        ),
+        _ => write_response(&stream, "404 Not Found", "text/plain", b"not found"),
+    }
+}
+
+/// Answer the requests sent over a WebSocket, and send it every published event
+fn serve_websocket(
+    mut stream: TcpStream,
+    key: &str,
+    buffered: Vec<u8>,
+    requests: &RequestQueue,
+    sockets: &Sockets,
+) -> Result<(), anyhow::Error> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    )?;
+
+    // responses and events are passed to the loop that serves the connection
+    let (sender, outgoing) = mpsc::channel();
+
+    // the connection receives events once it has sent a request with the token of the
+    // server, and only those of the session its last such request used
+    let subscriber = Arc::new(Subscriber {
+        session: Mutex::new(None),
+        sender: sender.clone(),
+    });
+    let registered = AtomicBool::new(false);
+    let subscribe = {
+        let subscriber = subscriber.clone();
+        let sockets = sockets.clone();
+        move |session: Option<&str>| {
+            *subscriber
+                .session
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = session.map(String::from);
+            if !registered.swap(true, Ordering::SeqCst) {
+                sockets
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(subscriber.clone());
+            }
+        }
+    };
+
+    // requests sent over the connection are evaluated in order
+    let ordered = Stream::new(MessageWriter::reply(sender)).on_authenticated(subscribe);
+    let connection = stream.try_clone()?;
+    let result = websocket_loop(stream, buffered, requests, &ordered, &outgoing);
+
+    // stop sending to the closed connection
+    sockets
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|socket| !Arc::ptr_eq(socket, &subscriber));
+    let _ = connection.shutdown(Shutdown::Both);
+    result
+}
+
+/// Queue the requests received over a WebSocket and send it the outgoing messages,
+/// until the connection is closed. Reading and writing share one `WebSocket`, so that
+/// the replies it sends to pings and close frames do not interleave with messages
+fn websocket_loop(
+    stream: TcpStream,
+    buffered: Vec<u8>,
+    requests: &RequestQueue,
+    ordered: &Stream,
+    outgoing: &Receiver<String>,
+) -> Result<(), anyhow::Error> {
+    // reading stops regularly to send the outgoing messages
+    stream.set_read_timeout(Some(WEBSOCKET_POLL_INTERVAL))?;
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_BODY_LENGTH),
+        max_frame_size: Some(MAX_BODY_LENGTH),
+        ..WebSocketConfig::default()
+    };
+    let mut socket = WebSocket::from_partially_read(stream, buffered, Role::Server, Some(config));
+
+    loop {
+        for message in outgoing.try_iter() {
+            socket.send(Message::Text(message))?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(request)) => {
+                if !requests.push_ordered(ordered, request) {
+                    return Ok(());
+                }
+            }
+            // the reply to a close frame is sent by the next read, which then ends
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(error))
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return Ok(())
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+/// Write a complete response and close the connection
+fn write_response(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), anyhow::Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::protocol::{
+    client_request::{ClientRequestMessage, RequestType},
+    server_response::ServerResponseMessage,
+};
+
+use super::IPCWorker;
+
+/// How often a stream that is waiting for its requests to be answered checks if the
+/// backend was closed
+const CLOSED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The stream that the response to a request is written to
+pub type Reply = Arc<Mutex<dyn Write + Send>>;
+
+/// A request line, the stream it was read from, and the ordered stream that is waiting
+/// for it to be answered, if any
+type QueuedRequest = (String, Reply, Option<Stream>);
+
+/// Called with the session of every request of a stream that includes the token of the server
+type Authenticated = Arc<dyn Fn(Option<&str>) + Send + Sync>;
+
+/// A queued request. None marks the end of the requests
+type Request = Option<QueuedRequest>;
+
+/// Requests read from every client, one JSON object per line, waiting for a worker
+#[derive(Clone)]
+pub struct RequestQueue {
+    sender: Sender<Request>,
+    receiver: Arc<Mutex<Receiver<Request>>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl RequestQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Queue every line of a client until its stream ends, and wait until they have been
+    /// answered. Responses are written to `reply` in the order the requests were sent
+    pub fn read(&self, reader: impl BufRead, reply: Reply) {
+        let stream = Stream::new(reply);
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !self.push_ordered(&stream, line) {
+                return;
+            }
+        }
+        stream.wait(&self.closed);
+    }
+
+    /// Queue a single request. Returns false if the workers have stopped
+    pub fn push(&self, request: String, reply: Reply) -> bool {
+        self.sender.send(Some((request, reply, None))).is_ok()
+    }
+
+    /// Queue a request once the previous request of the stream has been answered.
+    /// `cancel` requests are queued straight away, so that they can stop the running
+    /// request. Returns false if the workers have stopped
+    pub fn push_ordered(&self, stream: &Stream, request: String) -> bool {
+        if is_cancel(&request) {
+            return self.push(request, stream.reply.clone());
+        }
+
+        let mut state = stream.lock();
+        if state.running {
+            state.waiting.push_back(request);
+            true
+        } else {
+            state.running = true;
+            self.sender
+                .send(Some((request, stream.reply.clone(), Some(stream.clone()))))
+                .is_ok()
+        }
+    }
+
+    /// Queue the next waiting request of a stream whose request has been answered
+    fn finish(&self, stream: &Stream) {
+        let mut state = stream.lock();
+        match state.waiting.pop_front() {
+            Some(request) => {
+                let _ =
+                    self.sender
+                        .send(Some((request, stream.reply.clone(), Some(stream.clone()))));
+            }
+            None => {
+                state.running = false;
+                stream.state.1.notify_all();
+            }
+        }
+    }
+
+    /// Stop the workers once the requests that are already queued have been answered
+    pub fn end(&self) {
+        let _ = self.sender.send(None);
+    }
+
+    /// Stop the workers without answering the requests that are still queued
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.end();
+    }
+
+    /// Create a worker that answers the queued requests
+    pub fn worker(&self) -> JsonLinesWorker {
+        JsonLinesWorker {
+            requests: self.clone(),
+            reply: RefCell::new(None),
+        }
+    }
+}
+
+impl Default for RequestQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The requests of one client, which are evaluated one at a time in the order they were sent
+#[derive(Clone)]
+pub struct Stream {
+    reply: Reply,
+    state: Arc<(Mutex<StreamState>, Condvar)>,
+    authenticated: Option<Authenticated>,
+}
+
+/// Whether a request of a stream is being evaluated, and the requests waiting for it
+#[derive(Default)]
+struct StreamState {
+    running: bool,
+    waiting: VecDeque<String>,
+}
+
+impl Stream {
+    /// Create a stream whose responses are written to `reply`
+    pub fn new(reply: Reply) -> Self {
+        Self {
+            reply,
+            state: Arc::default(),
+            authenticated: None,
+        }
+    }
+
+    /// Call `callback` with the session of every request of the stream that includes the
+    /// token of the server
+    pub fn on_authenticated(
+        mut self,
+        callback: impl Fn(Option<&str>) + Send + Sync + 'static,
+    ) -> Self {
+        self.authenticated = Some(Arc::new(callback));
+        self
+    }
+
+    /// Lock the state of the stream
+    fn lock(&self) -> MutexGuard<'_, StreamState> {
+        self.state.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Block until every request of the stream has been answered, or the backend is closed
+    fn wait(&self, closed: &AtomicBool) {
+        let mut state = self.lock();
+        while state.running && !closed.load(Ordering::SeqCst) {
+            state = self
+                .state
+                .1
+                .wait_timeout(state, CLOSED_POLL_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+/// Test if a request line is a `cancel` request
+fn is_cancel(request: &str) -> bool {
+    #[derive(Deserialize)]
+    struct RequestKind {
+        request_type: RequestType,
+    }
+
+    matches!(
+        serde_json::from_str::<RequestKind>(request),
+        Ok(RequestKind {
+            request_type: RequestType::Cancel
+        })
+    )
+}
+
+/// Answers the requests of a `RequestQueue` on one thread
+pub struct JsonLinesWorker {
+    requests: RequestQueue,
+    /// The stream of the request that is being answered, and its ordered stream if any
+    reply: RefCell<Option<(Reply, Option<Stream>)>>,
+}
+
+impl JsonLinesWorker {
+    /// Stop answering the current request, letting its stream queue the next one
+    fn finish(&self) {
+        if let Some((_, Some(stream))) = self.reply.replace(None) {
+            self.requests.finish(&stream);
+        }
+    }
+}
+
+impl IPCWorker for JsonLinesWorker {
+    fn recv_data(&self) -> Result<ClientRequestMessage, anyhow::Error> {
+        self.finish();
+        if self.requests.closed.load(Ordering::SeqCst) {
+            return Err(anyhow!("the backend was closed"));
+        }
+
+        let request = self
+            .requests
+            .receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv()?;
+        let (line, reply, stream) = match request {
+            Some(request) => request,
+            None => {
+                // pass the end on to the next waiting worker
+                self.requests.end();
+                return Err(anyhow!("there are no more requests"));
+            }
+        };
+
+        // invalid requests are answered with an error on the same stream
+        self.reply.replace(Some((reply, stream)));
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    fn send_data(&self, response: ServerResponseMessage) -> Result<(), anyhow::Error> {
+        let reply = self
+            .reply
+            .borrow()
+            .as_ref()
+            .map(|(reply, _)| reply.clone())
+            .ok_or_else(|| anyhow!("there is no request to respond to"))?;
+        let json = serde_json::to_string(&response)?;
+
+        let result = {
+            let mut writer = reply.lock().unwrap_or_else(PoisonError::into_inner);
+            writeln!(writer, "{}", json).and_then(|_| writer.flush())
+        };
+        self.finish();
+        Ok(result?)
+    }
+
+    fn authenticated(&self, session: Option<&str>) {
+        if let Some((_, Some(stream))) = self.reply.borrow().as_ref() {
+            if let Some(callback) = &stream.authenticated {
+                callback(session);
+            }
+        }
+    }
+}
+
+impl Drop for JsonLinesWorker {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -1,19 +1,64 @@
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(any(
+    feature = "stdio",
+    feature = "http",
+    all(feature = "unix-socket", unix)
+))]
+pub mod json_lines;
+#[cfg(feature = "nng")]
 pub mod nng;
+#[cfg(feature = "stdio")]
+pub mod stdio;
+#[cfg(all(feature = "unix-socket", unix))]
+pub mod unix_socket;
 
 use crate::protocol::{
-    client_request::ClientRequestMessage, server_response::ServerResponseMessage,
+    client_request::ClientRequestMessage, event::EventMessage,
+    server_response::ServerResponseMessage,
 };
 
-pub trait IPCBackend {
+pub trait IPCBackend: Sync {
+    /// Handles the requests of one worker thread of the server
+    type Worker: IPCWorker;
+
+    /// Whether every request must include a token. If no token is given or configured,
+    /// the server generates one
+    const REQUIRES_TOKEN: bool = false;
+
     /// construct new default object
     fn new() -> Self;
 
     /// Bind and listen to the given address. Default is defined in lib.rs as DEFAULT_ADDRESS
     fn bind_and_listen(&self, address: &str) -> Result<(), anyhow::Error>;
 
-    /// Recieve data from the client
+    /// Create a worker that receives requests and sends their responses. Every worker
+    /// handles one request at a time, and several workers can wait for requests in parallel
+    fn worker(&self) -> Result<Self::Worker, anyhow::Error>;
+
+    /// Bind the socket that events are published on to the given address
+    fn bind_publisher(&self, address: &str) -> Result<(), anyhow::Error>;
+
+    /// Send an event to every subscriber. Events are dropped if there are no subscribers
+    fn publish(&self, event: &EventMessage) -> Result<(), anyhow::Error>;
+
+    /// Test if there may be subscribers. Events are only built while there are
+    fn has_subscribers(&self) -> bool;
+
+    /// Stop listening. Workers that are waiting for a request return an error
+    fn close(&self);
+}
+
+pub trait IPCWorker: Send {
+    /// Recieve data from the client. Every request must be answered with
+    /// `send_data` before the next request is received
     fn recv_data(&self) -> Result<ClientRequestMessage, anyhow::Error>;
 
     // Send data to the client
     fn send_data(&self, response: ServerResponseMessage) -> Result<(), anyhow::Error>;
+
+    /// Called when the received request includes the token of the server, with the session
+    /// it uses. Backends that send events to their clients only send them the events of
+    /// this session
+    fn authenticated(&self, _session: Option<&str>) {}
 }
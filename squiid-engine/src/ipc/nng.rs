@@ -1,30 +1,119 @@
-use nng::{Protocol, Socket};
+use std::sync::{
+    mpsc::{self, Receiver},
+    Mutex, MutexGuard, PoisonError,
+};
 
-use crate::protocol::client_request::ClientRequestMessage;
+use anyhow::anyhow;
+use nng::{Aio, AioResult, Context, Protocol, Socket};
 
-use super::IPCBackend;
+use crate::protocol::{
+    client_request::ClientRequestMessage, event::EventMessage,
+    server_response::ServerResponseMessage,
+};
+
+use super::{IPCBackend, IPCWorker};
 
 pub struct NanoMsg {
-    socket: nng::Socket,
+    /// Rep0 socket that requests are answered on, once it is listening
+    socket: Mutex<Option<nng::Socket>>,
+    /// Pub0 socket that events are published on, once it is listening
+    publisher: Mutex<Option<nng::Socket>>,
 }
 
 impl IPCBackend for NanoMsg {
+    type Worker = NanoMsgWorker;
+
     fn new() -> Self {
         Self {
-            socket: Socket::new(Protocol::Rep0).unwrap(),
+            socket: Mutex::new(None),
+            publisher: Mutex::new(None),
         }
     }
 
     fn bind_and_listen(&self, address: &str) -> Result<(), anyhow::Error> {
-        self.socket.listen(address)?;
+        let socket = Socket::new(Protocol::Rep0)?;
+        socket.listen(address)?;
+        *lock(&self.socket) = Some(socket);
+        Ok(())
+    }
+
+    fn worker(&self) -> Result<Self::Worker, anyhow::Error> {
+        let socket = lock(&self.socket)
+            .clone()
+            .ok_or_else(|| anyhow!("the backend is not listening"))?;
+
+        // every worker has its own context, so that requests from different clients
+        // can be answered out of order
+        let context = Context::new(&socket)?;
+
+        // the results of asynchronous operations are passed back to the worker thread
+        let (sender, results) = mpsc::channel();
+        let aio = Aio::new(move |_, result| {
+            let _ = sender.send(result);
+        })?;
+
+        Ok(NanoMsgWorker {
+            context,
+            aio,
+            results,
+        })
+    }
+
+    fn bind_publisher(&self, address: &str) -> Result<(), anyhow::Error> {
+        let publisher = Socket::new(Protocol::Pub0)?;
+        publisher.listen(address)?;
+        *lock(&self.publisher) = Some(publisher);
+        Ok(())
+    }
+
+    fn publish(&self, event: &EventMessage) -> Result<(), anyhow::Error> {
+        // events are dropped if no publisher was bound
+        let Some(publisher) = lock(&self.publisher).clone() else {
+            return Ok(());
+        };
+
+        // subscribers filter on the topic at the start of the message
+        let message = event.to_message()?;
+        if let Err(err) = publisher.send(message.as_bytes()) {
+            return Err(err.1.into());
+        }
         Ok(())
     }
 
-    fn recv_data(
-        &self,
-    ) -> Result<crate::protocol::client_request::ClientRequestMessage, anyhow::Error> {
+    // nng does not tell publishers whether anyone is subscribed
+    fn has_subscribers(&self) -> bool {
+        lock(&self.publisher).is_some()
+    }
+
+    fn close(&self) {
+        for socket in [&self.socket, &self.publisher] {
+            if let Some(socket) = lock(socket).take() {
+                socket.close();
+            }
+        }
+    }
+}
+
+/// Lock a socket that is created once it starts listening
+fn lock(socket: &Mutex<Option<Socket>>) -> MutexGuard<'_, Option<Socket>> {
+    socket.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Handles the requests of one nng context
+pub struct NanoMsgWorker {
+    context: Context,
+    aio: Aio,
+    results: Receiver<AioResult>,
+}
+
+impl IPCWorker for NanoMsgWorker {
+    fn recv_data(&self) -> Result<ClientRequestMessage, anyhow::Error> {
         // recieve data from client
-        let msg = self.socket.recv()?;
+        self.context.recv(&self.aio)?;
+        let msg = match self.results.recv()? {
+            AioResult::Recv(result) => result?,
+            _ => return Err(anyhow!("unexpected result while receiving a request")),
+        };
 
         // Convert received message to a string
         let recieved = std::str::from_utf8(&msg)?;
@@ -32,16 +121,16 @@ impl IPCBackend for NanoMsg {
         Ok(client_response)
     }
 
-    fn send_data(
-        &self,
-        response: crate::protocol::server_response::ServerResponseMessage,
-    ) -> Result<(), anyhow::Error> {
+    fn send_data(&self, response: ServerResponseMessage) -> Result<(), anyhow::Error> {
         let json = serde_json::to_string(&response)?;
 
-        match self.socket.send(json.as_bytes()) {
-            Ok(it) => it,
-            Err(err) => return Err(err.1.into()),
-        };
-        Ok(())
+        if let Err(err) = self.context.send(&self.aio, json.as_bytes()) {
+            return Err(err.1.into());
+        }
+        match self.results.recv()? {
+            AioResult::Send(Ok(())) => Ok(()),
+            AioResult::Send(Err(err)) => Err(err.1.into()),
+            _ => Err(anyhow!("unexpected result while sending a response")),
+        }
     }
 }
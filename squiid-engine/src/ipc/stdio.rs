@@ -0,0 +1,60 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::anyhow;
+
+use crate::protocol::event::EventMessage;
+
+use super::{
+    json_lines::{JsonLinesWorker, RequestQueue},
+    IPCBackend,
+};
+
+/// Answers requests read from stdin, one JSON object per line, on stdout
+pub struct Stdio {
+    requests: RequestQueue,
+}
+
+impl IPCBackend for Stdio {
+    type Worker = JsonLinesWorker;
+
+    fn new() -> Self {
+        Self {
+            requests: RequestQueue::new(),
+        }
+    }
+
+    fn bind_and_listen(&self, _address: &str) -> Result<(), anyhow::Error> {
+        let requests = self.requests.clone();
+        thread::spawn(move || {
+            requests.read(io::stdin().lock(), Arc::new(Mutex::new(io::stdout())));
+
+            // stop once every request has been answered, since there is only one client
+            requests.end();
+        });
+        Ok(())
+    }
+
+    fn worker(&self) -> Result<Self::Worker, anyhow::Error> {
+        Ok(self.requests.worker())
+    }
+
+    fn bind_publisher(&self, _address: &str) -> Result<(), anyhow::Error> {
+        Err(anyhow!("the stdio backend cannot publish events"))
+    }
+
+    fn publish(&self, _event: &EventMessage) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    fn has_subscribers(&self) -> bool {
+        false
+    }
+
+    fn close(&self) {
+        self.requests.close();
+    }
+}
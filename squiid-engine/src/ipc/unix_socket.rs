@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    io::{BufReader, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+use crate::protocol::event::EventMessage;
+
+use super::{
+    json_lines::{JsonLinesWorker, RequestQueue},
+    IPCBackend,
+};
+
+/// Answers requests sent over a Unix domain socket, one JSON object per line
+pub struct UnixSocket {
+    requests: RequestQueue,
+    /// Connections of the clients that events are published to
+    subscribers: Arc<Mutex<Vec<UnixStream>>>,
+    /// Socket files that are removed when the backend is closed
+    paths: Mutex<Vec<PathBuf>>,
+}
+
+impl IPCBackend for UnixSocket {
+    type Worker = JsonLinesWorker;
+
+    fn new() -> Self {
+        Self {
+            requests: RequestQueue::new(),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            paths: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn bind_and_listen(&self, address: &str) -> Result<(), anyhow::Error> {
+        let listener = self.bind(address)?;
+        let requests = self.requests.clone();
+
+        // read the requests of every client on its own thread
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Ok(writer) = stream.try_clone() else {
+                    continue;
+                };
+                let requests = requests.clone();
+                thread::spawn(move || {
+                    requests.read(BufReader::new(stream), Arc::new(Mutex::new(writer)))
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn worker(&self) -> Result<Self::Worker, anyhow::Error> {
+        Ok(self.requests.worker())
+    }
+
+    fn bind_publisher(&self, address: &str) -> Result<(), anyhow::Error> {
+        let listener = self.bind(address)?;
+        let subscribers = self.subscribers.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // a subscriber that does not read its events is dropped instead of
+                // blocking the events of every other subscriber
+                if stream.set_nonblocking(true).is_err() {
+                    continue;
+                }
+                subscribers
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(stream);
+            }
+        });
+        Ok(())
+    }
+
+    fn publish(&self, event: &EventMessage) -> Result<(), anyhow::Error> {
+        let message = event.to_message()? + "\n";
+
+        // forget subscribers that disconnected or whose events are not being read. They
+        // are disconnected, since part of the event may already have been written
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain_mut(|subscriber| {
+                let written = subscriber.write_all(message.as_bytes()).is_ok();
+                if !written {
+                    let _ = subscriber.shutdown(Shutdown::Both);
+                }
+                written
+            });
+        Ok(())
+    }
+
+    fn has_subscribers(&self) -> bool {
+        !self
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    fn close(&self) {
+        self.requests.close();
+        for path in self
+            .paths
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl UnixSocket {
+    /// Listen on the socket file at an address, which may start with `unix://`
+    fn bind(&self, address: &str) -> Result<UnixListener, anyhow::Error> {
+        let path = Path::new(address.strip_prefix("unix://").unwrap_or(address));
+        let listener = UnixListener::bind(path)?;
+        self.paths
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(path.to_path_buf());
+        Ok(listener)
+    }
+}
@@ -1,51 +1,143 @@
+pub mod algebraic;
 pub mod bucket;
+#[cfg(feature = "ipc")]
+pub mod client;
 pub mod command_mappings;
 pub mod config_handler;
+pub mod continued_fraction;
 pub mod crash_reporter;
 pub mod engine;
+pub mod error;
+pub mod history;
+pub mod interrupt;
+pub mod modular;
+pub mod polynomial;
+#[cfg(feature = "ipc")]
+pub mod server;
+pub mod session;
+pub mod session_manager;
+pub mod statistics;
 pub mod utils;
 
 pub mod protocol {
+    /// Version of the JSON protocol. Increased when the protocol changes incompatibly
+    pub const PROTOCOL_VERSION: u32 = 1;
+
     pub mod client_request;
+    pub mod event;
     pub mod server_response;
 }
 
-#[cfg(feature = "ipc")]
+#[cfg(feature = "nng")]
 pub mod ffi;
 
 #[cfg(feature = "ipc")]
 pub mod ipc;
 
-use std::{borrow::BorrowMut, panic};
+use std::{borrow::BorrowMut, collections::HashMap};
 
+#[cfg(feature = "ipc")]
+use anyhow::Context;
 use bucket::Bucket;
-use command_mappings::CommandsMap;
-use engine::Engine;
+use command_mappings::CommandRegistry;
+use engine::{Engine, EngineBuilder};
+use error::{EngineError, ErrorKind};
+use history::Checkpoint;
+#[cfg(feature = "ipc")]
+use server::{Server, ShutdownHandle};
+#[cfg(feature = "ipc")]
+use session::Session;
+use utils::ID_REGEX;
 
 #[cfg(feature = "ipc")]
 use crate::protocol::{
-    client_request::{ConfigurationActionType, ConfigurationPayload, RequestPayload, RequestType},
-    server_response::{MessageAction, ResponsePayload, ResponseType},
+    client_request::{
+        ConfigurationActionType, ConfigurationPayload, VariablesActionType, VariablesPayload,
+    },
+    server_response::MessageAction,
 };
 
+/// Commands that do not affect the stack. These are not added to the undo history
+/// and are evaluated immediately even while a program is being entered
+const NON_HISTORY_COMMANDS: [&str; 10] = [
+    "refresh",
+    "commands",
+    "commandinfo",
+    "history",
+    "statlist",
+    "getmod",
+    "update_previous_answer",
+    "undo",
+    "redo",
+    "branch",
+];
+
 #[cfg(feature = "ipc")]
-/// The default address to start the server on
-const DEFAULT_ADDRESS: &str = "tcp://*:33242";
+/// The default address to start the server on. Only clients on the same machine can connect
+pub const DEFAULT_ADDRESS: &str = "tcp://127.0.0.1:33242";
+
+#[cfg(feature = "ipc")]
+/// Start the server with an IPC backend at the given address (default is DEFAULT_ADDRESS).
+/// If a token is given, it overrides the `server_token` config key, and requests without
+/// it are rejected. Returns once the default session quits, or with an error if the
+/// server could not be started
+pub fn start_server<B: ipc::IPCBackend>(
+    address: Option<&str>,
+    token: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    start_server_with_shutdown::<B>(address, token, ShutdownHandle::new())
+}
+
+#[cfg(feature = "ipc")]
+/// Start the server like `start_server`, and also stop it when `shutdown` is triggered.
+/// The running requests are cancelled and the default session is saved before returning
+pub fn start_server_with_shutdown<B: ipc::IPCBackend>(
+    address: Option<&str>,
+    token: Option<&str>,
+    shutdown: ShutdownHandle,
+) -> Result<(), anyhow::Error> {
+    // create the engine of the default session, which is used by clients that do not open a session
+    let mut engine = EngineBuilder::new().user_config().build()?;
+
+    // restore the session that was saved when the server last quit
+    let autosave_path = session::autosave_path();
+    if engine.persist_session() && autosave_path.exists() {
+        if let Ok(saved_session) = Session::read(&autosave_path) {
+            saved_session.restore(&mut engine);
+        }
+    }
+
+    serve_engine::<B>(address, token, engine, shutdown, true)
+}
+
+#[cfg(feature = "ipc")]
+/// Start the server like `start_server_with_shutdown`, with `engine` as the engine of the
+/// default session. Use `EngineBuilder` to construct it with a config other than the user's.
+/// The session saved when quitting is not restored, and the default session is not saved
+pub fn start_server_with<B: ipc::IPCBackend>(
+    address: Option<&str>,
+    token: Option<&str>,
+    engine: Engine,
+    shutdown: ShutdownHandle,
+) -> Result<(), anyhow::Error> {
+    serve_engine::<B>(address, token, engine, shutdown, false)
+}
 
 #[cfg(feature = "ipc")]
-/// Start the server at the given address (default is DEFAULT_ADDRESS)
-pub fn start_server(address: Option<&str>) {
+/// Bind the backend and answer requests with `engine` as the engine of the default session.
+/// If `autosave` is set, the default session is saved when the server stops
+fn serve_engine<B: ipc::IPCBackend>(
+    address: Option<&str>,
+    token: Option<&str>,
+    engine: Engine,
+    shutdown: ShutdownHandle,
+    autosave: bool,
+) -> Result<(), anyhow::Error> {
     //TODO: document features
 
-    use crate::{ipc::IPCBackend, protocol::server_response::ServerResponseMessage};
+    // report panics while answering a request, which are sent to the client as errors
     #[cfg(not(feature = "disable-crash-reports"))]
-    panic::set_hook(Box::new(|panic_info| {
-        crash_reporter::crash_report(panic_info, true);
-
-        // propegate panic for frontend to handle
-        // TODO: document this
-        std::process::exit(2);
-    }));
+    crash_reporter::install_hook();
 
     // Use default address unless one was specified from the command line
 
@@ -54,163 +146,154 @@ pub fn start_server(address: Option<&str>) {
         None => DEFAULT_ADDRESS,
     };
 
-    // create nng IPC wrapper
-    let ipc_wrapper = ipc::nng::NanoMsg::new();
+    // create IPC wrapper
+    let ipc_wrapper = B::new();
 
     // Print and bind to selected port
-    assert!(
-        ipc_wrapper.bind_and_listen(address_to_bind).is_ok(),
-        "could not bind to address {:?}. if you're using this as a shared object file, are you encoding the input to utf-8 bytes?",
-        address_to_bind
-    );
-
-    // create message variable and engine
-    let mut engine = Engine::new();
-
-    // create hashmap of available commands
-    let commands = command_mappings::create_function_map();
-
-    // listen forever
-    loop {
-        // recieve message from client
-        let data = ipc_wrapper.recv_data();
-        // check if error was encountered when parsing JSON
-        let recieved = match data {
-            Ok(ref value) => value,
-            Err(_) => {
-                // send error back to client and continue loop
-                let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-                    ResponseType::Error,
-                    ResponsePayload::Error("invalid JSON data was sent to the server".to_string()),
-                ));
-                continue;
-            }
-        };
-
-        let result = match recieved.request_type {
-            RequestType::Input => handle_data(
-                &mut engine,
-                &commands,
-                extract_data!(&recieved.payload, RequestPayload::Input),
-            ),
-            RequestType::Configuration => handle_config_data(
-                &mut engine,
-                extract_data!(recieved.payload.clone(), RequestPayload::Configuration),
-            ),
-        };
-
-        match result {
-            Ok(MessageAction::SendStack) => {
-                let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-                    ResponseType::Stack,
-                    ResponsePayload::Stack(engine.stack.clone()),
-                ));
-            }
-            Ok(MessageAction::SendConfigValue(config_value)) => {
-                let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-                    ResponseType::Configuration,
-                    ResponsePayload::Configuration(config_value.into()),
-                ));
-            }
-            Ok(MessageAction::SendCommands) => {
-                let avaiable_commands: Vec<String> =
-                    commands.keys().map(|k| k.to_owned()).collect();
+    ipc_wrapper
+        .bind_and_listen(address_to_bind)
+        .with_context(|| format!("could not bind to address {:?}", address_to_bind))?;
 
-                let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-                    ResponseType::Commands,
-                    ResponsePayload::Commands(avaiable_commands),
-                ));
-            }
-            Ok(MessageAction::SendPrevAnswer) => {
-                let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-                    ResponseType::PrevAnswer,
-                    ResponsePayload::PrevAnswer(engine.previous_answer.clone()),
-                ));
-            }
-            Ok(MessageAction::Quit) => break,
-            Err(error) => {
-                let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-                    ResponseType::Error,
-                    ResponsePayload::Error(error.to_string()),
-                ));
-            }
+    let token = token
+        .map(String::from)
+        .or_else(|| server::configured_token(&engine))
+        .or_else(|| {
+            B::REQUIRES_TOKEN.then(|| {
+                let token = server::generate_token();
+                eprintln!(
+                    "requests must include the token {}. Open {}/?token={} to use the browser frontend",
+                    token, address_to_bind, token
+                );
+                token
+            })
+        });
+
+    // publish changes to subscribers if an address is configured. Subscribers cannot
+    // authenticate, so nothing is published if requests require a token. Requests are
+    // still answered if the backend cannot publish
+    if let Some(publish_address) = server::publish_address(&engine) {
+        if token.is_some() {
+            eprintln!(
+                "not publishing changes on address {:?}, since subscribers cannot authenticate with the token of the server",
+                publish_address
+            );
+        } else if let Err(err) = ipc_wrapper.bind_publisher(&publish_address) {
+            eprintln!(
+                "could not publish changes on address {:?}: {}",
+                publish_address, err
+            );
         }
     }
 
-    // send quit message to client
-    let _ = ipc_wrapper.send_data(ServerResponseMessage::new(
-        ResponseType::QuitSig,
-        ResponsePayload::QuitSig(None),
-    ));
+    // answer requests until the default session quits or the server is shut down
+    let workers = server::worker_count(&engine);
+    Server::new(engine)
+        .with_token(token)
+        .with_shutdown(shutdown)
+        .with_autosave(autosave)
+        .serve(&ipc_wrapper, workers)
 }
 
 pub fn handle_data(
     engine: &mut Engine,
-    commands: &CommandsMap,
+    commands: &CommandRegistry,
     data: &str,
-) -> Result<MessageAction, String> {
-    if engine.undo_history.len() > 20 {
-        _ = engine.undo_history.pop_front();
-        _ = engine.undo_variable_history.pop_front();
-    }
-
-    // Don't add to history if command is refresh, commands, or update_previous_answer as it does not affect the stack
-    if ![
-        "refresh",
-        "commands",
-        "update_previous_answer",
-        "undo",
-        "redo",
-    ]
-    .contains(&data)
+) -> Result<MessageAction, EngineError> {
+    // record the tokens of a program between `{` and `}` instead of evaluating them
+    let program_literal;
+    let data = if (engine.program_depth > 0 || data == "{") && !NON_HISTORY_COMMANDS.contains(&data)
     {
-        // reset everything in front of the undo history pointer
-        engine.undo_history.drain(
-            engine
-                .undo_history
-                .len()
-                .saturating_sub(engine.undo_state_pointer as usize)..,
-        );
-        engine.undo_variable_history.drain(
-            engine
-                .undo_variable_history
-                .len()
-                .saturating_sub(engine.undo_state_pointer as usize)..,
-        );
-        // reset history pointer
-        engine.undo_state_pointer = 0;
-
-        // Add current stack to history
-        engine.undo_history.push_back(engine.stack.clone());
-        // Add current variable state to history
-        engine
-            .undo_variable_history
-            .push_back(engine.variables.clone());
+        match engine.record_program_token(data) {
+            Some(literal) => {
+                program_literal = literal;
+                program_literal.as_str()
+            }
+            None => return Ok(MessageAction::SendStack),
+        }
+    } else {
+        data
+    };
+
+    // commands that do not affect the stack are not added to the history
+    let record_history = !NON_HISTORY_COMMANDS.contains(&data);
+    if record_history {
+        // keep changes made outside of handle_data out of this command's history entry
+        engine.synchronize_history();
     }
 
     let result = match commands.get(data) {
-        Some(func) => func(engine.borrow_mut()),
+        Some(command) => {
+            (command.function)(engine.borrow_mut()).map_err(|error| error.with_command(data))
+        }
         None => {
             // return result value of adding item to stack
             engine.add_item_to_stack(Bucket::from(data.to_string()))
         }
     };
 
+    if record_history {
+        engine.record_history(data);
+    }
+
     result
 }
 
+/// Evaluate a list of commands in order as one entry in the undo history.
+/// If any command fails, the engine is left as it was before the batch and the
+/// error carries the index of the failing command
+pub fn handle_batch_data(
+    engine: &mut Engine,
+    commands: &CommandRegistry,
+    data: &[String],
+) -> Result<MessageAction, EngineError> {
+    // keep changes made outside of the batch out of its history entry
+    engine.synchronize_history();
+    let history = engine.history.clone();
+    let checkpoint = Checkpoint::capture(engine);
+    let checkpoints = engine.checkpoints.clone();
+    let program_recording = engine.program_recording.clone();
+    let program_depth = engine.program_depth;
+
+    for (index, command) in data.iter().enumerate() {
+        if let Err(error) = engine
+            .interrupt
+            .check()
+            .and_then(|_| handle_data(engine, commands, command))
+        {
+            checkpoint.restore(engine);
+            engine.checkpoints = checkpoints;
+            engine.history = history;
+            engine.program_recording = program_recording;
+            engine.program_depth = program_depth;
+            return Err(error.at_index(index));
+        }
+    }
+
+    // replace the entries of the individual commands with one entry for the batch
+    engine.history = history;
+    engine.record_history(&data.join(" "));
+
+    Ok(MessageAction::SendStack)
+}
+
 /// handle config data sent to the server
 pub fn handle_config_data(
     engine: &mut Engine,
     data: ConfigurationPayload,
-) -> Result<MessageAction, String> {
+) -> Result<MessageAction, EngineError> {
     let value_option = match data.action_type {
         ConfigurationActionType::GetKey => {
             if data.section.is_none() {
-                return Err("config section not provided in GetKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in GetKey",
+                ));
             }
             if data.key.is_none() {
-                return Err("config key not provided in GetKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config key not provided in GetKey",
+                ));
             }
             engine
                 .config
@@ -219,31 +302,49 @@ pub fn handle_config_data(
         ConfigurationActionType::ListSections => engine.config.list_sections(),
         ConfigurationActionType::ListKeys => {
             if data.section.is_none() {
-                return Err("config section not provided in ListKeys".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in ListKeys",
+                ));
             }
             engine.config.list_keys(&data.section.unwrap())
         }
         ConfigurationActionType::ListValues => {
             if data.section.is_none() {
-                return Err("config section not provided in ListValues".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in ListValues",
+                ));
             }
             engine.config.list_values(&data.section.unwrap())
         }
         ConfigurationActionType::ListItems => {
             if data.section.is_none() {
-                return Err("config section not provided in ListItems".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in ListItems",
+                ));
             }
             engine.config.list_items(&data.section.unwrap())
         }
         ConfigurationActionType::SetKey => {
             if data.section.is_none() {
-                return Err("config section not provided in SetKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in SetKey",
+                ));
             }
             if data.key.is_none() {
-                return Err("config key not provided in SetKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config key not provided in SetKey",
+                ));
             }
             if data.value.is_none() {
-                return Err("config value not provided in SetKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config value not provided in SetKey",
+                ));
             }
             engine.config.set_key(
                 &data.section.unwrap(),
@@ -253,22 +354,34 @@ pub fn handle_config_data(
         }
         ConfigurationActionType::CreateSection => {
             if data.section.is_none() {
-                return Err("config section not provided in CreateSection".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in CreateSection",
+                ));
             }
             engine.config.create_section(&data.section.unwrap())
         }
         ConfigurationActionType::DeleteSection => {
             if data.section.is_none() {
-                return Err("config section not provided in DeleteSection".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in DeleteSection",
+                ));
             }
             engine.config.delete_section(&data.section.unwrap())
         }
         ConfigurationActionType::DeleteKey => {
             if data.section.is_none() {
-                return Err("config section not provided in DeleteKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config section not provided in DeleteKey",
+                ));
             }
             if data.key.is_none() {
-                return Err("config key not provided in DeleteKey".to_string());
+                return Err(EngineError::new(
+                    ErrorKind::InvalidRequest,
+                    "config key not provided in DeleteKey",
+                ));
             }
             engine
                 .config
@@ -278,6 +391,82 @@ pub fn handle_config_data(
 
     match value_option {
         Ok(item) => Ok(MessageAction::SendConfigValue(item)),
-        Err(e) => Err(e),
+        Err(e) => Err(EngineError::new(ErrorKind::Configuration, e)),
     }
 }
+
+/// handle variables data sent to the server
+pub fn handle_variables_data(
+    engine: &mut Engine,
+    data: VariablesPayload,
+) -> Result<MessageAction, EngineError> {
+    let (name, value) = match data.action_type {
+        VariablesActionType::List => {
+            return Ok(MessageAction::SendVariables(engine.variables.clone()))
+        }
+        VariablesActionType::Get => {
+            let name = variable_name(data.name, "Get")?;
+            match engine.variables.get(&name) {
+                Some(value) => {
+                    let value = value.clone();
+                    (name, value)
+                }
+                None => return Err(undefined_variable(&name)),
+            }
+        }
+        VariablesActionType::Set => {
+            let name = variable_name(data.name, "Set")?;
+            let value = match data.value {
+                Some(value) => Bucket::try_from(value)
+                    .map_err(|error| EngineError::new(ErrorKind::InvalidRequest, error))?,
+                None => {
+                    return Err(EngineError::new(
+                        ErrorKind::InvalidRequest,
+                        "variable value not provided in Set",
+                    ))
+                }
+            };
+
+            engine.synchronize_history();
+            engine.variables.insert(name.clone(), value.clone());
+            engine.record_history(&format!("set {}", name));
+            (name, value)
+        }
+        VariablesActionType::Delete => {
+            let name = variable_name(data.name, "Delete")?;
+            if !engine.variables.contains_key(&name) {
+                return Err(undefined_variable(&name));
+            }
+
+            engine.synchronize_history();
+            let value = engine.variables.remove(&name).unwrap();
+            engine.record_history(&format!("delete {}", name));
+            (name, value)
+        }
+    };
+
+    Ok(MessageAction::SendVariables(HashMap::from([(name, value)])))
+}
+
+/// Check the variable name of a variables request
+fn variable_name(name: Option<String>, action: &str) -> Result<String, EngineError> {
+    match name {
+        Some(name) if ID_REGEX.is_match(&name) => Ok(name),
+        Some(name) => Err(EngineError::new(
+            ErrorKind::InvalidName,
+            format!("`{}` is not a valid variable name", name),
+        )),
+        None => Err(EngineError::new(
+            ErrorKind::InvalidRequest,
+            format!("variable name not provided in {}", action),
+        )),
+    }
+}
+
+/// Construct an error for a variable that has not been set
+fn undefined_variable(name: &str) -> EngineError {
+    EngineError::new(
+        ErrorKind::UndefinedVariable,
+        format!("Variable `{}` does not exist", name),
+    )
+}
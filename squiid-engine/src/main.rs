@@ -1,13 +1,85 @@
 // pub mod lib;
-use squiid_engine::start_server;
+use squiid_engine::{server::ShutdownHandle, start_server_with_shutdown, DEFAULT_ADDRESS};
 use std::env;
 fn main() {
-    // Get command line arguments
-    let args: Vec<String> = env::args().collect();
+    // Get command line arguments: an optional address and an optional `--token <TOKEN>`
+    let mut address = DEFAULT_ADDRESS.to_string();
+    let mut token = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--token" {
+            match args.next() {
+                Some(value) => token = Some(value),
+                None => {
+                    eprintln!("--token requires a value");
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            address = arg;
+        }
+    }
+    let (address, token) = (address.as_str(), token.as_deref());
+
+    // stop cleanly on SIGINT and SIGTERM, saving the default session
+    let shutdown = ShutdownHandle::new();
+    #[cfg(unix)]
+    {
+        use signal_hook::{
+            consts::{SIGINT, SIGTERM},
+            iterator::Signals,
+        };
+
+        match Signals::new([SIGINT, SIGTERM]) {
+            Ok(mut signals) => {
+                let shutdown = shutdown.clone();
+                std::thread::spawn(move || {
+                    if signals.forever().next().is_some() {
+                        shutdown.shutdown();
+                    }
+                });
+            }
+            Err(err) => eprintln!("could not handle signals: {}", err),
+        }
+    }
+
+    // the address selects the IPC backend
+    let result = match address {
+        #[cfg(feature = "stdio")]
+        "stdio" => {
+            start_server_with_shutdown::<squiid_engine::ipc::stdio::Stdio>(None, token, shutdown)
+        }
+        #[cfg(feature = "http")]
+        http_address if http_address.starts_with("http://") => {
+            start_server_with_shutdown::<squiid_engine::ipc::http::Http>(
+                Some(http_address),
+                token,
+                shutdown,
+            )
+        }
+        #[cfg(all(feature = "unix-socket", unix))]
+        unix_address if unix_address.starts_with("unix://") => {
+            start_server_with_shutdown::<squiid_engine::ipc::unix_socket::UnixSocket>(
+                Some(unix_address),
+                token,
+                shutdown,
+            )
+        }
+        #[cfg(feature = "nng")]
+        nng_address => start_server_with_shutdown::<squiid_engine::ipc::nng::NanoMsg>(
+            Some(nng_address),
+            token,
+            shutdown,
+        ),
+        #[cfg(not(feature = "nng"))]
+        unsupported_address => Err(anyhow::anyhow!(
+            "unsupported address {:?}",
+            unsupported_address
+        )),
+    };
 
-    let mut address = "tcp://*:33242";
-    if args.len() > 1 {
-        address = &args[1];
+    if let Err(err) = result {
+        eprintln!("{:#}", err);
+        std::process::exit(1);
     }
-    start_server(Some(address));
 }
@@ -0,0 +1,77 @@
+// integer helpers used by modular arithmetic mode
+// every result is reduced into the range 0..modulus
+
+/// Reduce a value into the range 0..modulus
+pub fn reduce(value: i128, modulus: u64) -> u64 {
+    value.rem_euclid(modulus as i128) as u64
+}
+
+/// Add two values modulo n
+pub fn add(left: i128, right: i128, modulus: u64) -> Result<u64, String> {
+    let sum = reduce(left, modulus) as u128 + reduce(right, modulus) as u128;
+    Ok((sum % modulus as u128) as u64)
+}
+
+/// Subtract two values modulo n
+pub fn subtract(left: i128, right: i128, modulus: u64) -> Result<u64, String> {
+    let difference = reduce(left, modulus) as i128 - reduce(right, modulus) as i128;
+    Ok(reduce(difference, modulus))
+}
+
+/// Multiply two values modulo n
+pub fn multiply(left: i128, right: i128, modulus: u64) -> Result<u64, String> {
+    let product = reduce(left, modulus) as u128 * reduce(right, modulus) as u128;
+    Ok((product % modulus as u128) as u64)
+}
+
+/// Divide two values modulo n by multiplying with the modular inverse of the divisor
+pub fn divide(left: i128, right: i128, modulus: u64) -> Result<u64, String> {
+    match inverse(right, modulus) {
+        Some(inverse) => multiply(left, inverse as i128, modulus),
+        None => Err(format!("{} has no inverse modulo {}", right, modulus)),
+    }
+}
+
+/// Raise a value to an integer power modulo n using square-and-multiply.
+/// Negative exponents use the modular inverse of the base
+pub fn power(base: i128, exponent: i128, modulus: u64) -> Result<u64, String> {
+    let mut base = if exponent < 0 {
+        match inverse(base, modulus) {
+            Some(inverse) => inverse,
+            None => return Err(format!("{} has no inverse modulo {}", base, modulus)),
+        }
+    } else {
+        reduce(base, modulus)
+    } as u128;
+    let mut exponent = exponent.unsigned_abs();
+
+    let mut result = 1 % modulus as u128;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus as u128;
+        }
+        base = base * base % modulus as u128;
+        exponent >>= 1;
+    }
+
+    Ok(result as u64)
+}
+
+/// Find the modular inverse of a value with the extended euclidean algorithm.
+/// Returns None if the value and modulus are not coprime
+pub fn inverse(value: i128, modulus: u64) -> Option<u64> {
+    let (mut old_r, mut r) = (reduce(value, modulus) as i128, modulus as i128);
+    let (mut old_s, mut s) = (1_i128, 0_i128);
+
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+
+    if old_r == 1 {
+        Some(reduce(old_s, modulus))
+    } else {
+        None
+    }
+}
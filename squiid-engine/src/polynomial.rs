@@ -0,0 +1,420 @@
+// polynomial math helpers used by the poly* engine commands
+// coefficients are always ordered from the highest degree to the constant term
+
+use std::{
+    fmt,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use crate::{error::EngineError, interrupt::Interrupt};
+
+/// Maximum number of Durand-Kerner iterations before giving up on convergence
+const MAX_ROOT_ITERATIONS: usize = 1000;
+
+/// Number of significant digits that roots are rounded to in order to hide floating point
+/// noise. Both parts of a complex root are rounded relative to its modulus
+const ROOT_PRECISION: i32 = 10;
+
+/// Number of significant digits that fitted coefficients are rounded to
+const FIT_PRECISION: usize = 12;
+
+/// Fitted coefficients whose term is smaller than the largest term over the data by this
+/// factor are treated as floating point noise
+const FIT_TOLERANCE: f64 = 1e-12;
+
+/// A minimal complex number used for root finding
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    /// Construct a new complex number
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// Absolute value (modulus)
+    pub fn abs(&self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// Square root using the principal branch
+    pub fn sqrt(&self) -> Self {
+        let modulus = self.abs();
+        let re = ((modulus + self.re) / 2.0).sqrt();
+        let im = ((modulus - self.re) / 2.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    /// Test if the imaginary part is exactly zero. Roots are rounded before they are
+    /// returned, so imaginary parts that are only floating point noise are zero
+    pub fn is_real(&self) -> bool {
+        self.im == 0.0
+    }
+
+    /// Round both components to `ROOT_PRECISION` significant digits of the modulus
+    fn rounded(&self) -> Self {
+        let modulus = self.abs();
+        if modulus == 0.0 || !modulus.is_finite() {
+            return *self;
+        }
+
+        let exponent = modulus.log10().floor() as i32;
+        Self::new(round_to(self.re, exponent), round_to(self.im, exponent))
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let denominator = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator,
+        )
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_real() {
+            write!(f, "{}", self.re)
+        } else if self.im < 0.0 {
+            write!(f, "{}-{}i", self.re, -self.im)
+        } else {
+            write!(f, "{}+{}i", self.re, self.im)
+        }
+    }
+}
+
+/// Round a float to `ROOT_PRECISION` significant digits of a number with the given decimal
+/// exponent, normalizing -0 to 0
+fn round_to(value: f64, exponent: i32) -> f64 {
+    let decimals = ROOT_PRECISION - 1 - exponent;
+    let rounded = if decimals >= 0 {
+        let factor = 10_f64.powi(decimals);
+        if !factor.is_finite() {
+            return value;
+        }
+        (value * factor).round() / factor
+    } else {
+        // divide by a power of ten rather than multiplying by its inverse, which is not exact
+        let factor = 10_f64.powi(-decimals);
+        (value / factor).round() * factor
+    };
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
+
+/// Remove leading zero coefficients. The zero polynomial is returned as `[0]`
+pub fn trim(coefficients: &[f64]) -> Vec<f64> {
+    match coefficients.iter().position(|c| *c != 0.0) {
+        Some(index) => coefficients[index..].to_vec(),
+        None => vec![0.0],
+    }
+}
+
+/// Evaluate a polynomial at x using Horner's method
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+/// Evaluate a polynomial at a complex point using Horner's method
+fn evaluate_complex(coefficients: &[f64], x: Complex) -> Complex {
+    coefficients.iter().fold(Complex::new(0.0, 0.0), |acc, c| {
+        acc * x + Complex::new(*c, 0.0)
+    })
+}
+
+/// Differentiate a polynomial
+pub fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    let degree = coefficients.len().saturating_sub(1);
+    if degree == 0 {
+        return vec![0.0];
+    }
+
+    coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(index, c)| c * (degree - index) as f64)
+        .collect()
+}
+
+/// Integrate a polynomial with an integration constant of 0
+pub fn integral(coefficients: &[f64]) -> Vec<f64> {
+    let degree = coefficients.len();
+
+    let mut result: Vec<f64> = coefficients
+        .iter()
+        .enumerate()
+        .map(|(index, c)| c / (degree - index) as f64)
+        .collect();
+    result.push(0.0);
+
+    trim(&result)
+}
+
+/// Multiply two polynomials
+pub fn multiply(left: &[f64], right: &[f64]) -> Vec<f64> {
+    if left.is_empty() || right.is_empty() {
+        return vec![0.0];
+    }
+
+    let mut result = vec![0.0; left.len() + right.len() - 1];
+    for (i, a) in left.iter().enumerate() {
+        for (j, b) in right.iter().enumerate() {
+            result[i + j] += a * b;
+        }
+    }
+
+    trim(&result)
+}
+
+/// Divide two polynomials using long division.
+/// Returns (quotient, remainder), or None when dividing by the zero polynomial
+pub fn divide(dividend: &[f64], divisor: &[f64]) -> Option<(Vec<f64>, Vec<f64>)> {
+    let divisor = trim(divisor);
+    if divisor == [0.0] {
+        return None;
+    }
+
+    let mut remainder = trim(dividend);
+    if remainder.len() < divisor.len() {
+        return Some((vec![0.0], remainder));
+    }
+
+    let quotient_len = remainder.len() - divisor.len() + 1;
+    let mut quotient = vec![0.0; quotient_len];
+
+    for index in 0..quotient_len {
+        let factor = remainder[index] / divisor[0];
+        quotient[index] = factor;
+        for (offset, d) in divisor.iter().enumerate() {
+            remainder[index + offset] -= factor * d;
+        }
+    }
+
+    let remainder = trim(&remainder[quotient_len..]);
+    Some((trim(&quotient), remainder))
+}
+
+/// Find every real and complex root of a polynomial, stopping with an error if the
+/// interrupt is triggered. Returns None if the polynomial is constant
+pub fn roots(
+    coefficients: &[f64],
+    interrupt: &Interrupt,
+) -> Result<Option<Vec<Complex>>, EngineError> {
+    let mut coefficients = trim(coefficients);
+    if coefficients.len() < 2 {
+        return Ok(None);
+    }
+
+    // trailing zero coefficients are roots at exactly 0
+    let mut found = Vec::new();
+    while coefficients.len() > 1 && *coefficients.last().unwrap() == 0.0 {
+        coefficients.pop();
+        found.push(Complex::new(0.0, 0.0));
+    }
+
+    // make the polynomial monic
+    let leading = coefficients[0];
+    let monic: Vec<f64> = coefficients.iter().map(|c| c / leading).collect();
+
+    match monic.len() - 1 {
+        0 => (),
+        1 => found.push(Complex::new(-monic[1], 0.0)),
+        2 => {
+            // quadratic formula, avoiding cancellation
+            let (b, c) = (monic[1], monic[2]);
+            let discriminant = Complex::new(b * b - 4.0 * c, 0.0).sqrt();
+            let sign = if b < 0.0 { -1.0 } else { 1.0 };
+            let q = (Complex::new(b, 0.0) + discriminant * Complex::new(sign, 0.0))
+                * Complex::new(-0.5, 0.0);
+            if q.abs() == 0.0 {
+                found.push(Complex::new(0.0, 0.0));
+                found.push(Complex::new(0.0, 0.0));
+            } else {
+                found.push(q);
+                found.push(Complex::new(c, 0.0) / q);
+            }
+        }
+        _ => found.extend(durand_kerner(&monic, interrupt)?),
+    }
+
+    let mut rounded: Vec<Complex> = found.iter().map(|root| root.rounded()).collect();
+    rounded.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+
+    Ok(Some(rounded))
+}
+
+/// Find all roots of a monic polynomial with the Durand-Kerner method
+fn durand_kerner(monic: &[f64], interrupt: &Interrupt) -> Result<Vec<Complex>, EngineError> {
+    let degree = monic.len() - 1;
+
+    // start from points spread around a circle that bounds every root
+    let radius = 1.0 + monic[1..].iter().fold(0.0_f64, |acc, c| acc.max(c.abs()));
+    let seed = Complex::new(0.4, 0.9);
+    let mut estimates: Vec<Complex> = (0..degree)
+        .map(|index| {
+            let mut point = Complex::new(1.0, 0.0);
+            for _ in 0..index {
+                point = point * seed;
+            }
+            point * Complex::new(radius / seed.abs().powi(index as i32), 0.0)
+        })
+        .collect();
+
+    for _ in 0..MAX_ROOT_ITERATIONS {
+        interrupt.check()?;
+        let mut largest_change: f64 = 0.0;
+
+        for i in 0..degree {
+            let mut denominator = Complex::new(1.0, 0.0);
+            for j in 0..degree {
+                if i != j {
+                    denominator = denominator * (estimates[i] - estimates[j]);
+                }
+            }
+
+            let change = evaluate_complex(monic, estimates[i]) / denominator;
+            estimates[i] = estimates[i] - change;
+            largest_change = largest_change.max(change.abs());
+        }
+
+        if largest_change < 1e-15 {
+            break;
+        }
+    }
+
+    Ok(estimates)
+}
+
+/// Fit a polynomial of the given degree to the x/y data using least squares, stopping
+/// with an error if the interrupt is triggered. Returns None if there is not enough data
+/// or the system is singular
+pub fn fit(
+    x_values: &[f64],
+    y_values: &[f64],
+    degree: usize,
+    interrupt: &Interrupt,
+) -> Result<Option<Vec<f64>>, EngineError> {
+    if x_values.len() != y_values.len() || x_values.len() <= degree {
+        return Ok(None);
+    }
+
+    let size = degree + 1;
+
+    // build the normal equations (A^T A) c = A^T y with powers from 0 to degree
+    let mut matrix = vec![vec![0.0; size + 1]; size];
+    for (x, y) in x_values.iter().zip(y_values.iter()) {
+        interrupt.check()?;
+        let powers: Vec<f64> = (0..size).map(|power| x.powi(power as i32)).collect();
+        for row in 0..size {
+            for column in 0..size {
+                matrix[row][column] += powers[row] * powers[column];
+            }
+            matrix[row][size] += powers[row] * y;
+        }
+    }
+
+    // gaussian elimination with partial pivoting
+    for column in 0..size {
+        interrupt.check()?;
+        let pivot = match (column..size).max_by(|a, b| {
+            matrix[*a][column]
+                .abs()
+                .total_cmp(&matrix[*b][column].abs())
+        }) {
+            Some(pivot) => pivot,
+            None => return Ok(None),
+        };
+        if matrix[pivot][column].abs() < f64::EPSILON {
+            return Ok(None);
+        }
+        matrix.swap(column, pivot);
+
+        let pivot_row = matrix[column].clone();
+        for (row_index, row) in matrix.iter_mut().enumerate() {
+            if row_index != column {
+                let factor = row[column] / pivot_row[column];
+                for (value, pivot_value) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    // solution is ordered lowest degree first
+    let coefficients: Vec<f64> = (0..size)
+        .map(|row| matrix[row][size] / matrix[row][row])
+        .collect();
+
+    // compare the size of each term over the data instead of the coefficients, so that
+    // small coefficients of large powers are kept
+    let x_scale = x_values.iter().fold(0.0_f64, |scale, x| scale.max(x.abs()));
+    let x_scale = if x_scale == 0.0 { 1.0 } else { x_scale };
+    let terms: Vec<f64> = coefficients
+        .iter()
+        .enumerate()
+        .map(|(power, coefficient)| (coefficient * x_scale.powi(power as i32)).abs())
+        .collect();
+    let largest_term = terms
+        .iter()
+        .fold(0.0_f64, |largest, term| largest.max(*term));
+
+    // reverse the solution to match the other functions
+    Ok(Some(
+        coefficients
+            .iter()
+            .zip(terms)
+            .rev()
+            .map(|(coefficient, term)| {
+                if term <= largest_term * FIT_TOLERANCE {
+                    0.0
+                } else {
+                    round_significant(*coefficient)
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Round a float to `FIT_PRECISION` significant digits
+fn round_significant(value: f64) -> f64 {
+    format!("{:.*e}", FIT_PRECISION - 1, value)
+        .parse()
+        .unwrap_or(value)
+}
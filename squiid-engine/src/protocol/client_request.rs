@@ -1,13 +1,30 @@
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
+use crate::bucket::TypedBucket;
+
 /// Client message datatype
 /// this is what we recieve from the client
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ClientRequestMessage {
     pub request_type: RequestType,
     #[serde(flatten)]
     pub payload: RequestPayload,
+    /// The ID of the session to evaluate the request in. Requests without a session
+    /// are evaluated in the default session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// The number of milliseconds the request may take to evaluate, overriding the
+    /// `request_timeout` config key. 0 lets the request run for any amount of time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Whether buckets in the response should be sent in their typed representation,
+    /// such as `{"type": "constant", "name": "pi", "value": "3.14..."}`, instead of strings
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub typed: bool,
+    /// The shared secret that the server requires in every request, if one is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 impl ClientRequestMessage {
@@ -15,8 +32,36 @@ impl ClientRequestMessage {
         Self {
             request_type,
             payload: message_payload,
+            session: None,
+            timeout: None,
+            typed: false,
+            token: None,
         }
     }
+
+    /// Evaluate the request in the session with the given ID
+    pub fn with_session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// Request buckets in the response in their typed representation
+    pub fn with_typed_buckets(mut self) -> Self {
+        self.typed = true;
+        self
+    }
+
+    /// Authenticate the request with the token of the server
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Stop evaluating the request after the given number of milliseconds
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 }
 
 /// Types of messages to be received from the client
@@ -26,19 +71,73 @@ pub enum RequestType {
     Input,
     #[serde(rename = "configuration")]
     Configuration,
+    /// Evaluate an algebraic expression as a single command
+    #[serde(rename = "algebraic")]
+    Algebraic,
+    /// Evaluate a list of commands atomically
+    #[serde(rename = "batch")]
+    Batch,
+    /// List, get, set, or delete variables
+    #[serde(rename = "variables")]
+    Variables,
+    /// Open a new session with its own engine
+    #[serde(rename = "open_session")]
+    OpenSession,
+    /// Close the session given in the request
+    #[serde(rename = "close_session")]
+    CloseSession,
+    /// Cancel the request that is being evaluated in the session given in the request
+    #[serde(rename = "cancel")]
+    Cancel,
+    /// Get the versions and capabilities of the server
+    #[serde(rename = "hello")]
+    Hello,
 }
 
-/// Types of message payloads to be received from the client
-#[derive(Deserialize, Serialize, Debug, Clone)]
+impl RequestType {
+    /// Every request type
+    pub const ALL: [RequestType; 9] = [
+        RequestType::Input,
+        RequestType::Configuration,
+        RequestType::Algebraic,
+        RequestType::Batch,
+        RequestType::Variables,
+        RequestType::OpenSession,
+        RequestType::CloseSession,
+        RequestType::Cancel,
+        RequestType::Hello,
+    ];
+
+    /// The name of the request type in the protocol
+    pub fn name(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(Value::String(name)) => name,
+            _ => unreachable!("request types are serialized as strings"),
+        }
+    }
+}
+
+/// Types of message payloads to be received from the client.
+/// Every variant has the same name, so payloads are deserialized as part of
+/// `ClientRequestMessage` depending on the request type
+#[derive(Serialize, Debug, Clone)]
 pub enum RequestPayload {
     #[serde(rename = "payload")]
     Input(String),
     #[serde(rename = "payload")]
     Configuration(ConfigurationPayload),
+    /// The commands of a batch request, evaluated in order
+    #[serde(rename = "payload")]
+    Batch(Vec<String>),
+    #[serde(rename = "payload")]
+    Variables(VariablesPayload),
+    /// Payload of requests that do not need one. This should always be set to None
+    #[serde(rename = "payload")]
+    Empty(Option<u8>),
 }
 
 /// configuration deserialization struct
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ConfigurationPayload {
     pub action_type: ConfigurationActionType,
     pub section: Option<String>,
@@ -62,8 +161,47 @@ impl ConfigurationPayload {
     }
 }
 
-/// configuration request action types
+/// variables deserialization struct
 #[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct VariablesPayload {
+    pub action_type: VariablesActionType,
+    /// The name of the variable, without the `$` prefix. Not used by `list`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The value to set the variable to. Only used by `set`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<TypedBucket>,
+}
+
+impl VariablesPayload {
+    pub fn new(
+        action_type: VariablesActionType,
+        name: Option<String>,
+        value: Option<TypedBucket>,
+    ) -> Self {
+        Self {
+            action_type,
+            name,
+            value,
+        }
+    }
+}
+
+/// variables request action types
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum VariablesActionType {
+    #[serde(rename = "list")]
+    List,
+    #[serde(rename = "get")]
+    Get,
+    #[serde(rename = "set")]
+    Set,
+    #[serde(rename = "delete")]
+    Delete,
+}
+
+/// configuration request action types
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum ConfigurationActionType {
     #[serde(rename = "get_key")]
     GetKey,
@@ -94,24 +232,85 @@ impl<'de> Deserialize<'de> for ClientRequestMessage {
     {
         let json_value: Value = Deserialize::deserialize(deserializer)?;
 
+        let session = match json_value.get("session") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(session)) => Some(session.clone()),
+            Some(_) => return Err(serde::de::Error::custom("session must be a string")),
+        };
+
+        let timeout = match json_value.get("timeout") {
+            None | Some(Value::Null) => None,
+            Some(timeout) => Some(timeout.as_u64().ok_or_else(|| {
+                serde::de::Error::custom("timeout must be a non-negative integer")
+            })?),
+        };
+
+        let typed = match json_value.get("typed") {
+            None | Some(Value::Null) => false,
+            Some(Value::Bool(typed)) => *typed,
+            Some(_) => return Err(serde::de::Error::custom("typed must be a boolean")),
+        };
+
+        let token = match json_value.get("token") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(token)) => Some(token.clone()),
+            Some(_) => return Err(serde::de::Error::custom("token must be a string")),
+        };
+
         match json_value.get("request_type") {
             Some(request_type_value) => {
                 let request_type: RequestType = serde_json::from_value(request_type_value.clone())
                     .map_err(serde::de::Error::custom)?;
 
                 match request_type {
-                    RequestType::Input => {
+                    RequestType::Input | RequestType::Algebraic => {
                         let payload_str = json_value
                             .get("payload")
                             .and_then(|v| v.as_str())
                             .ok_or_else(|| {
-                                serde::de::Error::custom(
-                                    "Missing or invalid payload for RequestType::Input",
-                                )
+                                serde::de::Error::custom(format!(
+                                    "Missing or invalid payload for RequestType::{:?}",
+                                    request_type
+                                ))
                             })?;
                         Ok(ClientRequestMessage {
-                            request_type: RequestType::Input,
+                            request_type,
                             payload: RequestPayload::Input(payload_str.to_string()),
+                            session,
+                            timeout,
+                            typed,
+                            token,
+                        })
+                    }
+                    RequestType::Batch => {
+                        let payload_value = json_value.get("payload").ok_or_else(|| {
+                            serde::de::Error::custom("Missing payload for RequestType::Batch")
+                        })?;
+                        let payload: Vec<String> = serde_json::from_value(payload_value.clone())
+                            .map_err(serde::de::Error::custom)?;
+                        Ok(ClientRequestMessage {
+                            request_type: RequestType::Batch,
+                            payload: RequestPayload::Batch(payload),
+                            session,
+                            timeout,
+                            typed,
+                            token,
+                        })
+                    }
+                    RequestType::Variables => {
+                        let payload_value = json_value.get("payload").ok_or_else(|| {
+                            serde::de::Error::custom("Missing payload for RequestType::Variables")
+                        })?;
+                        let payload: VariablesPayload =
+                            serde_json::from_value(payload_value.clone())
+                                .map_err(serde::de::Error::custom)?;
+                        Ok(ClientRequestMessage {
+                            request_type: RequestType::Variables,
+                            payload: RequestPayload::Variables(payload),
+                            session,
+                            timeout,
+                            typed,
+                            token,
                         })
                     }
                     RequestType::Configuration => {
@@ -126,8 +325,23 @@ impl<'de> Deserialize<'de> for ClientRequestMessage {
                         Ok(ClientRequestMessage {
                             request_type: RequestType::Configuration,
                             payload: RequestPayload::Configuration(payload),
+                            session,
+                            timeout,
+                            typed,
+                            token,
                         })
                     }
+                    RequestType::OpenSession
+                    | RequestType::CloseSession
+                    | RequestType::Cancel
+                    | RequestType::Hello => Ok(ClientRequestMessage {
+                        request_type,
+                        payload: RequestPayload::Empty(None),
+                        session,
+                        timeout,
+                        typed,
+                        token,
+                    }),
                 }
             }
             None => Err(serde::de::Error::custom("Missing request_type field")),
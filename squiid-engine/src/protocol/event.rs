@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bucket::{typed, Bucket},
+    protocol::client_request::ConfigurationPayload,
+};
+
+/// Topic of the events of requests without a session ID
+pub const DEFAULT_SESSION_TOPIC: &str = "default";
+
+/// A change to the state of a session that is published to subscribers
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EventMessage {
+    /// The session that changed, or None for the default session
+    pub session: Option<String>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl EventMessage {
+    pub fn new(session: Option<String>, event: Event) -> Self {
+        Self { session, event }
+    }
+
+    /// The topic subscribers can filter on, such as `default/stack`
+    pub fn topic(&self) -> String {
+        format!(
+            "{}/{}",
+            self.session.as_deref().unwrap_or(DEFAULT_SESSION_TOPIC),
+            self.event.name()
+        )
+    }
+
+    /// The message sent to subscribers: the topic, a space, and the event as JSON
+    pub fn to_message(&self) -> Result<String, serde_json::Error> {
+        Ok(format!("{} {}", self.topic(), serde_json::to_string(self)?))
+    }
+}
+
+/// Types of changes published to subscribers
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event_type", content = "payload")]
+pub enum Event {
+    /// The stack after it changed
+    #[serde(rename = "stack")]
+    Stack(#[serde(with = "typed")] Vec<Bucket>),
+    /// The variables that changed with their new values, or null if they were deleted
+    #[serde(rename = "variables")]
+    Variables(#[serde(with = "typed")] HashMap<String, Option<Bucket>>),
+    /// A configuration change that was made
+    #[serde(rename = "configuration")]
+    Configuration(ConfigurationPayload),
+}
+
+impl Event {
+    /// The name of the event type in topics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Stack(_) => "stack",
+            Event::Variables(_) => "variables",
+            Event::Configuration(_) => "configuration",
+        }
+    }
+}
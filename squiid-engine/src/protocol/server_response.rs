@@ -1,14 +1,31 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::bucket::Bucket;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+use crate::{
+    bucket::{
+        typed::{self, Typed},
+        Bucket,
+    },
+    command_mappings::CommandInfo,
+    error::EngineError,
+    history::HistorySummary,
+    protocol::{client_request::RequestType, PROTOCOL_VERSION},
+};
 
 /// Server response type for internal handling
 #[derive(Debug, PartialEq)]
 pub enum MessageAction {
     SendStack,
     SendCommands,
+    SendCommandInfo,
     SendConfigValue(ConfigValue),
     SendPrevAnswer,
+    SendHistory,
+    SendStatistics,
+    SendModulus,
+    SendResult(Option<Bucket>),
+    SendVariables(HashMap<String, Bucket>),
     Quit,
 }
 
@@ -70,10 +87,69 @@ fn toml_to_serde_json_value(toml_value: &toml::Value) -> serde_json::Value {
 }
 
 /// Response struct
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ServerResponseMessage {
     pub response_type: ResponseType,
     pub payload: ResponsePayload,
+    /// Changes caused by the request that clients may want to react to
+    #[serde(default)]
+    pub notifications: Vec<Notification>,
+    /// Whether the buckets in the payload are sent in their typed representation.
+    /// Typed buckets are always accepted when deserializing
+    #[serde(skip)]
+    pub typed: bool,
+}
+
+impl Serialize for ServerResponseMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let fields = if self.notifications.is_empty() { 2 } else { 3 };
+        let mut state = serializer.serialize_struct("ServerResponseMessage", fields)?;
+        state.serialize_field("response_type", &self.response_type)?;
+        if self.typed {
+            state.serialize_field("payload", &TypedPayload(&self.payload))?;
+        } else {
+            state.serialize_field("payload", &self.payload)?;
+        }
+        if !self.notifications.is_empty() {
+            state.serialize_field("notifications", &self.notifications)?;
+        }
+        state.end()
+    }
+}
+
+/// Serializes the buckets of a payload in their typed representation
+struct TypedPayload<'a>(&'a ResponsePayload);
+
+impl Serialize for TypedPayload<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            ResponsePayload::Stack(stack) => serializer.serialize_newtype_variant(
+                "ResponsePayload",
+                0,
+                "stack",
+                &stack.to_typed(),
+            ),
+            ResponsePayload::PrevAnswer(answer) => serializer.serialize_newtype_variant(
+                "ResponsePayload",
+                6,
+                "previous_answer",
+                &answer.to_typed(),
+            ),
+            ResponsePayload::Result(result) => serializer.serialize_newtype_variant(
+                "ResponsePayload",
+                10,
+                "result",
+                &result.to_typed(),
+            ),
+            payload => payload.serialize(serializer),
+        }
+    }
 }
 
 impl ServerResponseMessage {
@@ -81,8 +157,30 @@ impl ServerResponseMessage {
         Self {
             response_type,
             payload: message_payload,
+            notifications: Vec::new(),
+            typed: false,
         }
     }
+
+    /// Send the buckets in the payload in their typed representation
+    pub fn with_typed_buckets(mut self, typed: bool) -> Self {
+        self.typed = typed;
+        self
+    }
+
+    /// Attach a notification to the response
+    pub fn with_notification(mut self, notification: Notification) -> Self {
+        self.notifications.push(notification);
+        self
+    }
+}
+
+/// Changes caused by a request that are sent along with its response
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// The names of the variables that were set or deleted, in alphabetical order
+    #[serde(rename = "variables_changed")]
+    VariablesChanged(Vec<String>),
 }
 
 /// Types of messages to send back to the client
@@ -94,12 +192,30 @@ pub enum ResponseType {
     Error,
     #[serde(rename = "commands")]
     Commands,
+    #[serde(rename = "command_info")]
+    CommandInfo,
     #[serde(rename = "quitsig")]
     QuitSig,
     #[serde(rename = "configuration")]
     Configuration,
     #[serde(rename = "previous_answer")]
     PrevAnswer,
+    #[serde(rename = "history")]
+    History,
+    #[serde(rename = "statistics")]
+    Statistics,
+    #[serde(rename = "modulus")]
+    Modulus,
+    #[serde(rename = "result")]
+    Result,
+    #[serde(rename = "variables")]
+    Variables,
+    #[serde(rename = "session")]
+    Session,
+    #[serde(rename = "cancel")]
+    Cancel,
+    #[serde(rename = "hello")]
+    Hello,
 }
 
 /// Types of message payloads to send to the client
@@ -109,8 +225,12 @@ pub enum ResponsePayload {
     Stack(Vec<Bucket>),
     #[serde(rename = "commands")]
     Commands(Vec<String>),
+    /// Metadata of every available command
+    #[serde(rename = "command_info")]
+    CommandInfo(Vec<CommandInfo>),
+    /// The error with its machine-readable code
     #[serde(rename = "error")]
-    Error(String),
+    Error(EngineError),
     /// This should always be set to None
     #[serde(rename = "quitsig")]
     QuitSig(Option<u8>),
@@ -118,4 +238,72 @@ pub enum ResponsePayload {
     Configuration(serde_json::Value),
     #[serde(rename = "previous_answer")]
     PrevAnswer(Bucket),
+    /// Summary of the undo history
+    #[serde(rename = "history")]
+    History(HistorySummary),
+    /// List of accumulated (x, y) statistics pairs
+    #[serde(rename = "statistics")]
+    Statistics(Vec<(f64, f64)>),
+    /// Active modulus of modular arithmetic mode, or null if disabled
+    #[serde(rename = "modulus")]
+    Modulus(Option<u64>),
+    /// The value of an algebraic expression, or null if it has no value
+    #[serde(rename = "result")]
+    Result(Option<Bucket>),
+    /// Variables by name with their typed values
+    #[serde(rename = "variables")]
+    Variables(#[serde(with = "typed")] HashMap<String, Bucket>),
+    /// The ID of the session that was opened or closed
+    #[serde(rename = "session")]
+    Session(String),
+    /// The session whose request was cancelled, or null for the default session
+    #[serde(rename = "cancel")]
+    Cancel(Option<String>),
+    /// The versions and capabilities of the server
+    #[serde(rename = "hello")]
+    Hello(Hello),
+}
+
+/// The versions and capabilities of a server, sent in response to a `hello` request
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Hello {
+    /// Version of the engine crate
+    pub engine_version: String,
+    /// Version of the JSON protocol
+    pub protocol_version: u32,
+    /// Cargo features the engine was compiled with
+    pub features: Vec<String>,
+    /// The library used for decimal arithmetic
+    pub numeric_backend: String,
+    /// Names of the request types the server accepts
+    pub request_types: Vec<String>,
+}
+
+impl Hello {
+    /// Describe this build of the engine
+    pub fn current() -> Hello {
+        let features = [
+            ("ipc", cfg!(feature = "ipc")),
+            ("nng", cfg!(feature = "nng")),
+            ("stdio", cfg!(feature = "stdio")),
+            ("unix-socket", cfg!(feature = "unix-socket")),
+            ("http", cfg!(feature = "http")),
+            (
+                "disable-crash-reports",
+                cfg!(feature = "disable-crash-reports"),
+            ),
+        ];
+
+        Hello {
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            features: features
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| feature.to_string())
+                .collect(),
+            numeric_backend: String::from("rust_decimal"),
+            request_types: RequestType::ALL.iter().map(RequestType::name).collect(),
+        }
+    }
 }
@@ -0,0 +1,673 @@
+// request handling of the IPC server
+// requests are received by a pool of workers, so that a slow request only blocks its own session
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, TryLockError,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    algebraic,
+    command_mappings::{self, CommandRegistry},
+    crash_reporter::SERVER_THREAD_NAME,
+    engine::Engine,
+    error::{EngineError, ErrorKind},
+    extract_data, handle_batch_data, handle_config_data, handle_data, handle_variables_data,
+    ipc::{IPCBackend, IPCWorker},
+    protocol::{
+        client_request::{
+            ClientRequestMessage, ConfigurationActionType, RequestPayload, RequestType,
+        },
+        event::{Event, EventMessage},
+        server_response::{
+            ConfigValue, Hello, MessageAction, Notification, ResponsePayload, ResponseType,
+            ServerResponseMessage,
+        },
+    },
+    session::{self, Session},
+    session_manager::SessionManager,
+};
+
+/// Number of requests evaluated in parallel if `server_workers` is not configured
+const DEFAULT_WORKERS: usize = 4;
+
+/// The longest time between two checks for idle sessions
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The state shared by the workers of a server
+pub struct Server {
+    sessions: Mutex<SessionManager>,
+    commands: CommandRegistry,
+    /// Receivers of the changes made by requests
+    subscribers: Mutex<Vec<Sender<EventMessage>>>,
+    /// Passes the changes made by requests to the backend while the server is serving
+    backend_events: Mutex<Option<Sender<EventMessage>>>,
+    /// The token that every request must include, if any
+    token: Option<String>,
+    /// Triggered when the server should stop
+    shutdown: ShutdownHandle,
+    /// Set once the server has started to stop
+    quitting: AtomicBool,
+    /// Whether the default session is saved when the server stops
+    autosave: bool,
+    /// The number of workers answering requests, or 0 if the server is not serving
+    workers: AtomicUsize,
+    /// The number of workers that are evaluating a request or waiting for a busy session
+    occupied: AtomicUsize,
+}
+
+/// Counts a worker as occupied until it is dropped
+struct Occupied<'a>(&'a AtomicUsize);
+
+impl Drop for Occupied<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Stops a running server from another thread, such as a signal handler or the
+/// application that embeds the engine. Clones stop the same server
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    /// Whether the server should stop, and the condition that is notified when it should
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ShutdownHandle {
+    pub fn new() -> ShutdownHandle {
+        ShutdownHandle::default()
+    }
+
+    /// Stop the server. Running requests are cancelled and the default session is saved
+    pub fn shutdown(&self) {
+        let (stopping, condvar) = &*self.state;
+        *stopping.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        condvar.notify_all();
+    }
+
+    /// Test if the server has been asked to stop
+    pub fn is_shutdown(&self) -> bool {
+        *self.state.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Block until the server is asked to stop, or until the timeout has passed.
+    /// Returns whether the server has been asked to stop
+    fn wait_timeout(&self, timeout: Duration) -> bool {
+        let (stopping, condvar) = &*self.state;
+        let stopping = stopping.lock().unwrap_or_else(PoisonError::into_inner);
+        let (stopping, _) = condvar
+            .wait_timeout_while(stopping, timeout, |stopping| !*stopping)
+            .unwrap_or_else(PoisonError::into_inner);
+        *stopping
+    }
+
+    /// Block until the server is asked to stop
+    fn wait(&self) {
+        let (stopping, condvar) = &*self.state;
+        let mut stopping = stopping.lock().unwrap_or_else(PoisonError::into_inner);
+        while !*stopping {
+            stopping = condvar
+                .wait(stopping)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl Server {
+    /// Create a server with the engine of the default session
+    pub fn new(engine: Engine) -> Server {
+        Server {
+            sessions: Mutex::new(SessionManager::new(engine)),
+            commands: command_mappings::create_command_registry(),
+            subscribers: Mutex::new(Vec::new()),
+            backend_events: Mutex::new(None),
+            token: None,
+            shutdown: ShutdownHandle::new(),
+            quitting: AtomicBool::new(false),
+            autosave: false,
+            workers: AtomicUsize::new(0),
+            occupied: AtomicUsize::new(0),
+        }
+    }
+
+    /// Save the default session when the server stops, if the `persist_session`
+    /// config key is enabled
+    pub fn with_autosave(mut self, autosave: bool) -> Server {
+        self.autosave = autosave;
+        self
+    }
+
+    /// Stop the server when the given handle is triggered
+    pub fn with_shutdown(mut self, shutdown: ShutdownHandle) -> Server {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Get a handle that stops the server
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Reject every request that does not include the given token
+    pub fn with_token(mut self, token: Option<String>) -> Server {
+        self.token = token;
+        self
+    }
+
+    /// Receive and answer requests with `workers` threads until the default session
+    /// quits, the backend stops, or the server is shut down
+    pub fn serve<B: IPCBackend>(&self, backend: &B, workers: usize) -> Result<(), anyhow::Error> {
+        let workers = (0..workers.max(1))
+            .map(|_| backend.worker())
+            .collect::<Result<Vec<_>, _>>()?;
+        self.workers.store(workers.len(), Ordering::SeqCst);
+
+        thread::scope(|scope| {
+            // publish changes until the server quits
+            let (sender, events) = mpsc::channel();
+            *self.lock_backend_events() = Some(sender);
+            scope.spawn(move || {
+                for event in events {
+                    let _ = backend.publish(&event);
+                }
+            });
+
+            scope.spawn(|| {
+                self.shutdown.wait();
+                self.stop(backend);
+            });
+
+            // close idle sessions even while no requests arrive
+            let timeout = self.lock_sessions().timeout();
+            if let Some(timeout) = timeout {
+                let interval = timeout.min(EVICTION_INTERVAL);
+                scope.spawn(move || {
+                    while !self.shutdown.wait_timeout(interval) {
+                        self.lock_sessions().evict_idle();
+                    }
+                });
+            }
+
+            let mut workers = workers
+                .into_iter()
+                .map(|worker| {
+                    thread::Builder::new()
+                        .name(SERVER_THREAD_NAME.to_string())
+                        .spawn_scoped(scope, move || self.run_worker(backend, worker))
+                })
+                .collect::<Result<Vec<_>, _>>();
+            if let Ok(workers) = &mut workers {
+                for worker in workers.drain(..) {
+                    let _ = worker.join();
+                }
+            }
+
+            // stop publishing changes once every worker has stopped, and release the
+            // shutdown thread if the backend stopped on its own
+            self.shutdown.shutdown();
+            self.lock_backend_events().take();
+            self.lock_subscribers().clear();
+            workers.map(|_| ()).map_err(anyhow::Error::from)
+        })
+    }
+
+    /// Stop answering requests. Running requests are cancelled, the default session is
+    /// saved, and workers that are waiting for a request return
+    fn stop<B: IPCBackend>(&self, backend: &B) {
+        self.lock_sessions().cancel_all();
+        if !self.quitting.swap(true, Ordering::SeqCst) {
+            self.save_session();
+        }
+        backend.close();
+    }
+
+    /// Answer requests until the server quits
+    fn run_worker<B: IPCBackend>(&self, backend: &B, worker: B::Worker) {
+        loop {
+            let request = match worker.recv_data() {
+                Ok(request) => request,
+                // the backend was closed because the server quit
+                Err(_) if self.quitting.load(Ordering::SeqCst) => return,
+                Err(_) => {
+                    // send error back to client and continue loop
+                    let response = error_response(EngineError::new(
+                        ErrorKind::InvalidRequest,
+                        "invalid JSON data was sent to the server",
+                    ));
+                    if worker.send_data(response).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            if self.authorized(&request) {
+                worker.authenticated(request.session.as_deref());
+            }
+
+            match self.answer(&request, backend.has_subscribers()) {
+                Some(response) => {
+                    let _ = worker.send_data(response);
+                }
+                None => {
+                    self.quitting.store(true, Ordering::SeqCst);
+                    self.save_session();
+
+                    // send quit message to client, then stop the other workers
+                    let _ = worker.send_data(ServerResponseMessage::new(
+                        ResponseType::QuitSig,
+                        ResponsePayload::QuitSig(None),
+                    ));
+                    self.shutdown.shutdown();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Evaluate a request. Returns None if the default session quit, which stops the server
+    pub fn handle_request(&self, request: &ClientRequestMessage) -> Option<ServerResponseMessage> {
+        self.answer(request, false)
+    }
+
+    /// Evaluate a request. Changes are published if `publishing` is set or there are
+    /// subscribers
+    fn answer(
+        &self,
+        request: &ClientRequestMessage,
+        publishing: bool,
+    ) -> Option<ServerResponseMessage> {
+        if !self.authorized(request) {
+            return Some(error_response(EngineError::new(
+                ErrorKind::Unauthorized,
+                "the request does not include the token of the server",
+            )));
+        }
+
+        // a panic while evaluating a command is reported to the client instead of
+        // stopping the server
+        let publishing = publishing || !self.lock_subscribers().is_empty();
+        let response = panic::catch_unwind(AssertUnwindSafe(|| self.respond(request, publishing)))
+            .unwrap_or_else(|payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown error".to_string());
+                Some(error_response(EngineError::new(
+                    ErrorKind::Internal,
+                    format!(
+                        "the server panicked while answering the request: {}",
+                        message
+                    ),
+                )))
+            });
+        response.map(|response| response.with_typed_buckets(request.typed))
+    }
+
+    /// Evaluate a request and construct its response
+    fn respond(
+        &self,
+        request: &ClientRequestMessage,
+        publishing: bool,
+    ) -> Option<ServerResponseMessage> {
+        let session_id = request.session.as_deref();
+
+        // the session manager is only locked while looking up the engine, so that
+        // requests in other sessions are not blocked while this one is evaluated
+        let engine = {
+            let mut sessions = self.lock_sessions();
+
+            // close sessions that have been idle for too long
+            sessions.evict_idle();
+
+            match request.request_type {
+                RequestType::OpenSession => {
+                    return Some(match sessions.open() {
+                        Ok(id) => ServerResponseMessage::new(
+                            ResponseType::Session,
+                            ResponsePayload::Session(id),
+                        ),
+                        Err(error) => error_response(error),
+                    })
+                }
+                RequestType::CloseSession => {
+                    return Some(match session_id {
+                        Some(id) => match sessions.close(id) {
+                            Ok(()) => ServerResponseMessage::new(
+                                ResponseType::Session,
+                                ResponsePayload::Session(id.to_string()),
+                            ),
+                            Err(error) => error_response(error),
+                        },
+                        None => error_response(EngineError::new(
+                            ErrorKind::InvalidRequest,
+                            "no session was given to close",
+                        )),
+                    })
+                }
+                RequestType::Hello => {
+                    return Some(ServerResponseMessage::new(
+                        ResponseType::Hello,
+                        ResponsePayload::Hello(Hello::current()),
+                    ))
+                }
+                RequestType::Cancel => {
+                    return Some(match sessions.cancel(session_id) {
+                        Ok(()) => ServerResponseMessage::new(
+                            ResponseType::Cancel,
+                            ResponsePayload::Cancel(request.session.clone()),
+                        ),
+                        Err(error) => error_response(error),
+                    })
+                }
+                RequestType::Input
+                | RequestType::Algebraic
+                | RequestType::Batch
+                | RequestType::Variables
+                | RequestType::Configuration => match sessions.get(session_id) {
+                    Ok(engine) => engine,
+                    Err(error) => return Some(error_response(error)),
+                },
+            }
+        };
+
+        let _occupied = self.occupy();
+        let mut engine = match engine.try_lock() {
+            Ok(engine) => engine,
+            Err(TryLockError::Poisoned(error)) => error.into_inner(),
+            // waiting for a busy session must leave a worker free to receive cancel requests
+            Err(TryLockError::WouldBlock) if self.has_free_worker() => {
+                engine.lock().unwrap_or_else(PoisonError::into_inner)
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Some(error_response(EngineError::new(
+                    ErrorKind::SessionBusy,
+                    "The session is busy with another request. Cancel it or try again later",
+                )))
+            }
+        };
+
+        // a timeout of 0 lets the request run for any amount of time
+        let timeout = match request.timeout {
+            Some(0) => None,
+            Some(milliseconds) => Some(Duration::from_millis(milliseconds)),
+            None => engine.request_timeout(),
+        };
+        engine.interrupt.reset(timeout);
+
+        // changes made outside of requests are not part of the changes of this request
+        engine.take_changes();
+
+        let result = match request.request_type {
+            RequestType::Configuration => handle_config_data(
+                &mut engine,
+                extract_data!(request.payload.clone(), RequestPayload::Configuration),
+            ),
+            RequestType::Variables => handle_variables_data(
+                &mut engine,
+                extract_data!(request.payload.clone(), RequestPayload::Variables),
+            ),
+            RequestType::Batch => handle_batch_data(
+                &mut engine,
+                &self.commands,
+                extract_data!(&request.payload, RequestPayload::Batch),
+            ),
+            RequestType::Algebraic => algebraic::evaluate(
+                &mut engine,
+                &self.commands,
+                extract_data!(&request.payload, RequestPayload::Input),
+            ),
+            _ => handle_data(
+                &mut engine,
+                &self.commands,
+                extract_data!(&request.payload, RequestPayload::Input),
+            ),
+        };
+
+        match (result, session_id) {
+            // quitting the default session stops the server
+            (Ok(MessageAction::Quit), None) => None,
+            // quitting any other session only closes that session
+            (Ok(MessageAction::Quit), Some(id)) => {
+                drop(engine);
+                let _ = self.lock_sessions().close(id);
+                Some(ServerResponseMessage::new(
+                    ResponseType::QuitSig,
+                    ResponsePayload::QuitSig(None),
+                ))
+            }
+            (result, _) => {
+                let mut events = Vec::new();
+                if let (Ok(_), RequestPayload::Configuration(payload)) = (&result, &request.payload)
+                {
+                    if changes_config(payload.action_type) {
+                        events.push(Event::Configuration(payload.clone()));
+                    }
+                }
+                let mut response = build_response(result, &engine, &self.commands);
+
+                // the events are only built if anyone receives them
+                let changes = engine.take_changes();
+                if changes.stack && publishing {
+                    events.push(Event::Stack(engine.stack.clone()));
+                }
+                let changed = changes.variables();
+                if !changed.is_empty() {
+                    if publishing {
+                        events.push(Event::Variables(
+                            changed
+                                .iter()
+                                .map(|name| (name.clone(), engine.variables.get(name).cloned()))
+                                .collect(),
+                        ));
+                    }
+                    response = response.with_notification(Notification::VariablesChanged(changed));
+                }
+
+                drop(engine);
+                if publishing {
+                    self.publish(request.session.as_deref(), events);
+                }
+                Some(response)
+            }
+        }
+    }
+
+    /// Count the current worker as occupied until the returned guard is dropped
+    fn occupy(&self) -> Occupied<'_> {
+        self.occupied.fetch_add(1, Ordering::SeqCst);
+        Occupied(&self.occupied)
+    }
+
+    /// Test if a worker is free to receive requests while the current worker is occupied
+    fn has_free_worker(&self) -> bool {
+        let workers = self.workers.load(Ordering::SeqCst);
+        workers == 0 || self.occupied.load(Ordering::SeqCst) < workers
+    }
+
+    /// Test if a request includes the token of the server, if it has one
+    fn authorized(&self, request: &ClientRequestMessage) -> bool {
+        match (&self.token, &request.token) {
+            (None, _) => true,
+            (Some(token), Some(given)) => {
+                // compare every byte, so that the time taken does not reveal how much matched
+                token.len() == given.len()
+                    && token
+                        .bytes()
+                        .zip(given.bytes())
+                        .fold(0, |difference, (a, b)| difference | (a ^ b))
+                        == 0
+            }
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Receive the changes made by every following request
+    pub fn subscribe(&self) -> Receiver<EventMessage> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock_subscribers().push(sender);
+        receiver
+    }
+
+    /// Send the changes made by a request in a session to every subscriber
+    fn publish(&self, session: Option<&str>, events: Vec<Event>) {
+        if events.is_empty() {
+            return;
+        }
+
+        let backend = self.lock_backend_events();
+        let mut subscribers = self.lock_subscribers();
+        for event in events {
+            let event = EventMessage::new(session.map(String::from), event);
+            if let Some(backend) = backend.as_ref() {
+                let _ = backend.send(event.clone());
+            }
+            // forget subscribers whose receiver has been dropped
+            subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+    }
+
+    /// Save the default session so that it can be restored on the next start
+    fn save_session(&self) {
+        let engine = self.lock_sessions().default_engine();
+        let engine = engine.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.autosave && engine.persist_session() {
+            let _ = Session::capture(&engine, "quit").write(&session::autosave_path());
+        }
+    }
+
+    /// Lock the list of subscribers
+    fn lock_subscribers(&self) -> MutexGuard<'_, Vec<Sender<EventMessage>>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the sender of the events that are published by the backend
+    fn lock_backend_events(&self) -> MutexGuard<'_, Option<Sender<EventMessage>>> {
+        self.backend_events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the session manager
+    fn lock_sessions(&self) -> MutexGuard<'_, SessionManager> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The number of requests a server evaluates in parallel
+pub fn worker_count(engine: &Engine) -> usize {
+    match engine.config.get_key("system", "server_workers") {
+        Ok(ConfigValue::Value(toml::Value::Integer(workers))) if workers > 0 => workers as usize,
+        _ => DEFAULT_WORKERS,
+    }
+}
+
+/// The address a server publishes changes on, or None if publishing is disabled
+pub fn publish_address(engine: &Engine) -> Option<String> {
+    system_string(engine, "publish_address")
+}
+
+/// The token a server requires in every request, or None if requests are not authenticated
+pub fn configured_token(engine: &Engine) -> Option<String> {
+    system_string(engine, "server_token")
+}
+
+/// Get a string key of the `system` config section, or None if it is empty
+fn system_string(engine: &Engine, key: &str) -> Option<String> {
+    match engine.config.get_key("system", key) {
+        Ok(ConfigValue::Value(toml::Value::String(value))) if !value.is_empty() => Some(value),
+        _ => None,
+    }
+}
+
+/// Generate a random token for a backend that requires one
+pub fn generate_token() -> String {
+    let random_state = RandomState::new();
+    (0..2)
+        .map(|part| {
+            let mut hasher = random_state.build_hasher();
+            hasher.write_u64(part);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Test if a configuration action changes the config
+fn changes_config(action_type: ConfigurationActionType) -> bool {
+    matches!(
+        action_type,
+        ConfigurationActionType::SetKey
+            | ConfigurationActionType::CreateSection
+            | ConfigurationActionType::DeleteSection
+            | ConfigurationActionType::DeleteKey
+    )
+}
+
+/// Construct the response to the client for the result of a request
+fn build_response(
+    result: Result<MessageAction, EngineError>,
+    engine: &Engine,
+    commands: &CommandRegistry,
+) -> ServerResponseMessage {
+    match result {
+        Ok(MessageAction::SendStack) => ServerResponseMessage::new(
+            ResponseType::Stack,
+            ResponsePayload::Stack(engine.stack.clone()),
+        ),
+        Ok(MessageAction::SendConfigValue(config_value)) => ServerResponseMessage::new(
+            ResponseType::Configuration,
+            ResponsePayload::Configuration(config_value.into()),
+        ),
+        Ok(MessageAction::SendCommands) => ServerResponseMessage::new(
+            ResponseType::Commands,
+            ResponsePayload::Commands(commands.names_and_aliases()),
+        ),
+        Ok(MessageAction::SendCommandInfo) => ServerResponseMessage::new(
+            ResponseType::CommandInfo,
+            ResponsePayload::CommandInfo(commands.info()),
+        ),
+        Ok(MessageAction::SendHistory) => ServerResponseMessage::new(
+            ResponseType::History,
+            ResponsePayload::History(engine.history.summary(engine.undo_depth())),
+        ),
+        Ok(MessageAction::SendStatistics) => ServerResponseMessage::new(
+            ResponseType::Statistics,
+            ResponsePayload::Statistics(engine.statistics.points.clone()),
+        ),
+        Ok(MessageAction::SendModulus) => ServerResponseMessage::new(
+            ResponseType::Modulus,
+            ResponsePayload::Modulus(engine.modulus),
+        ),
+        Ok(MessageAction::SendPrevAnswer) => ServerResponseMessage::new(
+            ResponseType::PrevAnswer,
+            ResponsePayload::PrevAnswer(engine.previous_answer.clone()),
+        ),
+        Ok(MessageAction::SendResult(result)) => {
+            ServerResponseMessage::new(ResponseType::Result, ResponsePayload::Result(result))
+        }
+        Ok(MessageAction::SendVariables(variables)) => ServerResponseMessage::new(
+            ResponseType::Variables,
+            ResponsePayload::Variables(variables),
+        ),
+        Ok(MessageAction::Quit) => {
+            ServerResponseMessage::new(ResponseType::QuitSig, ResponsePayload::QuitSig(None))
+        }
+        Err(error) => error_response(error),
+    }
+}
+
+/// Construct an error response to the client
+fn error_response(error: EngineError) -> ServerResponseMessage {
+    ServerResponseMessage::new(ResponseType::Error, ResponsePayload::Error(error))
+}
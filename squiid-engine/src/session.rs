@@ -0,0 +1,172 @@
+// saving and restoring the full engine state to and from a file
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use directories::{BaseDirs, ProjectDirs};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bucket::{typed, Bucket},
+    engine::Engine,
+    error::{EngineError, ErrorKind},
+    history::{Checkpoint, History},
+    statistics::Statistics,
+};
+
+/// Version of the session file format. Increased when the format changes incompatibly
+const SESSION_VERSION: u32 = 1;
+
+/// Name of the session file that is saved when quitting and restored on start
+const AUTOSAVE_FILE: &str = "session.json";
+
+/// The engine state that is stored in a session file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Version of the session file format
+    version: u32,
+    #[serde(with = "typed")]
+    stack: Vec<Bucket>,
+    #[serde(with = "typed")]
+    variables: HashMap<String, Bucket>,
+    #[serde(with = "typed")]
+    previous_answer: Bucket,
+    statistics: Statistics,
+    modulus: Option<u64>,
+    checkpoints: HashMap<String, Checkpoint>,
+    history: History,
+}
+
+impl Session {
+    /// Copy the state of an engine. Changes that have not been recorded in the
+    /// history yet are recorded in the copy of the history under `command`
+    pub fn capture(engine: &Engine, command: &str) -> Session {
+        let mut history = engine.history.clone();
+        history.record(command, engine, engine.undo_depth());
+
+        Session {
+            version: SESSION_VERSION,
+            stack: engine.stack.clone(),
+            variables: engine.variables.clone(),
+            previous_answer: engine.previous_answer.clone(),
+            statistics: engine.statistics.clone(),
+            modulus: engine.modulus,
+            checkpoints: engine.checkpoints.clone(),
+            history,
+        }
+    }
+
+    /// Replace the state of an engine with the session
+    pub fn restore(self, engine: &mut Engine) {
+        engine.stack = self.stack;
+        engine.variables = self.variables;
+        engine.previous_answer = self.previous_answer;
+        engine.statistics = self.statistics;
+        engine.modulus = self.modulus;
+        engine.checkpoints = self.checkpoints;
+        let mut history = self.history;
+        history.replace(std::mem::take(&mut engine.history));
+        engine.history = history;
+
+        // update the state that the history computes its deltas against
+        engine.synchronize_history();
+    }
+
+    /// Write the session to a file
+    pub fn write(&self, path: &Path) -> Result<(), EngineError> {
+        let contents = serde_json::to_string(self).map_err(|error| {
+            EngineError::new(
+                ErrorKind::Internal,
+                format!("Could not serialize session: {}", error),
+            )
+        })?;
+
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        fs::write(path, contents).map_err(|error| {
+            EngineError::new(
+                ErrorKind::Io,
+                format!("Could not write session to {}: {}", path.display(), error),
+            )
+        })
+    }
+
+    /// Read a session from a file
+    pub fn read(path: &Path) -> Result<Session, EngineError> {
+        let contents = fs::read_to_string(path).map_err(|error| {
+            EngineError::new(
+                ErrorKind::Io,
+                format!("Could not read session from {}: {}", path.display(), error),
+            )
+        })?;
+
+        let session: Session = serde_json::from_str(&contents).map_err(|error| {
+            EngineError::new(
+                ErrorKind::Io,
+                format!("{} is not a valid session file: {}", path.display(), error),
+            )
+        })?;
+
+        if session.version > SESSION_VERSION {
+            return Err(EngineError::new(
+                ErrorKind::Io,
+                format!("{} was saved by a newer version of Squiid", path.display()),
+            ));
+        }
+
+        Ok(session)
+    }
+}
+
+/// Determine the directory that sessions are saved in
+///
+/// Linux: `~/.local/share/squiid/`
+///
+/// MacOS: `/Users/<NAME>/Library/Application Support/net.ImaginaryInfinity.Squiid/`
+///
+/// Windows: `C:\Users\<NAME>\AppData\Roaming\ImaginaryInfinity\Squiid\data`
+///
+/// Anything else: See Linux
+pub fn data_directory() -> PathBuf {
+    if let Some(proj_dirs) = ProjectDirs::from("net", "ImaginaryInfinity", "Squiid") {
+        proj_dirs.data_dir().to_path_buf()
+    // couldn't determine data path, default to home directory .local/share folder
+    } else {
+        let home_dir = BaseDirs::new().unwrap().home_dir().to_owned();
+        [home_dir.to_str().unwrap(), ".local", "share", "squiid"]
+            .iter()
+            .collect()
+    }
+}
+
+/// Determine the path of the session that is saved when quitting
+pub fn autosave_path() -> PathBuf {
+    data_directory().join(AUTOSAVE_FILE)
+}
+
+/// Determine the path of a session file name inside a directory. Only plain file names
+/// are accepted, so sessions cannot be read or written outside of the directory
+pub fn resolve_path(directory: &Path, name: &str) -> Result<PathBuf, EngineError> {
+    let mut components = Path::new(name).components();
+    let is_file_name = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+
+    // backslashes are checked separately since they only separate components on Windows
+    if is_file_name && !name.contains(['/', '\\']) {
+        Ok(directory.join(name))
+    } else {
+        Err(EngineError::new(
+            ErrorKind::InvalidName,
+            format!(
+                "`{}` is not a file name. Sessions can only be saved in the data directory",
+                name
+            ),
+        ))
+    }
+}
@@ -0,0 +1,223 @@
+// isolated engines for the clients of a shared server
+// every session has its own engine, so clients can not see or change each other's stacks.
+// engines are locked separately, so requests in different sessions can be evaluated in parallel
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config_handler::Config,
+    engine::{Engine, EngineBuilder},
+    error::{EngineError, ErrorKind},
+    interrupt::Interrupt,
+    protocol::server_response::ConfigValue,
+};
+
+/// Number of seconds a session may be idle before it is evicted if `session_timeout` is not configured
+const DEFAULT_SESSION_TIMEOUT: u64 = 3600;
+
+/// Number of sessions that can be open at once if `max_sessions` is not configured
+const DEFAULT_MAX_SESSIONS: usize = 64;
+
+/// An engine and the last time it was used
+struct Session {
+    engine: Arc<Mutex<Engine>>,
+    /// Shares the cancellation flag of the engine, so that the running command
+    /// can be cancelled while the engine is locked
+    interrupt: Interrupt,
+    last_used: Instant,
+}
+
+impl Session {
+    fn new(engine: Engine) -> Session {
+        Session {
+            interrupt: engine.interrupt.clone(),
+            engine: Arc::new(Mutex::new(engine)),
+            last_used: Instant::now(),
+        }
+    }
+}
+
+/// The sessions of a server. Requests without a session ID are evaluated in the
+/// default session, which is never evicted
+pub struct SessionManager {
+    /// The session of requests without a session ID
+    default: Session,
+    /// Sessions opened by clients by ID
+    sessions: HashMap<String, Session>,
+    /// Config of new sessions, copied from the default session
+    config: Config,
+    /// The time a session may be idle before it is evicted
+    timeout: Option<Duration>,
+    /// The number of sessions that can be open at once, or None if unlimited
+    max_sessions: Option<usize>,
+    /// Source of random session IDs
+    random_state: RandomState,
+    /// Number of sessions that have been opened, mixed into the session IDs
+    opened: u64,
+}
+
+impl SessionManager {
+    /// Create a session manager with the engine of the default session
+    pub fn new(default: Engine) -> SessionManager {
+        let timeout = match default.config.get_key("system", "session_timeout") {
+            Ok(ConfigValue::Value(toml::Value::Integer(0))) => None,
+            Ok(ConfigValue::Value(toml::Value::Integer(seconds))) if seconds > 0 => {
+                Some(Duration::from_secs(seconds as u64))
+            }
+            _ => Some(Duration::from_secs(DEFAULT_SESSION_TIMEOUT)),
+        };
+        let max_sessions = match default.config.get_key("system", "max_sessions") {
+            Ok(ConfigValue::Value(toml::Value::Integer(0))) => None,
+            Ok(ConfigValue::Value(toml::Value::Integer(sessions))) if sessions > 0 => {
+                Some(sessions as usize)
+            }
+            _ => Some(DEFAULT_MAX_SESSIONS),
+        };
+
+        SessionManager {
+            config: default.config.clone(),
+            default: Session::new(default),
+            sessions: HashMap::new(),
+            timeout,
+            max_sessions,
+            random_state: RandomState::new(),
+            opened: 0,
+        }
+    }
+
+    /// The number of sessions opened by clients
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Test if no sessions have been opened by clients
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// The engine of the default session
+    pub fn default_engine(&self) -> Arc<Mutex<Engine>> {
+        Arc::clone(&self.default.engine)
+    }
+
+    /// The time a session may be idle before it is evicted, or None if sessions never expire
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Open a new session with an empty engine that uses the config of the
+    /// default session. Returns the ID of the session, or an error if the maximum
+    /// number of sessions is already open
+    pub fn open(&mut self) -> Result<String, EngineError> {
+        // idle sessions do not count towards the limit
+        self.evict_idle();
+        if let Some(max_sessions) = self.max_sessions {
+            if self.sessions.len() >= max_sessions {
+                return Err(EngineError::new(
+                    ErrorKind::SessionLimit,
+                    format!(
+                        "Cannot open more than {} sessions. Close a session first",
+                        max_sessions
+                    ),
+                ));
+            }
+        }
+
+        let id = loop {
+            self.opened += 1;
+            let mut hasher = self.random_state.build_hasher();
+            hasher.write_u64(self.opened);
+            let id = format!("{:016x}", hasher.finish());
+            if !self.sessions.contains_key(&id) {
+                break id;
+            }
+        };
+
+        let engine = EngineBuilder::new().config(self.config.clone()).build()?;
+        self.sessions.insert(id.clone(), Session::new(engine));
+        Ok(id)
+    }
+
+    /// Close a session. A request that is being evaluated in the session is
+    /// allowed to finish
+    pub fn close(&mut self, id: &str) -> Result<(), EngineError> {
+        match self.sessions.remove(id) {
+            Some(_) => Ok(()),
+            None => Err(undefined_session(id)),
+        }
+    }
+
+    /// Get the engine of a session, or of the default session if no ID is given.
+    /// This marks the session as used
+    pub fn get(&mut self, id: Option<&str>) -> Result<Arc<Mutex<Engine>>, EngineError> {
+        let session = self.session(id)?;
+        session.last_used = Instant::now();
+        Ok(Arc::clone(&session.engine))
+    }
+
+    /// Cancel the command that is running in a session, or in the default
+    /// session if no ID is given
+    pub fn cancel(&mut self, id: Option<&str>) -> Result<(), EngineError> {
+        self.session(id)?.interrupt.cancel();
+        Ok(())
+    }
+
+    /// Cancel the commands that are running in every session
+    pub fn cancel_all(&mut self) {
+        self.default.interrupt.cancel();
+        for session in self.sessions.values() {
+            session.interrupt.cancel();
+        }
+    }
+
+    /// Find a session, or the default session if no ID is given
+    fn session(&mut self, id: Option<&str>) -> Result<&mut Session, EngineError> {
+        match id {
+            None => Ok(&mut self.default),
+            Some(id) => self
+                .sessions
+                .get_mut(id)
+                .ok_or_else(|| undefined_session(id)),
+        }
+    }
+
+    /// Close every session that has not been used for longer than the timeout.
+    /// Sessions that are evaluating a request are kept and marked as used.
+    /// Returns the IDs of the closed sessions
+    pub fn evict_idle(&mut self) -> Vec<String> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Vec::new(),
+        };
+
+        for session in self.sessions.values_mut() {
+            if Arc::strong_count(&session.engine) > 1 || session.engine.try_lock().is_err() {
+                session.last_used = Instant::now();
+            }
+        }
+
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.last_used.elapsed() > timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            self.sessions.remove(id);
+        }
+        expired
+    }
+}
+
+/// Construct an error for a session that does not exist
+fn undefined_session(id: &str) -> EngineError {
+    EngineError::new(
+        ErrorKind::UndefinedSession,
+        format!("Session `{}` does not exist or has expired", id),
+    )
+}
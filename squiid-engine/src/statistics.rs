@@ -0,0 +1,136 @@
+// two-variable statistics and regression on accumulated (x, y) pairs
+
+use serde::{Deserialize, Serialize};
+
+/// Regression models that can be fit to the accumulated data
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum RegressionModel {
+    /// y = a + b*x
+    #[default]
+    Linear,
+    /// y = a * e^(b*x)
+    Exponential,
+    /// y = a + b*ln(x)
+    Logarithmic,
+    /// y = a * x^b
+    Power,
+}
+
+/// The result of fitting a regression model
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Regression {
+    /// The model that was fit
+    pub model: RegressionModel,
+    /// The first coefficient of the model
+    pub a: f64,
+    /// The second coefficient of the model
+    pub b: f64,
+    /// Coefficient of determination of the (linearized) fit
+    pub r_squared: f64,
+}
+
+impl Regression {
+    /// Predict a y value for the given x value
+    pub fn predict(&self, x: f64) -> f64 {
+        match self.model {
+            RegressionModel::Linear => self.a + self.b * x,
+            RegressionModel::Exponential => self.a * (self.b * x).exp(),
+            RegressionModel::Logarithmic => self.a + self.b * x.ln(),
+            RegressionModel::Power => self.a * x.powf(self.b),
+        }
+    }
+}
+
+/// Accumulated statistics data, similar to the Σ+ registers of a classic calculator
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Statistics {
+    /// The accumulated (x, y) pairs
+    pub points: Vec<(f64, f64)>,
+    /// The model used by `rsquared` and `predict`. Set by the last regression command
+    pub model: RegressionModel,
+}
+
+impl Statistics {
+    /// Add a data point
+    pub fn add_point(&mut self, x: f64, y: f64) {
+        self.points.push((x, y));
+    }
+
+    /// Remove the most recently added data point matching (x, y).
+    /// Returns false if no such point exists
+    pub fn remove_point(&mut self, x: f64, y: f64) -> bool {
+        match self.points.iter().rposition(|point| *point == (x, y)) {
+            Some(index) => {
+                self.points.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all data points
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Fit the given model to the accumulated data
+    pub fn regression(&self, model: RegressionModel) -> Result<Regression, String> {
+        if self.points.len() < 2 {
+            return Err("at least 2 data points are required for a regression".to_string());
+        }
+
+        // linearize the data so that every model can be fit as y' = A + B*x'
+        let mut transformed = Vec::with_capacity(self.points.len());
+        for (x, y) in &self.points {
+            let x_prime = match model {
+                RegressionModel::Linear | RegressionModel::Exponential => *x,
+                RegressionModel::Logarithmic | RegressionModel::Power if *x > 0.0 => x.ln(),
+                _ => return Err("x values must be positive for this regression".to_string()),
+            };
+            let y_prime = match model {
+                RegressionModel::Linear | RegressionModel::Logarithmic => *y,
+                RegressionModel::Exponential | RegressionModel::Power if *y > 0.0 => y.ln(),
+                _ => return Err("y values must be positive for this regression".to_string()),
+            };
+            transformed.push((x_prime, y_prime));
+        }
+
+        let n = transformed.len() as f64;
+        let mean_x = transformed.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = transformed.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+        let mut sxx = 0.0;
+        let mut syy = 0.0;
+        let mut sxy = 0.0;
+        for (x, y) in &transformed {
+            sxx += (x - mean_x).powi(2);
+            syy += (y - mean_y).powi(2);
+            sxy += (x - mean_x) * (y - mean_y);
+        }
+
+        if sxx == 0.0 {
+            return Err("x values must not all be the same".to_string());
+        }
+
+        let b = sxy / sxx;
+        let intercept = mean_y - b * mean_x;
+        // a perfectly horizontal line is a perfect fit
+        let r_squared = if syy == 0.0 {
+            1.0
+        } else {
+            (sxy * sxy) / (sxx * syy)
+        };
+
+        let a = match model {
+            RegressionModel::Linear | RegressionModel::Logarithmic => intercept,
+            RegressionModel::Exponential | RegressionModel::Power => intercept.exp(),
+        };
+
+        Ok(Regression {
+            model,
+            a,
+            b,
+            r_squared,
+        })
+    }
+}
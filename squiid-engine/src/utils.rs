@@ -1,12 +1,12 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 
-#[cfg(feature = "ipc")]
+#[cfg(feature = "nng")]
 use crate::protocol::{
     client_request::ClientRequestMessage,
     server_response::{ResponsePayload, ResponseType, ServerResponseMessage},
 };
-#[cfg(feature = "ipc")]
+#[cfg(feature = "nng")]
 use nng::Socket;
 
 lazy_static! {
@@ -17,7 +17,7 @@ lazy_static! {
         Regex::new(r"^[-]?(?:[0-9]*\.?[0-9]+(?:[eE][-+]?\d+(?:\.\d+)?)?|[0-9]+)$").unwrap();
 }
 
-#[cfg(feature = "ipc")]
+#[cfg(feature = "nng")]
 /// Send a response to the client
 pub fn send_response(
     socket: &Socket,
@@ -32,7 +32,7 @@ pub fn send_response(
     Ok(())
 }
 
-#[cfg(feature = "ipc")]
+#[cfg(feature = "nng")]
 /// Recieve data from the client
 pub fn recv_data(socket: &Socket) -> Result<ClientRequestMessage, serde_json::Error> {
     // recieve data from client
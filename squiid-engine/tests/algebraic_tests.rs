@@ -0,0 +1,101 @@
+use squiid_engine::{
+    algebraic,
+    bucket::Bucket,
+    command_mappings,
+    engine::EngineBuilder,
+    error::ErrorKind,
+    protocol::{
+        client_request::{ClientRequestMessage, RequestPayload, RequestType},
+        server_response::MessageAction,
+    },
+};
+
+#[test]
+fn test_operator_command() {
+    assert_eq!(algebraic::operator_command("+"), "add");
+    assert_eq!(algebraic::operator_command("^"), "power");
+    assert_eq!(algebraic::operator_command("="), "invstore");
+    assert_eq!(algebraic::operator_command(">="), "geq");
+    assert_eq!(algebraic::operator_command("sqrt"), "sqrt");
+}
+
+#[test]
+fn test_evaluate() {
+    let mut engine = EngineBuilder::new().build().unwrap();
+    let commands = command_mappings::create_command_registry();
+
+    // the expression is evaluated on top of the stack
+    let _ = engine.add_item_to_stack(Bucket::from(7));
+    assert_eq!(
+        algebraic::evaluate(&mut engine, &commands, "(1+2)*3^2"),
+        Ok(MessageAction::SendResult(Some(Bucket::from(27))))
+    );
+    assert_eq!(engine.stack, vec![Bucket::from(7), Bucket::from(27)]);
+    assert_eq!(engine.previous_answer, Bucket::from(27));
+
+    // assignments do not have a value
+    assert_eq!(
+        algebraic::evaluate(&mut engine, &commands, "x=5"),
+        Ok(MessageAction::SendResult(None))
+    );
+    assert_eq!(engine.variables.get("x"), Some(&Bucket::from(5)));
+    assert_eq!(
+        algebraic::evaluate(&mut engine, &commands, "2*$x"),
+        Ok(MessageAction::SendResult(Some(Bucket::from(10))))
+    );
+
+    // the whole expression is a single undo step
+    let _ = engine.undo();
+    assert_eq!(engine.stack, vec![Bucket::from(7), Bucket::from(27)]);
+    let _ = engine.undo();
+    assert_eq!(engine.variables.get("x"), None);
+    assert_eq!(
+        engine.history.entries().last().unwrap().command,
+        "(1+2)*3^2"
+    );
+}
+
+#[test]
+fn test_evaluate_errors() {
+    let mut engine = EngineBuilder::new().build().unwrap();
+    let commands = command_mappings::create_command_registry();
+    let _ = engine.add_item_to_stack(Bucket::from(7));
+
+    // a failing expression leaves the engine unchanged
+    let error = algebraic::evaluate(&mut engine, &commands, "y=1+1/0").unwrap_err();
+    assert_eq!(error.code, ErrorKind::DivideByZero);
+    assert_eq!(error.command.as_deref(), Some("divide"));
+    assert_eq!(engine.stack, vec![Bucket::from(7)]);
+    assert_eq!(engine.variables.get("y"), None);
+    assert_eq!(engine.history.len(), 0);
+
+    assert_eq!(
+        algebraic::evaluate(&mut engine, &commands, "(1+2")
+            .unwrap_err()
+            .code,
+        ErrorKind::InvalidExpression
+    );
+
+    // commands that only make sense in RPN mode are rejected
+    assert_eq!(
+        algebraic::evaluate(&mut engine, &commands, "swap(1,2)")
+            .unwrap_err()
+            .code,
+        ErrorKind::InvalidExpression
+    );
+    assert_eq!(engine.stack, vec![Bucket::from(7)]);
+}
+
+#[test]
+fn test_request_algebraic() {
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "algebraic", "payload": "1+2"}"#).unwrap();
+    assert!(matches!(request.request_type, RequestType::Algebraic));
+    assert!(
+        matches!(request.payload, RequestPayload::Input(ref expression) if expression == "1+2")
+    );
+
+    assert!(
+        serde_json::from_str::<ClientRequestMessage>(r#"{"request_type": "algebraic"}"#).is_err()
+    );
+}
@@ -0,0 +1,88 @@
+use squiid_engine::{
+    bucket::Bucket,
+    command_mappings,
+    engine::EngineBuilder,
+    error::ErrorKind,
+    protocol::{
+        client_request::{ClientRequestMessage, RequestPayload, RequestType},
+        server_response::MessageAction,
+    },
+};
+
+/// Convert a list of string slices to a batch payload
+fn batch(commands: &[&str]) -> Vec<String> {
+    commands.iter().map(|command| command.to_string()).collect()
+}
+
+#[test]
+fn test_batch() {
+    let mut engine = EngineBuilder::new().build().unwrap();
+    let commands = command_mappings::create_command_registry();
+
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "7");
+    assert_eq!(
+        squiid_engine::handle_batch_data(
+            &mut engine,
+            &commands,
+            &batch(&["1", "2", "add", "{", "$k", "}", "k", "1", "3", "sum"])
+        ),
+        Ok(MessageAction::SendStack)
+    );
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(7), Bucket::from(3), Bucket::from(6)]
+    );
+
+    // the whole batch is a single undo step
+    let _ = engine.undo();
+    assert_eq!(engine.stack, vec![Bucket::from(7)]);
+    let _ = engine.redo();
+    assert_eq!(engine.stack.len(), 3);
+}
+
+#[test]
+fn test_batch_rollback() {
+    let mut engine = EngineBuilder::new().build().unwrap();
+    let commands = command_mappings::create_command_registry();
+
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "7");
+    let history_length = engine.history.len();
+
+    // a failing command undoes the commands before it and reports its index
+    let error = squiid_engine::handle_batch_data(
+        &mut engine,
+        &commands,
+        &batch(&["5", "x", "store", "drop", "swap"]),
+    )
+    .unwrap_err();
+    assert_eq!(error.code, ErrorKind::StackUnderflow);
+    assert_eq!(error.index, Some(4));
+    assert_eq!(engine.variables.get("x"), None);
+
+    let error = squiid_engine::handle_batch_data(
+        &mut engine,
+        &commands,
+        &batch(&["1", "0", "divide", "2"]),
+    )
+    .unwrap_err();
+    assert_eq!(error.code, ErrorKind::DivideByZero);
+    assert_eq!(error.command.as_deref(), Some("divide"));
+    assert_eq!(error.index, Some(2));
+
+    assert_eq!(engine.stack, vec![Bucket::from(7)]);
+    assert_eq!(engine.history.len(), history_length);
+    assert_eq!(engine.program_depth, 0);
+}
+
+#[test]
+fn test_request_batch() {
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "batch", "payload": ["1", "2", "add"]}"#).unwrap();
+    assert!(matches!(request.request_type, RequestType::Batch));
+    assert!(matches!(request.payload, RequestPayload::Batch(ref commands) if commands.len() == 3));
+
+    assert!(serde_json::from_str::<ClientRequestMessage>(
+        r#"{"request_type": "batch", "payload": "add"}"#
+    )
+    .is_err());
+}
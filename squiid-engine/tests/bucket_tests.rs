@@ -1,4 +1,4 @@
-use squiid_engine::bucket::{Bucket, BucketTypes};
+use squiid_engine::bucket::{parse_program_literal, Bucket, BucketTypes, ConstantTypes};
 
 #[test]
 fn test_bucket_creation() {
@@ -29,3 +29,74 @@ fn test_convert_to_string() {
     assert_eq!(Bucket::from("test").to_string(), String::from("test"));
     assert_eq!(Bucket::from(3).to_string(), String::from("3"));
 }
+
+#[test]
+fn test_list_bucket() {
+    let list = Bucket::from_list(vec![Bucket::from(1), Bucket::from(-3.5), Bucket::from("a")]);
+
+    assert_eq!(list.bucket_type, BucketTypes::List);
+    assert_eq!(list.to_string(), String::from("[1, -3.5, a]"));
+    assert_eq!(
+        list.list_items(),
+        Some(vec![Bucket::from(1), Bucket::from(-3.5), Bucket::from("a")])
+    );
+
+    assert_eq!(Bucket::from(1).list_items(), None);
+}
+
+#[test]
+fn test_program_bucket() {
+    let tokens: Vec<String> = vec!["$k".into(), "2".into(), "power".into()];
+    let program = Bucket::from_program(&tokens);
+
+    assert_eq!(program.bucket_type, BucketTypes::Program);
+    assert_eq!(program.to_string(), String::from("{ $k 2 power }"));
+    assert_eq!(program.program_tokens(), Some(tokens));
+
+    assert_eq!(
+        parse_program_literal("{$k { $j } j 1 2 sum}"),
+        Some(vec![
+            "$k".into(),
+            "{".into(),
+            "$j".into(),
+            "}".into(),
+            "j".into(),
+            "1".into(),
+            "2".into(),
+            "sum".into()
+        ])
+    );
+    assert_eq!(parse_program_literal("{ 1 } { 2 }"), None);
+    assert_eq!(Bucket::from(1).program_tokens(), None);
+}
+
+#[test]
+fn test_deserialize_bucket() {
+    // plain strings are always string buckets
+    let bucket: Bucket = serde_json::from_str(r#""3""#).unwrap();
+    assert_eq!(bucket.bucket_type, BucketTypes::String);
+
+    // typed buckets keep their type
+    let bucket: Bucket = serde_json::from_str(r#"{"type": "float", "value": "3"}"#).unwrap();
+    assert_eq!(bucket, Bucket::from(3));
+    let bucket: Bucket = serde_json::from_str(r#"{"type": "undefined"}"#).unwrap();
+    assert_eq!(bucket, Bucket::new_undefined());
+    let bucket: Bucket =
+        serde_json::from_str(r#"{"type": "constant", "name": "pi", "value": "3.14"}"#).unwrap();
+    assert_eq!(bucket, Bucket::from_constant(ConstantTypes::PI));
+    let bucket: Bucket =
+        serde_json::from_str(r#"{"type": "string", "value": "Undefined"}"#).unwrap();
+    assert_eq!(bucket, Bucket::from("Undefined"));
+
+    assert!(serde_json::from_str::<Bucket>(r#"{"type": "constant", "name": "nothing"}"#).is_err());
+
+    // values that the engine cannot use are rejected
+    assert!(serde_json::from_str::<Bucket>(r#"{"type": "float", "value": "abc"}"#).is_err());
+    assert!(serde_json::from_str::<Bucket>(r#"{"type": "list", "value": "1, 2"}"#).is_err());
+    assert!(serde_json::from_str::<Bucket>(r#"{"type": "program", "value": "{ 1 } }"}"#).is_err());
+    let bucket: Bucket = serde_json::from_str(r#"{"type": "list", "value": "[1, 2]"}"#).unwrap();
+    assert_eq!(
+        bucket.list_items(),
+        Some(vec![Bucket::from(1), Bucket::from(2)])
+    );
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "ipc")]
+
+use std::time::Duration;
+
+use squiid_engine::{
+    client::{Client, ClientError, Transport},
+    engine::EngineBuilder,
+    error::{EngineError, ErrorKind},
+    protocol::{
+        client_request::ClientRequestMessage,
+        server_response::{ResponsePayload, ResponseType, ServerResponseMessage},
+    },
+    server::Server,
+};
+
+/// Answers requests with a server in the same process
+struct ServerTransport(Server);
+
+impl Transport for ServerTransport {
+    fn request(&self, request: &str, _timeout: Option<Duration>) -> Result<String, ClientError> {
+        let request: ClientRequestMessage =
+            serde_json::from_str(request).map_err(|err| ClientError::Transport(err.to_string()))?;
+        let response = self.0.handle_request(&request).unwrap_or_else(|| {
+            ServerResponseMessage::new(ResponseType::QuitSig, ResponsePayload::QuitSig(None))
+        });
+        serde_json::to_string(&response).map_err(|err| ClientError::Transport(err.to_string()))
+    }
+}
+
+/// Convert a stack to strings
+fn strings(stack: Vec<squiid_engine::bucket::Bucket>) -> Vec<String> {
+    stack.iter().map(|item| item.to_string()).collect()
+}
+
+#[test]
+fn test_client() {
+    let server =
+        Server::new(EngineBuilder::new().build().unwrap()).with_token(Some("secret".into()));
+    let client = Client::new(ServerTransport(server)).with_token("secret");
+
+    assert_eq!(strings(client.push("3").unwrap()), ["3"]);
+    assert_eq!(strings(client.push("4").unwrap()), ["3", "4"]);
+    assert!(matches!(
+        client.exec("add").unwrap(),
+        ResponsePayload::Stack(_)
+    ));
+    assert_eq!(strings(client.stack().unwrap()), ["7"]);
+    assert_eq!(strings(client.batch(&["2", "multiply"]).unwrap()), ["14"]);
+    assert_eq!(
+        client.eval_algebraic("2*3").unwrap().unwrap().to_string(),
+        "6"
+    );
+    assert!(client.commands().unwrap().contains(&"add".to_string()));
+    assert_eq!(
+        client.get_config("system", "start_mode").unwrap(),
+        serde_json::Value::String("algebraic".into())
+    );
+
+    // errors of the engine are returned with their code
+    match client.exec("clear").and_then(|_| client.exec("add")) {
+        Err(ClientError::Engine(error)) => assert_eq!(error.code, ErrorKind::StackUnderflow),
+        result => panic!("adding on an empty stack returned {:?}", result),
+    }
+    assert!(matches!(
+        client.stack(),
+        Ok(stack) if stack.is_empty()
+    ));
+
+    client.quit().unwrap();
+}
+
+#[test]
+fn test_client_token() {
+    let server =
+        Server::new(EngineBuilder::new().build().unwrap()).with_token(Some("secret".into()));
+    let client = Client::new(ServerTransport(server));
+    match client.stack() {
+        Err(ClientError::Engine(error)) => assert_eq!(error.code, ErrorKind::Unauthorized),
+        result => panic!("a request without the token returned {:?}", result),
+    }
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_unix_socket_client() {
+    use std::thread;
+
+    use squiid_engine::{
+        client::unix_socket::UnixSocketTransport,
+        ipc::{unix_socket::UnixSocket, IPCBackend},
+    };
+
+    let path = std::env::temp_dir().join(format!("squiid-client-{}.sock", std::process::id()));
+    let backend = UnixSocket::new();
+    backend.bind_and_listen(path.to_str().unwrap()).unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    // results are checked once the server has quit, so that a failed assertion does
+    // not leave it running
+    let (pushed, timed_out, stack, quit) = thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        let address = format!("unix://{}", path.display());
+        let client = Client::new(UnixSocketTransport::connect(&address).unwrap())
+            .with_timeout(Duration::from_millis(50));
+        let pushed = client.push("1");
+
+        // a program that runs for too long is stopped by the timeout
+        for input in ["{", "$k", "}", "k", "1", "999999"] {
+            let _ = client.exec(input);
+        }
+        let timed_out = client.exec("sum");
+
+        // the client can be used again after a timeout
+        let stack = client.exec("clear").and_then(|_| client.stack());
+        (pushed, timed_out, stack, client.quit())
+    });
+    assert!(!path.exists());
+
+    assert_eq!(strings(pushed.unwrap()), ["1"]);
+    assert!(matches!(
+        timed_out,
+        Err(ClientError::Timeout)
+            | Err(ClientError::Engine(EngineError {
+                code: ErrorKind::TimedOut,
+                ..
+            }))
+    ));
+    assert!(stack.unwrap().is_empty());
+    assert!(quit.is_ok());
+}
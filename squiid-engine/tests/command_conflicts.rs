@@ -18,9 +18,6 @@ fn check_commands(strings: &Vec<String>) -> Option<(String, String)> {
 
 #[test]
 fn test_no_conflicts() {
-    let commands = command_mappings::create_function_map()
-        .keys()
-        .map(|k| k.to_owned())
-        .collect();
+    let commands = command_mappings::create_command_registry().names_and_aliases();
     assert_eq!(check_commands(&commands), None);
 }
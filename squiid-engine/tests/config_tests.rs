@@ -0,0 +1,33 @@
+// the user config is found through XDG_CONFIG_HOME on Linux. These tests change it
+// for the whole process, so they are kept apart from tests that read the user config
+#![cfg(target_os = "linux")]
+
+use std::fs;
+
+use squiid_engine::{engine::EngineBuilder, error::ErrorKind};
+
+#[test]
+fn test_user_config_errors() {
+    let directory = std::env::temp_dir().join(format!("squiid_xdg_{}", std::process::id()));
+    let config_path = directory.join("squiid").join("config.toml");
+    fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &directory);
+
+    // a config that cannot be parsed is returned as an error
+    fs::write(&config_path, "[system\n").unwrap();
+    let error = EngineBuilder::new().user_config().build().err().unwrap();
+    assert_eq!(error.code, ErrorKind::Configuration);
+
+    // so is a config that cannot be read
+    fs::remove_file(&config_path).unwrap();
+    fs::create_dir(&config_path).unwrap();
+    let error = EngineBuilder::new().user_config().build().err().unwrap();
+    assert_eq!(error.code, ErrorKind::Configuration);
+
+    // a missing config is created
+    fs::remove_dir(&config_path).unwrap();
+    assert!(EngineBuilder::new().user_config().build().is_ok());
+    assert!(config_path.exists());
+
+    let _ = fs::remove_dir_all(directory);
+}
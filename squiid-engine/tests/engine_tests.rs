@@ -2,16 +2,60 @@ use std::{f64::consts::PI, fs, path::PathBuf};
 
 use squiid_engine::{
     bucket::{Bucket, BucketTypes, ConstantTypes},
-    command_mappings,
+    command_mappings::{self, CommandCategory, CommandInfo, OperandType},
     engine::*,
+    error::ErrorKind,
+    interrupt::Interrupt,
     protocol::server_response::MessageAction,
 };
 
+/// Create an engine with the given numbers on the stack
+fn engine_with_stack(items: &[i32]) -> Engine {
+    let mut engine = Engine::new();
+    for item in items {
+        let _ = engine.add_item_to_stack(Bucket::from(*item));
+    }
+    engine
+}
+
+/// Convert a slice of numbers to a stack of Buckets
+fn stack_of(items: &[i32]) -> Vec<Bucket> {
+    items.iter().map(|item| Bucket::from(*item)).collect()
+}
+
 #[test]
 fn test_create_engine() {
     Engine::new();
 }
 
+#[test]
+fn test_engine_builder() {
+    let engine = EngineBuilder::new().build().unwrap();
+    assert_eq!(engine.undo_depth(), Some(100));
+
+    let engine = EngineBuilder::new()
+        .config(squiid_engine::config_handler::Config::default())
+        .build()
+        .unwrap();
+    assert!(engine.persist_session());
+
+    // keys missing from a config file use their default value
+    let path = std::env::temp_dir().join(format!("squiid_config_{}.toml", std::process::id()));
+    fs::write(&path, "[system]\nundo_depth = 5\npersist_session = false\n").unwrap();
+    let engine = EngineBuilder::new().config_path(&path).build().unwrap();
+    let _ = fs::remove_file(&path);
+    assert_eq!(engine.undo_depth(), Some(5));
+    assert!(!engine.persist_session());
+    assert!(engine.config.get_key("system", "start_mode").is_ok());
+
+    let error = EngineBuilder::new()
+        .config_path(path)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(error.code, ErrorKind::Configuration);
+}
+
 #[test]
 fn test_add_negative_to_stack() {
     let mut engine = Engine::new();
@@ -741,239 +785,1163 @@ fn test_invert() {
 }
 
 #[test]
-fn test_drop() {
+fn test_polyroots() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("0.1".into());
+    // x^2 - 3x + 2 = (x - 1)(x - 2)
+    let _ = engine.add_item_to_stack("[1, -3, 2]".into());
+    let _ = engine.polyroots();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from_list(vec![Bucket::from(1), Bucket::from(2)])]
+    );
 
-    let _ = engine.drop();
-    assert_eq!(engine.stack, vec![Bucket::from(-1.2),]);
+    // x^2 + 1 has complex roots
+    let _ = engine.add_item_to_stack("[1, 0, 1]".into());
+    let _ = engine.polyroots();
+    assert_eq!(
+        *engine.stack.last().unwrap(),
+        Bucket::from_list(vec![Bucket::from("0-1i"), Bucket::from("0+1i")])
+    );
 
-    let _ = engine.drop();
-    assert_eq!(engine.stack, vec![]);
+    // x^3 - 6x^2 + 11x - 6 = (x - 1)(x - 2)(x - 3)
+    let _ = engine.add_item_to_stack("[1, -6, 11, -6]".into());
+    let _ = engine.polyroots();
+    assert_eq!(
+        *engine.stack.last().unwrap(),
+        Bucket::from_list(vec![Bucket::from(1), Bucket::from(2), Bucket::from(3)])
+    );
 
-    let result = engine.drop();
+    // small roots are rounded relative to their magnitude
+    let _ = engine.add_item_to_stack("[1, -1e-12]".into());
+    let _ = engine.polyroots();
     assert_eq!(
-        result,
-        Ok(squiid_engine::protocol::server_response::MessageAction::SendStack)
+        *engine.stack.last().unwrap(),
+        Bucket::from_list(vec![Bucket::from(1e-12)])
+    );
+    let _ = engine.add_item_to_stack("[1, 0, 1e-24]".into());
+    let _ = engine.polyroots();
+    assert_eq!(
+        *engine.stack.last().unwrap(),
+        Bucket::from_list(vec![
+            Bucket::from("0-0.000000000001i"),
+            Bucket::from("0+0.000000000001i")
+        ])
     );
+
+    // constant polynomials have no roots
+    let _ = engine.add_item_to_stack("[5]".into());
+    let result = engine.polyroots();
+    assert!(matches!(result, Err(_)));
+
+    // non-list operands are rejected without being consumed
+    let _ = engine.add_item_to_stack("3".into());
+    let result = engine.polyroots();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(*engine.stack.last().unwrap(), Bucket::from(3));
 }
 
 #[test]
-fn test_swap() {
+fn test_polyval() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("0.1".into());
-    let _ = engine.add_item_to_stack("3".into());
+    let _ = engine.add_item_to_stack("[1, -3, 2]".into());
+    let _ = engine.add_item_to_stack("4".into());
+    let _ = engine.polyval();
+    assert_eq!(engine.stack, vec![Bucket::from(6)]);
 
-    let _ = engine.swap();
+    let _ = engine.add_item_to_stack("4".into());
+    let result = engine.polyval();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_polyder() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("[1, -3, 2]".into());
+    let _ = engine.polyder();
     assert_eq!(
         engine.stack,
-        vec![Bucket::from(-1.2), Bucket::from(3), Bucket::from(0.1),]
+        vec![Bucket::from_list(vec![Bucket::from(2), Bucket::from(-3)])]
     );
 }
 
 #[test]
-fn test_dup() {
+fn test_polyint() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("0.1".into());
-
-    let _ = engine.dup();
+    let _ = engine.add_item_to_stack("[3, 2, 1]".into());
+    let _ = engine.polyint();
     assert_eq!(
         engine.stack,
-        vec![Bucket::from(-1.2), Bucket::from(0.1), Bucket::from(0.1),]
+        vec![Bucket::from_list(vec![
+            Bucket::from(1),
+            Bucket::from(1),
+            Bucket::from(1),
+            Bucket::from(0)
+        ])]
     );
 }
 
 #[test]
-fn test_rolldown() {
+fn test_polymul() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("0.1".into());
-    let _ = engine.add_item_to_stack("3".into());
-
-    let _ = engine.roll_down();
+    let _ = engine.add_item_to_stack("[1, -1]".into());
+    let _ = engine.add_item_to_stack("[1, -2]".into());
+    let _ = engine.polymul();
     assert_eq!(
         engine.stack,
-        vec![Bucket::from(3), Bucket::from(-1.2), Bucket::from(0.1),]
+        vec![Bucket::from_list(vec![
+            Bucket::from(1),
+            Bucket::from(-3),
+            Bucket::from(2)
+        ])]
     );
-
-    let _ = engine.clear();
-    let result = engine.roll_down();
-
-    assert!(matches!(result, Err(_)));
 }
 
 #[test]
-fn test_rollup() {
+fn test_polydiv() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("0.1".into());
-    let _ = engine.add_item_to_stack("3".into());
-
-    let _ = engine.roll_up();
+    // (x^2 - 3x + 3) / (x - 1) = x - 2 remainder 1
+    let _ = engine.add_item_to_stack("[1, -3, 3]".into());
+    let _ = engine.add_item_to_stack("[1, -1]".into());
+    let _ = engine.polydiv();
     assert_eq!(
         engine.stack,
-        vec![Bucket::from(0.1), Bucket::from(3), Bucket::from(-1.2),]
+        vec![
+            Bucket::from_list(vec![Bucket::from(1), Bucket::from(-2)]),
+            Bucket::from_list(vec![Bucket::from(1)]),
+        ]
     );
 
-    let _ = engine.clear();
-    let result = engine.roll_up();
-
+    // division by the zero polynomial
+    let _ = engine.add_item_to_stack("[0]".into());
+    let result = engine.polydiv();
     assert!(matches!(result, Err(_)));
 }
 
 #[test]
-fn test_store() {
+fn test_polynomial_interrupt() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("0".into());
+    // iterative commands stop once they are cancelled, leaving the stack untouched
+    let _ = engine.add_item_to_stack("[1, 0, 0, -1]".into());
+    engine.interrupt.clone().cancel();
+    assert_eq!(engine.polyroots().unwrap_err().code, ErrorKind::Cancelled);
+    assert_eq!(engine.stack.len(), 1);
 
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("a".into());
+    engine.stack.clear();
+    let _ = engine.add_item_to_stack("[1, 2, 3]".into());
+    let _ = engine.add_item_to_stack("[1, 4, 9]".into());
+    let _ = engine.add_item_to_stack("2".into());
+    assert_eq!(engine.polyfit().unwrap_err().code, ErrorKind::Cancelled);
+    assert_eq!(engine.stack.len(), 3);
 
-    // test valid variable assignment
-    let _ = engine.store();
-    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(-1.2));
+    engine.interrupt.reset(None);
+    assert!(engine.polyfit().is_ok());
+}
 
-    // test invalid variable assignment
-    let result = engine.store();
+#[test]
+fn test_polyfit() {
+    let mut engine = Engine::new();
+
+    // y = 2x + 1
+    let _ = engine.add_item_to_stack("[0, 1, 2, 3]".into());
+    let _ = engine.add_item_to_stack("[1, 3, 5, 7]".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.polyfit();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from_list(vec![Bucket::from(2), Bucket::from(1)])]
+    );
+
+    // y = x^2
+    let _ = engine.add_item_to_stack("[-2, -1, 0, 1, 2]".into());
+    let _ = engine.add_item_to_stack("[4, 1, 0, 1, 4]".into());
+    let _ = engine.add_item_to_stack("2".into());
+    let _ = engine.polyfit();
+    assert_eq!(
+        *engine.stack.last().unwrap(),
+        Bucket::from_list(vec![Bucket::from(1), Bucket::from(0), Bucket::from(0)])
+    );
+
+    // small coefficients are not rounded away
+    let coefficients = squiid_engine::polynomial::fit(
+        &[0.0, 1.0, 2.0, 3.0],
+        &[1.0, 1.0 + 1e-11, 1.0 + 2e-11, 1.0 + 3e-11],
+        1,
+        &Interrupt::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert!((coefficients[0] - 1e-11).abs() < 1e-15);
+    assert_eq!(coefficients[1], 1.0);
+    let coefficients = squiid_engine::polynomial::fit(
+        &[1e6, 2e6, 3e6],
+        &[1e-12, 4e-12, 9e-12],
+        2,
+        &Interrupt::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert!((coefficients[0] - 1e-24).abs() < 1e-30);
+
+    // not enough points for the degree
+    let _ = engine.add_item_to_stack("[1, 2]".into());
+    let _ = engine.add_item_to_stack("[1, 2]".into());
+    let _ = engine.add_item_to_stack("2".into());
+    let result = engine.polyfit();
     assert!(matches!(result, Err(_)));
 }
 
 #[test]
-fn test_purge() {
+fn test_tocf() {
     let mut engine = Engine::new();
 
-    engine
-        .variables
-        .insert(String::from("a"), Bucket::from(-1.2));
+    let _ = engine.add_item_to_stack("0.75".into());
+    let _ = engine.tocf();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from_list(vec![
+            Bucket::from(0),
+            Bucket::from(1),
+            Bucket::from(3)
+        ])]
+    );
 
-    let _ = engine.add_item_to_stack("a".into());
-    let _ = engine.add_item_to_stack("a".into());
+    // floating point noise is ignored
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("0.30000000000000004".into());
+    let _ = engine.tocf();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from_list(vec![
+            Bucket::from(0),
+            Bucket::from(3),
+            Bucket::from(3)
+        ])]
+    );
 
-    // test for variable presence
-    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(-1.2));
+    // negative values use a negative first term
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("-1.5".into());
+    let _ = engine.tocf();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from_list(vec![Bucket::from(-2), Bucket::from(2)])]
+    );
 
-    let _ = engine.purge();
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("#pi".into());
+    let _ = engine.tocf();
+    let terms = engine.stack[0].list_items().unwrap();
+    assert_eq!(
+        terms[..5],
+        [
+            Bucket::from(3),
+            Bucket::from(7),
+            Bucket::from(15),
+            Bucket::from(1),
+            Bucket::from(292)
+        ]
+    );
+}
 
-    // test that variable was deleted
-    assert!(engine.variables.get("a").is_none());
+#[test]
+fn test_fromcf() {
+    let mut engine = Engine::new();
 
-    // test invalid variable deletion
-    let result = engine.purge();
+    let _ = engine.add_item_to_stack("[0, 1, 3]".into());
+    let _ = engine.fromcf();
+    assert_eq!(engine.stack, vec![Bucket::from(0.75)]);
+
+    let _ = engine.add_item_to_stack("[3, 7, 16]".into());
+    let _ = engine.fromcf();
+    let value = engine
+        .stack
+        .last()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    assert!((value - 355.0 / 113.0).abs() < 1e-12);
+
+    // invalid terms leave the list on the stack
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("[1, 0]".into());
+    let result = engine.fromcf();
     assert!(matches!(result, Err(_)));
+    let _ = engine.add_item_to_stack("[1, 2.5]".into());
+    let result = engine.fromcf();
+    assert!(matches!(result, Err(_)));
+    let _ = engine.add_item_to_stack("[]".into());
+    let result = engine.fromcf();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack.len(), 3);
 }
 
 #[test]
-fn test_invstore() {
+fn test_approx() {
     let mut engine = Engine::new();
 
-    let _ = engine.add_item_to_stack("a".into());
-    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("#pi".into());
+    let _ = engine.add_item_to_stack("1000".into());
+    let _ = engine.approx();
+    assert_eq!(engine.stack, vec![Bucket::from(355), Bucket::from(113)]);
 
-    let _ = engine.invstore();
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("#pi".into());
+    let _ = engine.add_item_to_stack("10".into());
+    let _ = engine.approx();
+    assert_eq!(engine.stack, vec![Bucket::from(22), Bucket::from(7)]);
 
-    // test for variable presence
-    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(-1.2));
-}
+    // exact values are reduced
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("0.75".into());
+    let _ = engine.add_item_to_stack("100".into());
+    let _ = engine.approx();
+    assert_eq!(engine.stack, vec![Bucket::from(3), Bucket::from(4)]);
 
-#[test]
-fn test_clear() {
-    let mut engine = Engine::new();
+    // semiconvergents are considered
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("0.1428".into());
+    let _ = engine.add_item_to_stack("6".into());
+    let _ = engine.approx();
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(6)]);
 
-    let _ = engine.add_item_to_stack("23".into());
-    let _ = engine.add_item_to_stack("-1.2".into());
-    let _ = engine.add_item_to_stack("test".into());
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("-1.3333".into());
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.approx();
+    assert_eq!(engine.stack, vec![Bucket::from(-4), Bucket::from(3)]);
 
-    assert!(!engine.stack.is_empty());
     let _ = engine.clear();
-    assert!(engine.stack.is_empty());
+    let _ = engine.add_item_to_stack("1.5".into());
+    let _ = engine.add_item_to_stack("0".into());
+    let result = engine.approx();
+    assert!(matches!(result, Err(_)));
 }
 
 #[test]
-fn test_undo() {
+fn test_setmod() {
     let mut engine = Engine::new();
 
-    // after each command, we must push a copy of the stack to the engine history
-    let commands = command_mappings::create_function_map();
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "1");
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "2");
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "test");
+    let _ = engine.add_item_to_stack("17".into());
+    let _ = engine.setmod();
+    assert_eq!(engine.modulus, Some(17));
+    assert_eq!(engine.stack, vec![]);
 
-    // test undo of adding something to the stack
-    let _ = engine.undo();
-    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+    // add, subtract, multiply are reduced
+    let _ = engine.add_item_to_stack("15".into());
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.add();
+    assert_eq!(engine.stack, vec![Bucket::from(3)]);
 
-    // test undo of operation
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "add");
-    assert_eq!(engine.stack, vec![Bucket::from(3),]);
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.subtract();
+    assert_eq!(engine.stack, vec![Bucket::from(15)]);
 
-    let _ = engine.undo();
-    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+    let _ = engine.add_item_to_stack("4".into());
+    let _ = engine.multiply();
+    assert_eq!(engine.stack, vec![Bucket::from(9)]);
 
-    // test undo of clear
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "clear");
-    assert_eq!(engine.stack, vec![]);
+    // division multiplies by the modular inverse
+    let _ = engine.add_item_to_stack("3".into());
+    let _ = engine.divide();
+    assert_eq!(engine.stack, vec![Bucket::from(3)]);
 
-    let _ = engine.undo();
-    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.divide();
+    assert_eq!(*engine.stack.last().unwrap(), Bucket::from(7));
 
-    // test undo of variable assignment
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "a");
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "store");
+    // exponents are not reduced, negative exponents use the inverse
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("3".into());
+    let _ = engine.add_item_to_stack("100".into());
+    let _ = engine.power();
+    assert_eq!(engine.stack, vec![Bucket::from(13)]);
 
-    assert_eq!(engine.stack, vec![Bucket::from(1),]);
-    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(2));
+    let _ = engine.add_item_to_stack("-1".into());
+    let _ = engine.power();
+    assert_eq!(engine.stack, vec![Bucket::from(4)]);
 
-    let _ = engine.undo();
-    assert_eq!(
-        engine.stack,
-        vec![Bucket::from(1), Bucket::from(2), Bucket::from("a"),]
-    );
-    assert_eq!(engine.variables.get("a"), None);
+    // non-integer operands are rejected
+    let _ = engine.add_item_to_stack("1.5".into());
+    let _ = engine.add_item_to_stack("2".into());
+    let result = engine.add();
+    assert!(matches!(result, Err(_)));
 
-    // test that we dont crash from extra undos
-    let _ = engine.undo();
-    let _ = engine.undo();
+    // non-invertible divisors are rejected
+    let _ = engine.add_item_to_stack("12".into());
+    let _ = engine.setmod();
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.add_item_to_stack("4".into());
+    let result = engine.divide();
+    assert!(matches!(result, Err(_)));
+
+    // invalid moduli are rejected
+    let _ = engine.add_item_to_stack("1".into());
+    let result = engine.setmod();
+    assert!(matches!(result, Err(_)));
+    let _ = engine.add_item_to_stack("2.5".into());
+    let result = engine.setmod();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.modulus, Some(12));
+
+    // modulus survives undo
+    let commands = command_mappings::create_command_registry();
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "7");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "setmod");
+    assert_eq!(engine.modulus, Some(7));
     let _ = engine.undo();
+    assert_eq!(engine.modulus, Some(12));
+    let _ = engine.redo();
+    assert_eq!(engine.modulus, Some(7));
 }
 
 #[test]
-fn test_redo() {
+fn test_unsetmod() {
     let mut engine = Engine::new();
+    engine.modulus = Some(17);
 
-    // after each command, we must push a copy of the stack to the engine history
-    let commands = command_mappings::create_function_map();
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "1");
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "2");
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "test");
+    let _ = engine.unsetmod();
+    assert_eq!(engine.modulus, None);
 
-    // undo adding something to the stack
-    let _ = engine.undo();
-    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
-    // redo the undo
-    let _ = engine.redo();
-    assert_eq!(
-        engine.stack,
-        vec![Bucket::from(1), Bucket::from(2), Bucket::from("test")]
-    );
+    let _ = engine.add_item_to_stack("15".into());
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.add();
+    assert_eq!(engine.stack, vec![Bucket::from(20)]);
+}
 
-    // undo drop
-    let _ = squiid_engine::handle_data(&mut engine, &commands, "drop");
-    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+#[test]
+fn test_getmod() {
+    let mut engine = Engine::new();
+    engine.modulus = Some(17);
 
-    let _ = engine.undo();
+    let result = engine.getmod();
     assert_eq!(
-        engine.stack,
-        vec![Bucket::from(1), Bucket::from(2), Bucket::from("test")]
+        result,
+        Ok(squiid_engine::protocol::server_response::MessageAction::SendModulus)
+    );
+}
+
+#[test]
+fn test_statadd() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("2".into());
+    let _ = engine.statadd();
+    assert_eq!(engine.stack, vec![]);
+    assert_eq!(engine.statistics.points, vec![(1.0, 2.0)]);
+
+    // non-numeric operands are rejected
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("\"test\"".into());
+    let result = engine.statadd();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.statistics.points, vec![(1.0, 2.0)]);
+
+    // stats survive undo and redo
+    let commands = command_mappings::create_command_registry();
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "clear");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "3");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "4");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "statadd");
+    assert_eq!(engine.statistics.points, vec![(1.0, 2.0), (3.0, 4.0)]);
+
+    let _ = engine.undo();
+    assert_eq!(engine.statistics.points, vec![(1.0, 2.0)]);
+    assert_eq!(engine.stack, vec![Bucket::from(3), Bucket::from(4)]);
+
+    let _ = engine.redo();
+    assert_eq!(engine.statistics.points, vec![(1.0, 2.0), (3.0, 4.0)]);
+}
+
+#[test]
+fn test_statdel() {
+    let mut engine = Engine::new();
+    engine.statistics.add_point(1.0, 2.0);
+    engine.statistics.add_point(3.0, 4.0);
+
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("2".into());
+    let _ = engine.statdel();
+    assert_eq!(engine.stack, vec![]);
+    assert_eq!(engine.statistics.points, vec![(3.0, 4.0)]);
+
+    // removing a point that does not exist leaves the stack intact
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.add_item_to_stack("6".into());
+    let result = engine.statdel();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack, vec![Bucket::from(5), Bucket::from(6)]);
+    assert_eq!(engine.statistics.points, vec![(3.0, 4.0)]);
+}
+
+#[test]
+fn test_statclear() {
+    let mut engine = Engine::new();
+    engine.statistics.add_point(1.0, 2.0);
+    engine.statistics.add_point(3.0, 4.0);
+
+    let _ = engine.statclear();
+    assert_eq!(engine.statistics.points, vec![]);
+}
+
+#[test]
+fn test_statlist() {
+    let mut engine = Engine::new();
+    engine.statistics.add_point(1.0, 2.0);
+
+    let result = engine.statlist();
+    assert_eq!(
+        result,
+        Ok(squiid_engine::protocol::server_response::MessageAction::SendStatistics)
+    );
+}
+
+#[test]
+fn test_reglin() {
+    let mut engine = Engine::new();
+
+    // not enough data
+    let result = engine.reglin();
+    assert!(matches!(result, Err(_)));
+
+    // y = 1 + 2x
+    for (x, y) in [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0), (3.0, 7.0)] {
+        engine.statistics.add_point(x, y);
+    }
+    let _ = engine.reglin();
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2)]);
+}
+
+#[test]
+fn test_regexp() {
+    let mut engine = Engine::new();
+
+    // y = 3 * e^(0.5x)
+    for x in [0.0, 1.0, 2.0, 3.0] {
+        engine.statistics.add_point(x, 3.0 * (0.5 * x as f64).exp());
+    }
+    let _ = engine.regexp();
+    let b = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    let a = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    assert!((a - 3.0).abs() < 1e-9);
+    assert!((b - 0.5).abs() < 1e-9);
+
+    // non-positive y values cannot be fit
+    engine.statistics.add_point(4.0, -1.0);
+    let result = engine.regexp();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_reglog() {
+    let mut engine = Engine::new();
+
+    // y = 2 + 4ln(x)
+    for x in [1.0, 2.0, 4.0, 8.0] {
+        engine.statistics.add_point(x, 2.0 + 4.0 * (x as f64).ln());
+    }
+    let _ = engine.reglog();
+    let b = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    let a = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    assert!((a - 2.0).abs() < 1e-9);
+    assert!((b - 4.0).abs() < 1e-9);
+
+    // non-positive x values cannot be fit
+    engine.statistics.add_point(0.0, 1.0);
+    let result = engine.reglog();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_regpow() {
+    let mut engine = Engine::new();
+
+    // y = 5x^2
+    for x in [1.0, 2.0, 3.0, 4.0] {
+        engine.statistics.add_point(x, 5.0 * (x as f64).powi(2));
+    }
+    let _ = engine.regpow();
+    let b = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    let a = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    assert!((a - 5.0).abs() < 1e-9);
+    assert!((b - 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_rsquared() {
+    let mut engine = Engine::new();
+
+    // perfect linear fit
+    for (x, y) in [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)] {
+        engine.statistics.add_point(x, y);
+    }
+    let _ = engine.rsquared();
+    assert_eq!(engine.stack, vec![Bucket::from(1)]);
+
+    // imperfect fit
+    let _ = engine.statclear();
+    for (x, y) in [(1.0, 1.0), (2.0, 3.0), (3.0, 2.0)] {
+        engine.statistics.add_point(x, y);
+    }
+    let _ = engine.rsquared();
+    assert_eq!(*engine.stack.last().unwrap(), Bucket::from(0.25));
+}
+
+#[test]
+fn test_predict() {
+    let mut engine = Engine::new();
+
+    // y = 1 + 2x
+    for (x, y) in [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)] {
+        engine.statistics.add_point(x, y);
+    }
+    let _ = engine.add_item_to_stack("10".into());
+    let _ = engine.predict();
+    assert_eq!(engine.stack, vec![Bucket::from(21)]);
+
+    // predict uses the model of the last regression command
+    let _ = engine.statclear();
+    for x in [1.0, 2.0, 3.0] {
+        engine.statistics.add_point(x, 5.0 * (x as f64).powi(2));
+    }
+    let _ = engine.regpow();
+    let _ = engine.add_item_to_stack("10".into());
+    let _ = engine.predict();
+    let prediction = engine
+        .stack
+        .pop()
+        .unwrap()
+        .to_string()
+        .parse::<f64>()
+        .unwrap();
+    assert!((prediction - 500.0).abs() < 1e-6);
+
+    // x is left on the stack if there is no data
+    let _ = engine.statclear();
+    let _ = engine.add_item_to_stack("10".into());
+    let result = engine.predict();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(*engine.stack.last().unwrap(), Bucket::from(10));
+}
+
+#[test]
+fn test_sum() {
+    let mut engine = Engine::new();
+    let commands = command_mappings::create_command_registry();
+
+    // sum of k^2 for k from 1 to 100, entered token by token
+    for token in ["{", "$k", "2", "power", "}", "k", "1", "100", "sum"] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, token);
+    }
+    assert_eq!(engine.stack, vec![Bucket::from(338350)]);
+    // the temporary variable is removed afterwards
+    assert_eq!(engine.variables.get("k"), None);
+
+    // bare references to the variable and program literals are supported
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("{k 1 add}".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("3".into());
+    let _ = engine.sum();
+    assert_eq!(engine.stack, vec![Bucket::from(9)]);
+
+    // nested sums and restoring an existing variable
+    let _ = engine.clear();
+    engine.variables.insert("k".to_string(), Bucket::from(42));
+    let _ = engine.add_item_to_stack("{ { $j $k multiply } j 1 3 sum }".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("3".into());
+    let _ = engine.sum();
+    assert_eq!(engine.stack, vec![Bucket::from(36)]);
+    assert_eq!(*engine.variables.get("k").unwrap(), Bucket::from(42));
+
+    // empty range
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("{ $k }".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.sum();
+    assert_eq!(engine.stack, vec![Bucket::from(0)]);
+
+    // errors leave the stack untouched
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("{ $k drop }".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("3".into());
+    let result = engine.sum();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack.len(), 4);
+
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("3".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("3".into());
+    let result = engine.sum();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack.len(), 4);
+}
+
+#[test]
+fn test_prod() {
+    let mut engine = Engine::new();
+
+    // 5!
+    let _ = engine.add_item_to_stack("{ $k }".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("5".into());
+    let _ = engine.prod();
+    assert_eq!(engine.stack, vec![Bucket::from(120)]);
+
+    // empty range
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("{ $k }".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("0".into());
+    let _ = engine.prod();
+    assert_eq!(engine.stack, vec![Bucket::from(1)]);
+
+    // non-integer bounds
+    let _ = engine.clear();
+    let _ = engine.add_item_to_stack("{ $k }".into());
+    let _ = engine.add_item_to_stack("k".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let _ = engine.add_item_to_stack("2.5".into());
+    let result = engine.prod();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_drop() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("0.1".into());
+
+    let _ = engine.drop();
+    assert_eq!(engine.stack, vec![Bucket::from(-1.2),]);
+
+    let _ = engine.drop();
+    assert_eq!(engine.stack, vec![]);
+
+    let result = engine.drop();
+    assert_eq!(
+        result,
+        Ok(squiid_engine::protocol::server_response::MessageAction::SendStack)
+    );
+}
+
+#[test]
+fn test_swap() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("0.1".into());
+    let _ = engine.add_item_to_stack("3".into());
+
+    let _ = engine.swap();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(-1.2), Bucket::from(3), Bucket::from(0.1),]
+    );
+}
+
+#[test]
+fn test_dup() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("0.1".into());
+
+    let _ = engine.dup();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(-1.2), Bucket::from(0.1), Bucket::from(0.1),]
+    );
+}
+
+#[test]
+fn test_rolldown() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("0.1".into());
+    let _ = engine.add_item_to_stack("3".into());
+
+    let _ = engine.roll_down();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(3), Bucket::from(-1.2), Bucket::from(0.1),]
+    );
+
+    let _ = engine.clear();
+    let result = engine.roll_down();
+
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_rollup() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("0.1".into());
+    let _ = engine.add_item_to_stack("3".into());
+
+    let _ = engine.roll_up();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(0.1), Bucket::from(3), Bucket::from(-1.2),]
+    );
+
+    let _ = engine.clear();
+    let result = engine.roll_up();
+
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_over() {
+    let mut engine = engine_with_stack(&[1, 2]);
+    let _ = engine.over();
+    assert_eq!(engine.stack, stack_of(&[1, 2, 1]));
+
+    let mut engine = engine_with_stack(&[1]);
+    let result = engine.over();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack, stack_of(&[1]));
+}
+
+#[test]
+fn test_rot() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 4]);
+    let _ = engine.rot();
+    assert_eq!(engine.stack, stack_of(&[1, 3, 4, 2]));
+
+    let mut engine = engine_with_stack(&[1, 2]);
+    let result = engine.rot();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_nip() {
+    let mut engine = engine_with_stack(&[1, 2, 3]);
+    let _ = engine.nip();
+    assert_eq!(engine.stack, stack_of(&[1, 3]));
+
+    let mut engine = engine_with_stack(&[1]);
+    let result = engine.nip();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_tuck() {
+    let mut engine = engine_with_stack(&[1, 2]);
+    let _ = engine.tuck();
+    assert_eq!(engine.stack, stack_of(&[2, 1, 2]));
+
+    let mut engine = engine_with_stack(&[1]);
+    let result = engine.tuck();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_pick() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 3]);
+    let _ = engine.pick();
+    assert_eq!(engine.stack, stack_of(&[1, 2, 3, 1]));
+
+    // errors leave the count on the stack
+    let mut engine = engine_with_stack(&[1, 2, 3]);
+    let result = engine.pick();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack, stack_of(&[1, 2, 3]));
+
+    let mut engine = engine_with_stack(&[1, 0]);
+    let result = engine.pick();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_nroll() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 4, 3]);
+    let _ = engine.nroll();
+    assert_eq!(engine.stack, stack_of(&[1, 3, 4, 2]));
+
+    let mut engine = engine_with_stack(&[1, 2, 1]);
+    let _ = engine.nroll();
+    assert_eq!(engine.stack, stack_of(&[1, 2]));
+
+    let mut engine = engine_with_stack(&[1, 2, 5]);
+    let result = engine.nroll();
+    assert!(matches!(result, Err(_)));
+    assert_eq!(engine.stack, stack_of(&[1, 2, 5]));
+}
+
+#[test]
+fn test_ndup() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 2]);
+    let _ = engine.ndup();
+    assert_eq!(engine.stack, stack_of(&[1, 2, 3, 2, 3]));
+
+    let mut engine = engine_with_stack(&[1, 0]);
+    let _ = engine.ndup();
+    assert_eq!(engine.stack, stack_of(&[1]));
+
+    let mut engine = engine_with_stack(&[1, 2]);
+    let result = engine.ndup();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_ndrop() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 2]);
+    let _ = engine.ndrop();
+    assert_eq!(engine.stack, stack_of(&[1]));
+
+    let mut engine = engine_with_stack(&[1, 2]);
+    let result = engine.ndrop();
+    assert!(matches!(result, Err(_)));
+
+    let mut engine = engine_with_stack(&[1]);
+    let _ = engine.add_item_to_stack("1.5".into());
+    let result = engine.ndrop();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_depth() {
+    let mut engine = engine_with_stack(&[]);
+    let _ = engine.depth();
+    assert_eq!(engine.stack, stack_of(&[0]));
+
+    let mut engine = engine_with_stack(&[5, 6, 7]);
+    let _ = engine.depth();
+    assert_eq!(engine.stack, stack_of(&[5, 6, 7, 3]));
+}
+
+#[test]
+fn test_reverse() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 4, 3]);
+    let _ = engine.reverse();
+    assert_eq!(engine.stack, stack_of(&[1, 4, 3, 2]));
+
+    let mut engine = engine_with_stack(&[1, 2]);
+    let result = engine.reverse();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_keep() {
+    let mut engine = engine_with_stack(&[1, 2, 3, 4, 2]);
+    let _ = engine.keep();
+    assert_eq!(engine.stack, stack_of(&[3, 4]));
+
+    let mut engine = engine_with_stack(&[1, 2, 0]);
+    let _ = engine.keep();
+    assert_eq!(engine.stack, stack_of(&[]));
+
+    let mut engine = engine_with_stack(&[1, 2]);
+    let result = engine.keep();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_store() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("0".into());
+
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("a".into());
+
+    // test valid variable assignment
+    let _ = engine.store();
+    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(-1.2));
+
+    // test invalid variable assignment
+    let result = engine.store();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_purge() {
+    let mut engine = Engine::new();
+
+    engine
+        .variables
+        .insert(String::from("a"), Bucket::from(-1.2));
+
+    let _ = engine.add_item_to_stack("a".into());
+    let _ = engine.add_item_to_stack("a".into());
+
+    // test for variable presence
+    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(-1.2));
+
+    let _ = engine.purge();
+
+    // test that variable was deleted
+    assert!(engine.variables.get("a").is_none());
+
+    // test invalid variable deletion
+    let result = engine.purge();
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_invstore() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("a".into());
+    let _ = engine.add_item_to_stack("-1.2".into());
+
+    let _ = engine.invstore();
+
+    // test for variable presence
+    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(-1.2));
+}
+
+#[test]
+fn test_clear() {
+    let mut engine = Engine::new();
+
+    let _ = engine.add_item_to_stack("23".into());
+    let _ = engine.add_item_to_stack("-1.2".into());
+    let _ = engine.add_item_to_stack("test".into());
+
+    assert!(!engine.stack.is_empty());
+    let _ = engine.clear();
+    assert!(engine.stack.is_empty());
+}
+
+#[test]
+fn test_undo() {
+    let mut engine = Engine::new();
+
+    // after each command, we must push a copy of the stack to the engine history
+    let commands = command_mappings::create_command_registry();
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "1");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "2");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "test");
+
+    // test undo of adding something to the stack
+    let _ = engine.undo();
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+
+    // test undo of operation
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "add");
+    assert_eq!(engine.stack, vec![Bucket::from(3),]);
+
+    let _ = engine.undo();
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+
+    // test undo of clear
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "clear");
+    assert_eq!(engine.stack, vec![]);
+
+    let _ = engine.undo();
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+
+    // test undo of variable assignment
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "a");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "store");
+
+    assert_eq!(engine.stack, vec![Bucket::from(1),]);
+    assert_eq!(*engine.variables.get("a").unwrap(), Bucket::from(2));
+
+    let _ = engine.undo();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(1), Bucket::from(2), Bucket::from("a"),]
+    );
+    assert_eq!(engine.variables.get("a"), None);
+
+    // test that we dont crash from extra undos
+    let _ = engine.undo();
+    let _ = engine.undo();
+    let _ = engine.undo();
+}
+
+#[test]
+fn test_redo() {
+    let mut engine = Engine::new();
+
+    // after each command, we must push a copy of the stack to the engine history
+    let commands = command_mappings::create_command_registry();
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "1");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "2");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "test");
+
+    // undo adding something to the stack
+    let _ = engine.undo();
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+    // redo the undo
+    let _ = engine.redo();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(1), Bucket::from(2), Bucket::from("test")]
+    );
+
+    // undo drop
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "drop");
+    assert_eq!(engine.stack, vec![Bucket::from(1), Bucket::from(2),]);
+
+    let _ = engine.undo();
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(1), Bucket::from(2), Bucket::from("test")]
     );
 
     // test redo drop
@@ -1029,6 +1997,290 @@ fn test_redo() {
     let _ = engine.redo();
 }
 
+#[test]
+fn test_history() {
+    let mut engine = Engine::new();
+
+    let commands = command_mappings::create_command_registry();
+    for data in ["1", "2", "add", "refresh"] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+
+    let result = squiid_engine::handle_data(&mut engine, &commands, "history");
+    assert_eq!(result.unwrap(), MessageAction::SendHistory);
+
+    // commands that do not change anything are not recorded
+    let summary = engine.history.summary(Some(100));
+    assert_eq!(summary.commands, vec!["1", "2", "add"]);
+    assert_eq!(summary.undone, 0);
+
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "undo");
+    assert_eq!(engine.history.summary(None).undone, 1);
+
+    // recording a new command replaces the undone entries
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "multiply");
+    let summary = engine.history.summary(None);
+    assert_eq!(summary.commands, vec!["1", "2", "multiply"]);
+    assert_eq!(summary.undone, 0);
+}
+
+#[test]
+fn test_history_depth() {
+    let mut engine = Engine::new();
+    let mut history = squiid_engine::history::History::default();
+
+    for item in 0..5 {
+        let _ = engine.add_item_to_stack(Bucket::from(item));
+        history.record(&item.to_string(), &engine, Some(3));
+    }
+    assert_eq!(history.len(), 3);
+
+    // only the recorded entries can be undone
+    let mut undone = 0;
+    while history.undo(&mut engine) {
+        undone += 1;
+    }
+    assert_eq!(undone, 3);
+    assert_eq!(engine.stack, stack_of(&[0, 1]));
+
+    // redo every entry
+    while history.redo(&mut engine) {}
+    assert_eq!(engine.stack, stack_of(&[0, 1, 2, 3, 4]));
+
+    // changes made outside of commands are kept out of the next entry
+    let _ = engine.add_item_to_stack(Bucket::from(5));
+    history.synchronize(&engine);
+    let _ = engine.drop();
+    assert!(history.record("drop", &engine, None));
+    assert!(history.undo(&mut engine));
+    assert_eq!(engine.stack, stack_of(&[0, 1, 2, 3, 4, 5]));
+}
+
+#[test]
+fn test_branch() {
+    let mut engine = Engine::new();
+
+    let commands = command_mappings::create_command_registry();
+    for data in ["1", "2", "add"] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+
+    // undoing and running another command starts a new branch
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "undo");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "multiply");
+    assert_eq!(engine.stack, stack_of(&[2]));
+
+    // redo moves to the newest branch
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "undo");
+    assert_eq!(engine.history.branches(), 2);
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "redo");
+    assert_eq!(engine.stack, stack_of(&[2]));
+
+    // branch moves to the older branch that was undone before
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "undo");
+    let result = squiid_engine::handle_data(&mut engine, &commands, "branch");
+    assert_eq!(result.unwrap(), MessageAction::SendStack);
+    assert_eq!(engine.stack, stack_of(&[3]));
+    assert_eq!(engine.history.summary(None).commands, vec!["1", "2", "add"]);
+
+    // branch wraps around to the newest branch
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "undo");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "branch");
+    assert_eq!(engine.stack, stack_of(&[2]));
+
+    // there is nothing to redo at the newest state
+    let result = squiid_engine::handle_data(&mut engine, &commands, "branch");
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_checkpoint() {
+    let mut engine = Engine::new();
+
+    let commands = command_mappings::create_command_registry();
+    for data in ["1", "2", "x", "store", "before", "checkpoint"] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+
+    // the name is removed from the stack
+    assert_eq!(engine.stack, stack_of(&[1]));
+    let checkpoint = engine.checkpoints.get("before").unwrap();
+    assert_eq!(checkpoint.stack, stack_of(&[1]));
+    assert_eq!(checkpoint.variables.get("x"), Some(&Bucket::from(2)));
+
+    let mut engine = Engine::new();
+    let result = squiid_engine::handle_data(&mut engine, &commands, "checkpoint");
+    assert!(matches!(result, Err(_)));
+
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "3");
+    let result = squiid_engine::handle_data(&mut engine, &commands, "checkpoint");
+    assert!(matches!(result, Err(_)));
+}
+
+#[test]
+fn test_restore() {
+    let mut engine = Engine::new();
+
+    let commands = command_mappings::create_command_registry();
+    for data in [
+        "1",
+        "2",
+        "x",
+        "store",
+        "update_previous_answer",
+        "a",
+        "checkpoint",
+    ] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+
+    // change everything that the checkpoint captured
+    for data in [
+        "5",
+        "x",
+        "store",
+        "7",
+        "update_previous_answer",
+        "a",
+        "restore",
+    ] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+    assert_eq!(engine.stack, stack_of(&[1]));
+    assert_eq!(engine.variables.get("x"), Some(&Bucket::from(2)));
+    assert_eq!(engine.previous_answer, Bucket::from(1));
+
+    // restoring can be undone
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "undo");
+    assert_eq!(
+        engine.stack,
+        vec![Bucket::from(1), Bucket::from(7), Bucket::from("a")]
+    );
+    assert_eq!(engine.previous_answer, Bucket::from(7));
+
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "redo");
+    assert_eq!(engine.previous_answer, Bucket::from(1));
+
+    let result = squiid_engine::handle_data(&mut engine, &commands, "missing");
+    assert!(result.is_ok());
+    let error = squiid_engine::handle_data(&mut engine, &commands, "restore").unwrap_err();
+    assert_eq!(error.code, ErrorKind::UndefinedCheckpoint);
+}
+
+/// Create an engine that saves sessions in a temporary directory used by a test
+fn engine_with_data_directory(name: &str) -> (Engine, PathBuf) {
+    let directory = std::env::temp_dir().join(format!("squiid_{}_{}", name, std::process::id()));
+    let engine = EngineBuilder::new()
+        .data_directory(&directory)
+        .build()
+        .unwrap();
+    (engine, directory)
+}
+
+#[test]
+fn test_save() {
+    let (mut engine, directory) = engine_with_data_directory("save");
+
+    let commands = command_mappings::create_command_registry();
+    for data in ["1", "#pi", "session.json", "save"] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+
+    // the file name is removed from the stack
+    assert_eq!(engine.stack.len(), 2);
+    let contents = fs::read_to_string(directory.join("session.json")).unwrap();
+    assert!(contents.contains(r#""type":"constant","name":"pi""#));
+    let _ = fs::remove_dir_all(&directory);
+
+    let mut engine = engine_with_stack(&[1]);
+    let result = squiid_engine::handle_data(&mut engine, &commands, "save");
+    assert_eq!(result.unwrap_err().code, ErrorKind::TypeMismatch);
+}
+
+#[test]
+fn test_load() {
+    let (mut engine, directory) = engine_with_data_directory("load");
+
+    let commands = command_mappings::create_command_registry();
+    for data in [
+        "5",
+        "x",
+        "store",
+        "#pi",
+        "hello",
+        "3",
+        "update_previous_answer",
+        "2",
+        "add",
+        "session.json",
+        "save",
+    ] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, data);
+    }
+
+    let mut loaded = EngineBuilder::new()
+        .data_directory(&directory)
+        .build()
+        .unwrap();
+    for data in ["9", "session.json", "load"] {
+        let _ = squiid_engine::handle_data(&mut loaded, &commands, data);
+    }
+    let _ = fs::remove_dir_all(&directory);
+
+    // types, variables and the previous answer are restored
+    assert_eq!(loaded.stack, engine.stack);
+    assert_eq!(
+        loaded.stack[0].bucket_type,
+        BucketTypes::Constant(ConstantTypes::PI)
+    );
+    assert_eq!(loaded.stack[1].bucket_type, BucketTypes::String);
+    assert_eq!(loaded.variables, engine.variables);
+    assert_eq!(loaded.previous_answer, Bucket::from(3));
+
+    // the undo history is restored, including removing the file name
+    let _ = squiid_engine::handle_data(&mut loaded, &commands, "undo");
+    assert_eq!(loaded.stack.last(), Some(&Bucket::from("session.json")));
+    for _ in 0..2 {
+        let _ = squiid_engine::handle_data(&mut loaded, &commands, "undo");
+    }
+    assert_eq!(loaded.stack.last(), Some(&Bucket::from(2)));
+    let _ = squiid_engine::handle_data(&mut loaded, &commands, "redo");
+    assert_eq!(loaded.stack.last(), Some(&Bucket::from(5)));
+
+    let result = squiid_engine::handle_data(&mut loaded, &commands, "missing.json");
+    assert!(result.is_ok());
+    let error = squiid_engine::handle_data(&mut loaded, &commands, "load").unwrap_err();
+    assert_eq!(error.code, ErrorKind::Io);
+}
+
+#[test]
+fn test_session_file_names() {
+    let (mut engine, directory) = engine_with_data_directory("file_names");
+
+    let commands = command_mappings::create_command_registry();
+    let absolute = directory
+        .join("absolute.json")
+        .to_string_lossy()
+        .into_owned();
+    for name in [
+        absolute.as_str(),
+        "../session.json",
+        "..",
+        "sessions/session.json",
+        "sessions\\session.json",
+    ] {
+        for command in ["save", "load"] {
+            let _ = engine.add_item_to_stack(Bucket::from(name));
+            let error = squiid_engine::handle_data(&mut engine, &commands, command).unwrap_err();
+            assert_eq!(error.code, ErrorKind::InvalidName, "{} {}", command, name);
+
+            // the name is kept on the stack
+            assert_eq!(engine.stack.pop(), Some(Bucket::from(name)));
+        }
+    }
+    assert!(!directory.exists());
+}
+
 #[test]
 fn test_list_commands() {
     let mut engine = Engine::new();
@@ -1064,18 +2316,91 @@ fn test_update_previous_answer() {
 fn test_commands() {
     let mut engine = Engine::new();
 
-    let commands = command_mappings::create_function_map();
+    let commands = command_mappings::create_command_registry();
 
     let result = squiid_engine::handle_data(&mut engine, &commands, "commands");
 
     assert_eq!(result.unwrap(), MessageAction::SendCommands);
 }
 
+#[test]
+fn test_commandinfo() {
+    let mut engine = Engine::new();
+
+    let commands = command_mappings::create_command_registry();
+
+    let result = squiid_engine::handle_data(&mut engine, &commands, "commandinfo");
+
+    assert_eq!(result.unwrap(), MessageAction::SendCommandInfo);
+    // querying metadata does not affect the undo history
+    assert!(engine.history.is_empty());
+
+    let info = commands.info();
+    let swap = info.iter().find(|info| info.name == "swap").unwrap();
+    assert_eq!(swap.arity(), 2);
+    assert_eq!(swap.category, CommandCategory::Stack);
+    assert!(!swap.algebraic);
+    assert!(
+        info.iter()
+            .find(|info| info.name == "sin")
+            .unwrap()
+            .algebraic
+    );
+}
+
+#[test]
+fn test_command_aliases() {
+    let mut engine = Engine::new();
+
+    let mut commands = command_mappings::create_command_registry();
+    commands.register(
+        CommandInfo {
+            name: String::from("double"),
+            operand_types: vec![OperandType::Number],
+            category: CommandCategory::Arithmetic,
+            description: String::from("Double a number"),
+            aliases: vec![String::from("twice")],
+            algebraic: true,
+        },
+        Box::new(|engine: &mut Engine| {
+            let _ = engine.add_item_to_stack("2".into());
+            engine.multiply()
+        }),
+    );
+
+    let _ = engine.add_item_to_stack("3".into());
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "twice");
+    assert_eq!(engine.stack, vec![Bucket::from(6)]);
+
+    assert!(commands.contains("twice"));
+    assert!(commands.names_and_aliases().contains(&"twice".to_string()));
+    assert!(!commands.names().contains(&"twice".to_string()));
+}
+
+#[test]
+fn test_comparison_command_names() {
+    let mut engine = Engine::new();
+
+    let commands = command_mappings::create_command_registry();
+
+    // 1 >= 2 and 1 <= 2 when run by name
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "1");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "2");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "geq");
+    assert_eq!(engine.stack, vec![Bucket::from(0)]);
+
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "clear");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "1");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "2");
+    let _ = squiid_engine::handle_data(&mut engine, &commands, "leq");
+    assert_eq!(engine.stack, vec![Bucket::from(1)]);
+}
+
 #[test]
 fn test_refresh() {
     let mut engine = Engine::new();
 
-    let commands = command_mappings::create_function_map();
+    let commands = command_mappings::create_command_registry();
 
     let result = squiid_engine::handle_data(&mut engine, &commands, "refresh");
 
@@ -1086,7 +2411,7 @@ fn test_refresh() {
 fn test_quit() {
     let mut engine = Engine::new();
 
-    let commands = command_mappings::create_function_map();
+    let commands = command_mappings::create_command_registry();
 
     let result = squiid_engine::handle_data(&mut engine, &commands, "quit");
 
@@ -1128,8 +2453,8 @@ fn test_all_commands_covered() {
     d.push("tests/engine_tests.rs");
     let data = fs::read_to_string(d).expect("Unable to read test file");
 
-    let commands = command_mappings::create_function_map();
-    for command in commands.keys() {
+    let commands = command_mappings::create_command_registry();
+    for command in commands.names() {
         assert!(
             data.contains(&format!("fn test_{}", command)),
             "command {} is missing a test",
@@ -1137,3 +2462,39 @@ fn test_all_commands_covered() {
         );
     }
 }
+
+#[test]
+fn test_error_codes() {
+    let commands = command_mappings::create_command_registry();
+
+    // dividing by zero reports the divisor
+    let mut engine = engine_with_stack(&[1, 0]);
+    let error = squiid_engine::handle_data(&mut engine, &commands, "divide").unwrap_err();
+    assert_eq!(error.code, ErrorKind::DivideByZero);
+    assert_eq!(error.command, Some("divide".to_string()));
+    assert_eq!(error.operands, vec![1]);
+
+    // type errors report the offending operand
+    let mut engine = Engine::new();
+    let _ = engine.add_item_to_stack("a".into());
+    let _ = engine.add_item_to_stack("1".into());
+    let error = squiid_engine::handle_data(&mut engine, &commands, "add").unwrap_err();
+    assert_eq!(error.code, ErrorKind::TypeMismatch);
+    assert_eq!(error.operands, vec![2]);
+
+    // underflow reports every required position
+    let mut engine = engine_with_stack(&[1]);
+    let error = squiid_engine::handle_data(&mut engine, &commands, "rot").unwrap_err();
+    assert_eq!(error.code, ErrorKind::StackUnderflow);
+    assert_eq!(error.operands, vec![1, 2, 3]);
+
+    let mut engine = Engine::new();
+    let error = squiid_engine::handle_data(&mut engine, &commands, "$undefined").unwrap_err();
+    assert_eq!(error.code, ErrorKind::UndefinedVariable);
+    assert_eq!(error.command, None);
+
+    let mut engine = engine_with_stack(&[1, 2]);
+    let error = squiid_engine::handle_data(&mut engine, &commands, "store").unwrap_err();
+    assert_eq!(error.code, ErrorKind::InvalidName);
+    assert_eq!(error.to_string(), "Cannot store in non-variable object `2`");
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use squiid_engine::{
+    bucket::Bucket,
+    engine::EngineBuilder,
+    protocol::{
+        client_request::{
+            ClientRequestMessage, ConfigurationActionType, ConfigurationPayload, RequestPayload,
+            RequestType,
+        },
+        event::{Event, EventMessage},
+        server_response::ResponsePayload,
+    },
+    server::Server,
+};
+
+/// Construct an input request
+fn input(data: &str) -> ClientRequestMessage {
+    ClientRequestMessage::new(RequestType::Input, RequestPayload::Input(data.into()))
+}
+
+#[test]
+fn test_stack_events() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let events = server.subscribe();
+
+    let _ = server.handle_request(&input("1"));
+    let event = events.try_recv().unwrap();
+    assert_eq!(
+        event,
+        EventMessage::new(None, Event::Stack(vec![Bucket::from(1)]))
+    );
+    assert_eq!(event.topic(), "default/stack");
+
+    // requests that do not change anything are not published
+    let _ = server.handle_request(&input("refresh"));
+    let _ = server.handle_request(&input("drop"));
+    let _ = server.handle_request(&input("drop"));
+    assert_eq!(events.try_recv().unwrap().event, Event::Stack(vec![]));
+    assert!(events.try_recv().is_err());
+
+    // every subscriber receives the events
+    let other_events = server.subscribe();
+    let _ = server.handle_request(&input("2"));
+    assert!(events.try_recv().is_ok());
+    assert!(other_events.try_recv().is_ok());
+
+    // events of sessions are published on their own topic
+    let response = server
+        .handle_request(&ClientRequestMessage::new(
+            RequestType::OpenSession,
+            RequestPayload::Empty(None),
+        ))
+        .unwrap();
+    let id = match response.payload {
+        ResponsePayload::Session(id) => id,
+        _ => panic!("open_session did not respond with a session"),
+    };
+    let _ = server.handle_request(&input("3").with_session(id.clone()));
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.session.as_deref(), Some(id.as_str()));
+    assert_eq!(event.topic(), format!("{}/stack", id));
+}
+
+#[test]
+fn test_variable_and_configuration_events() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let events = server.subscribe();
+
+    for data in ["5", "x", "store"] {
+        let _ = server.handle_request(&input(data));
+    }
+    let events: Vec<Event> = events.try_iter().map(|event| event.event).collect();
+    assert_eq!(
+        events.last(),
+        Some(&Event::Variables(HashMap::from([(
+            "x".to_string(),
+            Some(Bucket::from(5))
+        )])))
+    );
+
+    let events = server.subscribe();
+    let payload: ConfigurationPayload = serde_json::from_str(
+        r#"{"action_type": "set_key", "section": "system", "key": "request_timeout", "value": 10}"#,
+    )
+    .unwrap();
+    let _ = server.handle_request(&ClientRequestMessage::new(
+        RequestType::Configuration,
+        RequestPayload::Configuration(payload.clone()),
+    ));
+    assert_eq!(
+        events.try_recv().unwrap().event,
+        Event::Configuration(payload)
+    );
+
+    // reading the config is not a change
+    let _ = server.handle_request(&ClientRequestMessage::new(
+        RequestType::Configuration,
+        RequestPayload::Configuration(ConfigurationPayload::new(
+            ConfigurationActionType::ListSections,
+            None,
+            None,
+            None,
+        )),
+    ));
+    assert!(events.try_recv().is_err());
+}
+
+#[test]
+fn test_history_events() {
+    // changes made before the server started are not published
+    let mut engine = EngineBuilder::new().build().unwrap();
+    let _ = engine.add_item_to_stack(Bucket::from(1));
+    let server = Server::new(engine);
+    let events = server.subscribe();
+    let _ = server.handle_request(&input("refresh"));
+    assert!(events.try_recv().is_err());
+
+    // undoing a command publishes what it changed back
+    for data in ["5", "x", "store", "undo"] {
+        let _ = server.handle_request(&input(data));
+    }
+    let events: Vec<Event> = events.try_iter().map(|event| event.event).collect();
+    assert_eq!(
+        events[events.len() - 2..],
+        [
+            Event::Stack(vec![Bucket::from(1), Bucket::from(5), Bucket::from("x")]),
+            Event::Variables(HashMap::from([("x".to_string(), None)])),
+        ]
+    );
+
+    // a request that changes the stack back to how it was publishes nothing
+    let events = server.subscribe();
+    let _ = server.handle_request(&ClientRequestMessage::new(
+        RequestType::Batch,
+        RequestPayload::Batch(vec!["2".to_string(), "drop".to_string()]),
+    ));
+    assert!(events.try_recv().is_err());
+}
+
+#[test]
+fn test_event_message() {
+    let event = EventMessage::new(
+        Some("abc".to_string()),
+        Event::Variables(HashMap::from([("x".to_string(), None)])),
+    );
+    assert_eq!(event.topic(), "abc/variables");
+
+    let message = event.to_message().unwrap();
+    assert_eq!(
+        message,
+        r#"abc/variables {"session":"abc","event_type":"variables","payload":{"x":null}}"#
+    );
+    let (_, json) = message.split_once(' ').unwrap();
+    assert_eq!(serde_json::from_str::<EventMessage>(json).unwrap(), event);
+}
@@ -0,0 +1,353 @@
+#![cfg(feature = "http")]
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use squiid_engine::{
+    engine::EngineBuilder,
+    ipc::{http::Http, IPCBackend},
+    server::{Server, ShutdownHandle},
+    start_server_with,
+};
+use tungstenite::Message;
+
+/// Send a raw HTTP request and return the whole response
+fn http(address: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// Send a JSON request with HTTP POST
+fn post(address: &str, body: &str) -> String {
+    http(
+        address,
+        &format!(
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            address,
+            body.len(),
+            body
+        ),
+    )
+}
+
+/// Find a free port on the loopback interface
+fn free_address() -> String {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_http() {
+    let address = free_address();
+    let backend = Http::new();
+    backend
+        .bind_and_listen(&format!("http://{}", address))
+        .unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        let response = post(&address, r#"{"request_type": "input", "payload": "1"}"#);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(r#"{"response_type":"stack","payload":{"stack":["1"]}}"#));
+        assert!(post(&address, "not json").contains("invalid_request"));
+
+        // the browser frontend is served from the same address
+        let page = http(
+            &address,
+            &format!("GET /?token=secret HTTP/1.1\r\nHost: {}\r\n\r\n", address),
+        );
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(page.contains("<title>Squiid</title>"));
+        assert!(http(
+            &address,
+            &format!("GET /missing HTTP/1.1\r\nHost: {}\r\n\r\n", address)
+        )
+        .starts_with("HTTP/1.1 404"));
+        assert!(http(
+            &address,
+            &format!("DELETE / HTTP/1.1\r\nHost: {}\r\n\r\n", address)
+        )
+        .starts_with("HTTP/1.1 405"));
+
+        // websockets receive responses and the events of other clients
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}/ws", address)).unwrap();
+        socket
+            .send(Message::Text(
+                r#"{"request_type": "input", "payload": "refresh"}"#.to_string(),
+            ))
+            .unwrap();
+        assert_eq!(
+            socket.read().unwrap(),
+            Message::Text(r#"{"response_type":"stack","payload":{"stack":["1"]}}"#.to_string())
+        );
+        let _ = post(&address, r#"{"request_type": "input", "payload": "2"}"#);
+        let event = socket.read().unwrap().into_text().unwrap();
+        assert!(event.contains(r#""event_type":"stack""#));
+
+        assert!(
+            post(&address, r#"{"request_type": "input", "payload": "quit"}"#).contains("quitsig")
+        );
+    });
+}
+
+#[test]
+fn test_http_host_and_origin() {
+    let address = free_address();
+    let backend = Http::new();
+    backend
+        .bind_and_listen(&format!("http://{}", address))
+        .unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let body = r#"{"request_type": "input", "payload": "1"}"#;
+    let request = |host: &str, origin: Option<&str>| {
+        let origin = origin
+            .map(|origin| format!("Origin: {}\r\n", origin))
+            .unwrap_or_default();
+        http(
+            &address,
+            &format!(
+                "POST / HTTP/1.1\r\nHost: {}\r\n{}Content-Length: {}\r\n\r\n{}",
+                host,
+                origin,
+                body.len(),
+                body
+            ),
+        )
+    };
+
+    let responses = thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        let port = address.rsplit(':').next().unwrap();
+        let own_origin = format!("http://{}", address);
+        let localhost = format!("localhost:{}", port);
+        let responses = vec![
+            request(&address, Some(&own_origin)),
+            request(&localhost, Some(&format!("http://{}", localhost))),
+            // a host name that was rebound to the loopback address
+            request(&format!("attacker.example:{}", port), None),
+            // a request from another web page
+            request(&address, Some("http://attacker.example")),
+            // a WebSocket opened by another web page
+            http(
+                &address,
+                &format!(
+                    "GET /ws HTTP/1.1\r\nHost: {}\r\nOrigin: http://attacker.example\r\n\
+                     Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+                     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+                    address
+                ),
+            ),
+        ];
+
+        let _ = post(&address, r#"{"request_type": "input", "payload": "quit"}"#);
+        responses
+    });
+
+    assert!(responses[0].starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(responses[1].starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(responses[2].starts_with("HTTP/1.1 421"));
+    assert!(responses[3].starts_with("HTTP/1.1 403"));
+    assert!(responses[4].starts_with("HTTP/1.1 403"));
+}
+
+#[test]
+fn test_http_generated_token() {
+    let address = free_address();
+    let url = format!("http://{}", address);
+    let shutdown = ShutdownHandle::new();
+
+    // without a token, the server generates one that requests must include
+    let (response, result) = thread::scope(|scope| {
+        let serving = scope.spawn(|| {
+            start_server_with::<Http>(
+                Some(&url),
+                None,
+                EngineBuilder::new().build().unwrap(),
+                shutdown.clone(),
+            )
+        });
+
+        let mut response = None;
+        for _ in 0..100 {
+            if TcpStream::connect(&address).is_ok() {
+                response = Some(post(
+                    &address,
+                    r#"{"request_type": "input", "payload": "1"}"#,
+                ));
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        shutdown.shutdown();
+        (response, serving.join().unwrap())
+    });
+    assert!(result.is_ok());
+    assert!(response.unwrap().contains("unauthorized"));
+}
+
+#[test]
+fn test_websocket_events() {
+    let address = free_address();
+    let backend = Http::new();
+    backend
+        .bind_and_listen(&format!("http://{}", address))
+        .unwrap();
+
+    let server =
+        Server::new(EngineBuilder::new().build().unwrap()).with_token(Some("secret".to_string()));
+    let messages = thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        let (mut socket, _) = tungstenite::connect(format!("ws://{}/ws", address)).unwrap();
+        let mut send = |request: &str| {
+            socket.send(Message::Text(request.to_string())).unwrap();
+            socket.read().unwrap().into_text().unwrap()
+        };
+        let mut messages = Vec::new();
+
+        // a connection without the token receives no events
+        let _ = post(
+            &address,
+            r#"{"request_type": "input", "payload": "1", "token": "secret"}"#,
+        );
+        thread::sleep(Duration::from_millis(100));
+        messages.push(send(r#"{"request_type": "input", "payload": "refresh"}"#));
+
+        // once authenticated, it only receives the events of its own session
+        let session: serde_json::Value = serde_json::from_str(
+            post(
+                &address,
+                r#"{"request_type": "open_session", "token": "secret"}"#,
+            )
+            .split("\r\n\r\n")
+            .nth(1)
+            .unwrap(),
+        )
+        .unwrap();
+        let session = session["payload"]["session"].as_str().unwrap().to_string();
+        messages.push(send(&format!(
+            r#"{{"request_type": "input", "payload": "refresh", "token": "secret", "session": "{}"}}"#,
+            session
+        )));
+        let _ = post(
+            &address,
+            r#"{"request_type": "input", "payload": "2", "token": "secret"}"#,
+        );
+        thread::sleep(Duration::from_millis(100));
+        let _ = post(
+            &address,
+            &format!(
+                r#"{{"request_type": "input", "payload": "3", "token": "secret", "session": "{}"}}"#,
+                session
+            ),
+        );
+        messages.push(socket.read().unwrap().into_text().unwrap());
+        messages.push(session);
+
+        let _ = post(
+            &address,
+            r#"{"request_type": "input", "payload": "quit", "token": "secret"}"#,
+        );
+        messages
+    });
+
+    assert!(messages[0].contains("unauthorized"));
+    assert_eq!(
+        messages[1],
+        r#"{"response_type":"stack","payload":{"stack":[]}}"#
+    );
+    assert!(messages[2].contains(r#""event_type":"stack""#));
+    assert!(messages[2].contains(&format!(r#""session":"{}""#, messages[3])));
+    assert!(messages[2].contains(r#""3""#));
+}
+
+#[test]
+fn test_http_limits() {
+    let address = free_address();
+    let backend = Http::new();
+    backend
+        .bind_and_listen(&format!("http://{}", address))
+        .unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let (header, busy, messages) = thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        // headers are limited in size
+        let request_line = "GET / HTTP/1.1\r\nCookie: ";
+        let header = http(
+            &address,
+            &format!(
+                "{}{}",
+                request_line,
+                "a".repeat((16 << 10) - request_line.len())
+            ),
+        );
+
+        // connections beyond the limit are turned away while the others are open
+        let idle: Vec<TcpStream> = (0..64)
+            .map(|_| TcpStream::connect(&address).unwrap())
+            .collect();
+        let busy = http(&address, "");
+        drop(idle);
+
+        // pings are answered on the same connection as responses
+        let (mut socket, _) = (0..100)
+            .find_map(|_| {
+                tungstenite::connect(format!("ws://{}/ws", address))
+                    .map_err(|_| thread::sleep(Duration::from_millis(20)))
+                    .ok()
+            })
+            .unwrap();
+        let mut messages = Vec::new();
+        for _ in 0..5 {
+            socket.send(Message::Ping(b"ping".to_vec())).unwrap();
+            socket
+                .send(Message::Text(
+                    r#"{"request_type": "input", "payload": "refresh"}"#.to_string(),
+                ))
+                .unwrap();
+        }
+        while messages.len() < 10 {
+            match socket.read() {
+                Ok(message) => messages.push(message),
+                Err(_) => break,
+            }
+        }
+
+        let _ = post(&address, r#"{"request_type": "input", "payload": "quit"}"#);
+        (header, busy, messages)
+    });
+
+    assert!(header.starts_with("HTTP/1.1 400"));
+    assert!(busy.starts_with("HTTP/1.1 503"));
+    assert_eq!(
+        messages
+            .iter()
+            .filter(|message| **message == Message::Pong(b"ping".to_vec()))
+            .count(),
+        5
+    );
+    assert_eq!(
+        messages
+            .iter()
+            .filter(|message| message.to_text().is_ok_and(|text| text.contains("stack")))
+            .count(),
+        5
+    );
+}
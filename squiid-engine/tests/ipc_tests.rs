@@ -0,0 +1,322 @@
+#![cfg(feature = "stdio")]
+
+use std::{
+    io::Cursor,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use squiid_engine::{
+    ipc::{json_lines::RequestQueue, IPCWorker},
+    protocol::{
+        client_request::RequestType,
+        server_response::{ResponsePayload, ResponseType, ServerResponseMessage},
+    },
+};
+
+#[test]
+fn test_json_lines() {
+    let queue = RequestQueue::new();
+    let output = Arc::new(Mutex::new(Vec::new()));
+
+    // reading waits until every request of the stream has been answered
+    let reader = {
+        let (queue, output) = (queue.clone(), output.clone());
+        thread::spawn(move || {
+            queue.read(
+                Cursor::new(
+                    "{\"request_type\": \"input\", \"payload\": \"1\"}\n\n{\"request_type\"\n",
+                ),
+                output,
+            );
+            queue.end();
+        })
+    };
+
+    let worker = queue.worker();
+    let request = worker.recv_data().unwrap();
+    assert!(matches!(request.request_type, RequestType::Input));
+    assert!(worker
+        .send_data(ServerResponseMessage::new(
+            ResponseType::Cancel,
+            ResponsePayload::Cancel(None)
+        ))
+        .is_ok());
+
+    // invalid lines can still be answered, but nothing can be sent after the end
+    assert!(worker.recv_data().is_err());
+    assert!(worker
+        .send_data(ServerResponseMessage::new(
+            ResponseType::Cancel,
+            ResponsePayload::Cancel(None)
+        ))
+        .is_ok());
+    assert!(worker.recv_data().is_err());
+    assert!(worker
+        .send_data(ServerResponseMessage::new(
+            ResponseType::Cancel,
+            ResponsePayload::Cancel(None)
+        ))
+        .is_err());
+
+    // every worker stops at the end
+    assert!(queue.worker().recv_data().is_err());
+    reader.join().unwrap();
+
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(output.lines().count(), 2);
+    assert!(output.ends_with("}\n"));
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_unix_socket() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        thread,
+        time::Duration,
+    };
+
+    use squiid_engine::{
+        engine::EngineBuilder,
+        ipc::{unix_socket::UnixSocket, IPCBackend},
+        server::Server,
+    };
+
+    let path = std::env::temp_dir().join(format!("squiid-test-{}.sock", std::process::id()));
+    let publish_path = path.with_extension("pub");
+    let backend = UnixSocket::new();
+    backend.bind_and_listen(path.to_str().unwrap()).unwrap();
+    backend
+        .bind_publisher(&format!("unix://{}", publish_path.display()))
+        .unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        let subscriber = UnixStream::connect(&publish_path).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        let mut responses = BufReader::new(client.try_clone().unwrap()).lines();
+        let mut send = |line: &str| {
+            writeln!(&client, "{}", line).unwrap();
+            responses.next().unwrap().unwrap()
+        };
+
+        assert_eq!(
+            send(r#"{"request_type": "input", "payload": "1"}"#),
+            r#"{"response_type":"stack","payload":{"stack":["1"]}}"#
+        );
+        assert!(send("not json").contains("invalid_request"));
+
+        // the subscriber may be accepted after the first changes were published
+        subscriber
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        let mut events = BufReader::new(subscriber).lines();
+        let event = (0..100)
+            .find_map(|_| {
+                send(r#"{"request_type": "input", "payload": "2"}"#);
+                events.next().and_then(Result::ok)
+            })
+            .unwrap();
+        assert!(event.starts_with("default/stack {"));
+
+        assert!(send(r#"{"request_type": "input", "payload": "quit"}"#).contains("quitsig"));
+    });
+
+    // the socket files are removed when the server quits
+    assert!(!path.exists());
+    assert!(!publish_path.exists());
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_unix_socket_order() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    use squiid_engine::{
+        engine::EngineBuilder,
+        ipc::{unix_socket::UnixSocket, IPCBackend},
+        server::Server,
+    };
+
+    let path = std::env::temp_dir().join(format!("squiid-order-{}.sock", std::process::id()));
+    let backend = UnixSocket::new();
+    backend.bind_and_listen(path.to_str().unwrap()).unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let responses = thread::scope(|scope| {
+        let serving = scope.spawn(|| server.serve(&backend, 4));
+
+        // pipelined requests are evaluated in the order they were sent
+        let client = UnixStream::connect(&path).unwrap();
+        let mut requests = String::new();
+        for item in 1..=20 {
+            requests += &format!(
+                "{{\"request_type\": \"input\", \"payload\": \"{}\"}}\n",
+                item
+            );
+        }
+
+        // except for cancel, which stops the running request of the same stream
+        requests += r#"{"request_type": "batch", "payload": ["{", "$k", "}", "k", "1", "999999", "sum"], "timeout": 10000}"#;
+        requests += "\n";
+        let _ = (&client).write_all(requests.as_bytes());
+        thread::sleep(std::time::Duration::from_millis(200));
+        let _ = writeln!(&client, r#"{{"request_type": "cancel"}}"#);
+
+        let responses: Vec<String> = BufReader::new(client.try_clone().unwrap())
+            .lines()
+            .take(22)
+            .map_while(Result::ok)
+            .collect();
+        let _ = writeln!(&client, r#"{{"request_type": "input", "payload": "quit"}}"#);
+        let _ = serving.join();
+        responses
+    });
+
+    assert_eq!(responses.len(), 22);
+    let stacks: Vec<&String> = responses
+        .iter()
+        .filter(|response| response.contains(r#""response_type":"stack""#))
+        .collect();
+    for (index, response) in stacks.iter().enumerate() {
+        assert!(
+            response.ends_with(&format!(r#""{}"]}}}}"#, index + 1)),
+            "{}",
+            response
+        );
+    }
+    assert_eq!(stacks.len(), 20);
+    assert!(responses
+        .iter()
+        .any(|r| r.contains(r#""response_type":"cancel""#)));
+    assert!(responses[21].contains("cancelled"), "{}", responses[21]);
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_unix_socket_slow_subscriber() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        time::Duration,
+    };
+
+    use squiid_engine::{
+        engine::EngineBuilder,
+        ipc::{unix_socket::UnixSocket, IPCBackend},
+        server::Server,
+    };
+
+    let path = std::env::temp_dir().join(format!("squiid-slow-{}.sock", std::process::id()));
+    let publish_path = path.with_extension("pub");
+    let backend = UnixSocket::new();
+    backend.bind_and_listen(path.to_str().unwrap()).unwrap();
+    backend
+        .bind_publisher(&format!("unix://{}", publish_path.display()))
+        .unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let event = thread::scope(|scope| {
+        scope.spawn(|| server.serve(&backend, 2));
+
+        // a subscriber that never reads its events
+        let _slow = UnixStream::connect(&publish_path).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        let mut responses = BufReader::new(client.try_clone().unwrap()).lines();
+        let mut send = |line: &str| {
+            writeln!(&client, "{}", line).unwrap();
+            responses.next().unwrap().unwrap()
+        };
+
+        // publish more than the socket buffer of the slow subscriber can hold
+        let items = vec![r#""12345678901234567890""#; 100].join(", ");
+        for _ in 0..30 {
+            send(&format!(
+                r#"{{"request_type": "batch", "payload": [{}]}}"#,
+                items
+            ));
+        }
+
+        // other subscribers still receive events
+        let subscriber = UnixStream::connect(&publish_path).unwrap();
+        subscriber
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        let mut events = BufReader::new(subscriber).lines();
+        let event = (0..100).find_map(|_| {
+            send(r#"{"request_type": "input", "payload": "drop"}"#);
+            events.next().and_then(Result::ok)
+        });
+
+        send(r#"{"request_type": "input", "payload": "quit"}"#);
+        event
+    });
+    assert!(event.unwrap().starts_with("default/stack {"));
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_unix_socket_publisher_with_token() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        time::Duration,
+    };
+
+    use squiid_engine::{
+        config_handler::Config, engine::EngineBuilder, ipc::unix_socket::UnixSocket,
+        server::ShutdownHandle, start_server_with,
+    };
+
+    let path = std::env::temp_dir().join(format!("squiid-token-{}.sock", std::process::id()));
+    let publish_path = path.with_extension("pub");
+    let address = format!("unix://{}", path.display());
+
+    // subscribers cannot authenticate, so the publisher is not bound
+    let mut config = Config::default();
+    let _ = config.set_key(
+        "system",
+        "publish_address",
+        toml::Value::String(format!("unix://{}", publish_path.display())),
+    );
+    let engine = EngineBuilder::new().config(config).build().unwrap();
+
+    let shutdown = ShutdownHandle::new();
+    let (response, published) = thread::scope(|scope| {
+        let serving = scope.spawn(|| {
+            start_server_with::<UnixSocket>(
+                Some(&address),
+                Some("secret"),
+                engine,
+                shutdown.clone(),
+            )
+        });
+
+        let client = (0..100).find_map(|_| {
+            UnixStream::connect(&path)
+                .map_err(|_| thread::sleep(Duration::from_millis(20)))
+                .ok()
+        });
+        let response = client.and_then(|client| {
+            let _ = writeln!(
+                &client,
+                r#"{{"request_type": "input", "payload": "1", "token": "secret"}}"#
+            );
+            BufReader::new(client).lines().next()
+        });
+        let published = publish_path.exists();
+
+        shutdown.shutdown();
+        let _ = serving.join();
+        (response, published)
+    });
+    assert!(response.unwrap().unwrap().contains(r#""stack":["1"]"#));
+    assert!(!published);
+}
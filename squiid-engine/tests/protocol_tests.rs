@@ -0,0 +1,144 @@
+use squiid_engine::{
+    bucket::{Bucket, ConstantTypes},
+    engine::EngineBuilder,
+    error::ErrorKind,
+    protocol::{
+        client_request::{ClientRequestMessage, RequestPayload, RequestType},
+        server_response::{Hello, ResponsePayload, ResponseType},
+        PROTOCOL_VERSION,
+    },
+    server::Server,
+};
+
+#[test]
+fn test_hello() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "hello"}"#).unwrap();
+    let response = server.handle_request(&request).unwrap();
+    assert!(matches!(response.response_type, ResponseType::Hello));
+
+    let hello = match response.payload {
+        ResponsePayload::Hello(hello) => hello,
+        _ => panic!("hello did not respond with the server capabilities"),
+    };
+    assert_eq!(hello, Hello::current());
+    assert_eq!(hello.protocol_version, PROTOCOL_VERSION);
+    assert_eq!(hello.engine_version, env!("CARGO_PKG_VERSION"));
+    assert!(hello.features.contains(&"ipc".to_string()));
+    assert_eq!(
+        hello.features.contains(&"stdio".to_string()),
+        cfg!(feature = "stdio")
+    );
+    assert_eq!(
+        hello.features.contains(&"http".to_string()),
+        cfg!(feature = "http")
+    );
+    assert!(hello.request_types.contains(&"input".to_string()));
+    assert!(hello.request_types.contains(&"hello".to_string()));
+
+    // clients that skip the handshake keep working
+    let response = server
+        .handle_request(&ClientRequestMessage::new(
+            RequestType::Input,
+            RequestPayload::Input("1".into()),
+        ))
+        .unwrap();
+    assert!(matches!(response.response_type, ResponseType::Stack));
+}
+
+#[test]
+fn test_request_type_names() {
+    // every advertised request type can be sent to the server
+    for name in Hello::current().request_types {
+        let request_type: RequestType = serde_json::from_value(name.clone().into()).unwrap();
+        assert_eq!(request_type.name(), name);
+    }
+    assert_eq!(RequestType::CloseSession.name(), "close_session");
+}
+
+#[test]
+fn test_typed_buckets() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    for input in ["#pi", "Undefined"] {
+        let _ = server.handle_request(&ClientRequestMessage::new(
+            RequestType::Input,
+            RequestPayload::Input(input.into()),
+        ));
+    }
+    let _ = server.handle_request(&ClientRequestMessage::new(
+        RequestType::Input,
+        RequestPayload::Input("1".into()),
+    ));
+    let refresh =
+        ClientRequestMessage::new(RequestType::Input, RequestPayload::Input("refresh".into()));
+
+    // buckets are sent as strings unless typed buckets are requested
+    let plain = serde_json::to_string(&server.handle_request(&refresh).unwrap()).unwrap();
+    assert!(plain.contains(r#"["3.141592653589793","Undefined","1"]"#));
+
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "input", "payload": "refresh", "typed": true}"#)
+            .unwrap();
+    assert!(request.typed);
+    let response = server.handle_request(&request).unwrap();
+    let typed = serde_json::to_string(&response).unwrap();
+    assert!(typed.contains(r#"{"type":"constant","name":"pi","value":"3.141592653589793"}"#));
+    assert!(typed.contains(r#"{"type":"string","value":"Undefined"}"#));
+    assert!(typed.contains(r#"{"type":"float","value":"1"}"#));
+
+    // typed responses round trip
+    let response: squiid_engine::protocol::server_response::ServerResponseMessage =
+        serde_json::from_str(&typed).unwrap();
+    match response.payload {
+        ResponsePayload::Stack(stack) => assert_eq!(
+            stack,
+            vec![
+                Bucket::from_constant(ConstantTypes::PI),
+                Bucket::from("Undefined"),
+                Bucket::from(1)
+            ]
+        ),
+        _ => panic!("refresh did not respond with the stack"),
+    }
+}
+
+#[test]
+fn test_token() {
+    let server =
+        Server::new(EngineBuilder::new().build().unwrap()).with_token(Some("secret".into()));
+    let input = ClientRequestMessage::new(RequestType::Input, RequestPayload::Input("1".into()));
+
+    // requests without the token are rejected before they are evaluated
+    for request in [
+        input.clone(),
+        input.clone().with_token("secreT"),
+        input.clone().with_token("secret2"),
+    ] {
+        match server.handle_request(&request).unwrap().payload {
+            ResponsePayload::Error(error) => assert_eq!(error.code, ErrorKind::Unauthorized),
+            _ => panic!("a request without the token was accepted"),
+        }
+    }
+
+    let request: ClientRequestMessage = serde_json::from_str(
+        r#"{"request_type": "input", "payload": "refresh", "token": "secret"}"#,
+    )
+    .unwrap();
+    assert_eq!(request.token.as_deref(), Some("secret"));
+    match server.handle_request(&request).unwrap().payload {
+        ResponsePayload::Stack(stack) => assert!(stack.is_empty()),
+        _ => panic!("a request with the token was rejected"),
+    }
+
+    // servers without a token accept every request
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    assert!(matches!(
+        server
+            .handle_request(&input.with_token("secret"))
+            .unwrap()
+            .response_type,
+        ResponseType::Stack
+    ));
+}
@@ -0,0 +1,318 @@
+use std::{thread, time::Duration};
+
+use squiid_engine::{
+    bucket::Bucket,
+    command_mappings,
+    config_handler::Config,
+    engine::EngineBuilder,
+    error::ErrorKind,
+    interrupt::Interrupt,
+    protocol::{
+        client_request::{ClientRequestMessage, RequestPayload, RequestType},
+        server_response::{ResponsePayload, ResponseType},
+    },
+    server::Server,
+    session_manager::SessionManager,
+};
+
+#[test]
+fn test_sessions_are_isolated() {
+    let mut sessions = SessionManager::new(EngineBuilder::new().build().unwrap());
+    let commands = command_mappings::create_command_registry();
+
+    let first = sessions.open().unwrap();
+    let second = sessions.open().unwrap();
+    assert_ne!(first, second);
+    assert_eq!(sessions.len(), 2);
+
+    for (id, input) in [
+        (Some(first.as_str()), "1"),
+        (Some(&second), "2"),
+        (None, "3"),
+    ] {
+        let engine = sessions.get(id).unwrap();
+        let _ = squiid_engine::handle_data(&mut engine.lock().unwrap(), &commands, input);
+    }
+
+    let stack_of = |engine: std::sync::Arc<std::sync::Mutex<_>>| {
+        let engine: &squiid_engine::engine::Engine = &engine.lock().unwrap();
+        engine.stack.clone()
+    };
+    assert_eq!(
+        stack_of(sessions.get(Some(&first)).unwrap()),
+        vec![Bucket::from(1)]
+    );
+    assert_eq!(
+        stack_of(sessions.get(Some(&second)).unwrap()),
+        vec![Bucket::from(2)]
+    );
+    assert_eq!(stack_of(sessions.default_engine()), vec![Bucket::from(3)]);
+}
+
+#[test]
+fn test_close_session() {
+    let mut sessions = SessionManager::new(EngineBuilder::new().build().unwrap());
+
+    let id = sessions.open().unwrap();
+    assert!(sessions.close(&id).is_ok());
+    assert!(sessions.is_empty());
+
+    assert_eq!(
+        sessions.close(&id).unwrap_err().code,
+        ErrorKind::UndefinedSession
+    );
+    assert_eq!(
+        sessions.get(Some(&id)).err().unwrap().code,
+        ErrorKind::UndefinedSession
+    );
+}
+
+#[test]
+fn test_evict_idle() {
+    let mut sessions = SessionManager::new(EngineBuilder::new().build().unwrap());
+    assert_eq!(sessions.timeout(), Some(Duration::from_secs(3600)));
+
+    // recently used sessions are kept
+    let id = sessions.open().unwrap();
+    assert!(sessions.evict_idle().is_empty());
+    assert!(sessions.get(Some(&id)).is_ok());
+}
+
+#[test]
+fn test_max_sessions() {
+    let mut config = Config::default();
+    let _ = config.set_key("system", "max_sessions", toml::Value::Integer(2));
+    let _ = config.set_key("system", "session_timeout", toml::Value::Integer(1));
+    let mut sessions = SessionManager::new(EngineBuilder::new().config(config).build().unwrap());
+
+    let first = sessions.open().unwrap();
+    let _ = sessions.open().unwrap();
+    assert_eq!(sessions.open().unwrap_err().code, ErrorKind::SessionLimit);
+
+    // closing a session makes room for another one
+    assert!(sessions.close(&first).is_ok());
+    assert!(sessions.open().is_ok());
+
+    // idle sessions are evicted before opening a new one
+    thread::sleep(Duration::from_millis(1100));
+    assert!(sessions.open().is_ok());
+    assert_eq!(sessions.len(), 1);
+}
+
+#[test]
+fn test_evict_in_use() {
+    let mut config = Config::default();
+    let _ = config.set_key("system", "session_timeout", toml::Value::Integer(1));
+    let mut sessions = SessionManager::new(EngineBuilder::new().config(config).build().unwrap());
+
+    let idle = sessions.open().unwrap();
+    let busy = sessions.open().unwrap();
+    let engine = sessions.get(Some(&busy)).unwrap();
+    thread::sleep(Duration::from_millis(1100));
+
+    // sessions that are evaluating a request are not evicted
+    assert_eq!(sessions.evict_idle(), vec![idle]);
+    drop(engine);
+    assert!(sessions.evict_idle().is_empty());
+    assert!(sessions.get(Some(&busy)).is_ok());
+}
+
+#[test]
+fn test_request_session() {
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "input", "payload": "1", "session": "abc"}"#)
+            .unwrap();
+    assert_eq!(request.session.as_deref(), Some("abc"));
+
+    // requests without a session use the default session
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "input", "payload": "1"}"#).unwrap();
+    assert_eq!(request.session, None);
+
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "open_session"}"#).unwrap();
+    assert!(matches!(request.request_type, RequestType::OpenSession));
+    assert!(matches!(request.payload, RequestPayload::Empty(None)));
+
+    let serialized = serde_json::to_string(
+        &ClientRequestMessage::new(RequestType::Input, RequestPayload::Input("1".into()))
+            .with_session("abc"),
+    )
+    .unwrap();
+    assert!(serialized.contains(r#""session":"abc""#));
+}
+
+#[test]
+fn test_interrupt() {
+    let mut interrupt = Interrupt::default();
+    assert!(interrupt.check().is_ok());
+
+    // clones share the cancellation flag
+    interrupt.clone().cancel();
+    assert_eq!(interrupt.check().unwrap_err().code, ErrorKind::Cancelled);
+
+    // resetting clears the cancellation and starts the deadline
+    interrupt.reset(None);
+    assert!(interrupt.check().is_ok());
+    interrupt.reset(Some(Duration::ZERO));
+    assert_eq!(interrupt.check().unwrap_err().code, ErrorKind::TimedOut);
+}
+
+#[test]
+fn test_cancel_session() {
+    let mut sessions = SessionManager::new(EngineBuilder::new().build().unwrap());
+    let commands = command_mappings::create_command_registry();
+    let id = sessions.open().unwrap();
+
+    // cancelling stops the program that is running in the session
+    let engine = sessions.get(Some(&id)).unwrap();
+    let mut engine = engine.lock().unwrap();
+    for input in ["{", "$k", "}", "k", "1", "10"] {
+        let _ = squiid_engine::handle_data(&mut engine, &commands, input);
+    }
+    engine.interrupt.reset(None);
+    assert!(sessions.cancel(Some(&id)).is_ok());
+    assert_eq!(
+        squiid_engine::handle_data(&mut engine, &commands, "sum")
+            .unwrap_err()
+            .code,
+        ErrorKind::Cancelled
+    );
+
+    // stopping the server cancels every session
+    engine.interrupt.reset(None);
+    sessions.cancel_all();
+    assert_eq!(
+        engine.interrupt.check().unwrap_err().code,
+        ErrorKind::Cancelled
+    );
+    drop(engine);
+
+    assert_eq!(
+        sessions.cancel(Some("missing")).unwrap_err().code,
+        ErrorKind::UndefinedSession
+    );
+}
+
+#[test]
+fn test_server_requests() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+
+    let response = server
+        .handle_request(&ClientRequestMessage::new(
+            RequestType::OpenSession,
+            RequestPayload::Empty(None),
+        ))
+        .unwrap();
+    let id = match response.payload {
+        ResponsePayload::Session(id) => id,
+        _ => panic!("open_session did not respond with a session"),
+    };
+
+    // sessions can be used from several threads at once
+    thread::scope(|scope| {
+        for session in [Some(id.clone()), None] {
+            let server = &server;
+            scope.spawn(move || {
+                let mut request = ClientRequestMessage::new(
+                    RequestType::Input,
+                    RequestPayload::Input("5".into()),
+                );
+                request.session = session;
+                let response = server.handle_request(&request).unwrap();
+                assert!(matches!(response.response_type, ResponseType::Stack));
+            });
+        }
+    });
+
+    let response = server
+        .handle_request(
+            &ClientRequestMessage::new(RequestType::Cancel, RequestPayload::Empty(None))
+                .with_session(id.clone()),
+        )
+        .unwrap();
+    assert!(
+        matches!(response.payload, ResponsePayload::Cancel(Some(ref cancelled)) if *cancelled == id)
+    );
+
+    // a timeout of 0 milliseconds never expires
+    let request: ClientRequestMessage =
+        serde_json::from_str(r#"{"request_type": "input", "payload": "1", "timeout": 0}"#).unwrap();
+    assert_eq!(request.timeout, Some(0));
+    let response = server.handle_request(&request).unwrap();
+    assert!(matches!(response.response_type, ResponseType::Stack));
+
+    // quitting a session closes it, quitting the default session stops the server
+    let quit = ClientRequestMessage::new(RequestType::Input, RequestPayload::Input("quit".into()));
+    assert!(matches!(
+        server
+            .handle_request(&quit.clone().with_session(id.clone()))
+            .unwrap()
+            .response_type,
+        ResponseType::QuitSig
+    ));
+    assert!(matches!(
+        server
+            .handle_request(
+                &ClientRequestMessage::new(RequestType::CloseSession, RequestPayload::Empty(None),)
+                    .with_session(id)
+            )
+            .unwrap()
+            .payload,
+        ResponsePayload::Error(_)
+    ));
+    assert!(server.handle_request(&quit).is_none());
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_busy_session() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    use squiid_engine::ipc::{unix_socket::UnixSocket, IPCBackend};
+
+    let path = std::env::temp_dir().join(format!("squiid-busy-{}.sock", std::process::id()));
+    let backend = UnixSocket::new();
+    backend.bind_and_listen(path.to_str().unwrap()).unwrap();
+
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let send = |line: &str| {
+        let client = UnixStream::connect(&path).unwrap();
+        writeln!(&client, "{}", line).unwrap();
+        BufReader::new(client)
+            .lines()
+            .next()
+            .and_then(Result::ok)
+            .unwrap_or_default()
+    };
+    let responses = thread::scope(|scope| {
+        let serving = scope.spawn(|| server.serve(&backend, 2));
+
+        // keep the default session busy until it is cancelled
+        let busy = scope.spawn(|| {
+            send(
+                r#"{"request_type": "batch", "payload": ["{", "$k", "}", "k", "1", "999999", "sum"], "timeout": 10000}"#,
+            )
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        // waiting for the busy session would occupy the last free worker
+        let waiting = send(r#"{"request_type": "input", "payload": "1"}"#);
+        let hello = send(r#"{"request_type": "hello"}"#);
+        let cancel = send(r#"{"request_type": "cancel"}"#);
+        let busy = busy.join().unwrap();
+
+        let quit = send(r#"{"request_type": "input", "payload": "quit"}"#);
+        let _ = serving.join();
+        [waiting, hello, cancel, busy, quit]
+    });
+
+    assert!(responses[0].contains("session_busy"), "{}", responses[0]);
+    assert!(responses[1].contains("hello"));
+    assert!(responses[2].contains("cancel"));
+    assert!(responses[3].contains("cancelled"), "{}", responses[3]);
+    assert!(responses[4].contains("quitsig"));
+}
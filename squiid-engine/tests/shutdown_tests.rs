@@ -0,0 +1,131 @@
+#![cfg(feature = "ipc")]
+
+use squiid_engine::{
+    engine::EngineBuilder,
+    error::ErrorKind,
+    protocol::{
+        client_request::{ClientRequestMessage, RequestPayload, RequestType},
+        server_response::ResponsePayload,
+    },
+    server::{Server, ShutdownHandle},
+};
+
+#[test]
+fn test_panic_response() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+
+    // a payload that does not match the request type panics while it is evaluated
+    let request = ClientRequestMessage::new(RequestType::Input, RequestPayload::Batch(Vec::new()));
+    match server.handle_request(&request).unwrap().payload {
+        ResponsePayload::Error(error) => {
+            assert_eq!(error.code, ErrorKind::Internal);
+            assert!(error
+                .message
+                .contains("Invalid data type provided for payload"));
+        }
+        _ => panic!("a panic was not turned into an error response"),
+    }
+
+    // the server keeps answering requests afterwards
+    let request = ClientRequestMessage::new(RequestType::Input, RequestPayload::Input("1".into()));
+    assert!(matches!(
+        server.handle_request(&request).unwrap().payload,
+        ResponsePayload::Stack(_)
+    ));
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_shutdown() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        thread,
+    };
+
+    use squiid_engine::{
+        ipc::{unix_socket::UnixSocket, IPCBackend},
+        start_server_with,
+    };
+
+    let path = std::env::temp_dir().join(format!("squiid-shutdown-{}.sock", std::process::id()));
+    let backend = UnixSocket::new();
+    backend.bind_and_listen(path.to_str().unwrap()).unwrap();
+
+    let shutdown = ShutdownHandle::new();
+    let server = Server::new(EngineBuilder::new().build().unwrap()).with_shutdown(shutdown.clone());
+    assert!(!server.shutdown_handle().is_shutdown());
+    thread::scope(|scope| {
+        let serving = scope.spawn(|| server.serve(&backend, 2));
+
+        let client = UnixStream::connect(&path).unwrap();
+        writeln!(&client, r#"{{"request_type": "input", "payload": "1"}}"#).unwrap();
+        let mut responses = BufReader::new(client).lines();
+        assert!(responses.next().unwrap().unwrap().contains("stack"));
+
+        // the server stops without a quit request
+        shutdown.shutdown();
+        assert!(serving.join().unwrap().is_ok());
+    });
+    assert!(server.shutdown_handle().is_shutdown());
+    assert!(!path.exists());
+
+    // addresses that cannot be bound are returned as errors
+    assert!(start_server_with::<UnixSocket>(
+        Some("unix:///nonexistent/squiid.sock"),
+        None,
+        EngineBuilder::new().build().unwrap(),
+        ShutdownHandle::new(),
+    )
+    .is_err());
+}
+
+#[cfg(all(feature = "unix-socket", unix))]
+#[test]
+fn test_start_server_with() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        thread,
+        time::Duration,
+    };
+
+    use squiid_engine::{ipc::unix_socket::UnixSocket, start_server_with};
+
+    let path = std::env::temp_dir().join(format!("squiid-with-{}.sock", std::process::id()));
+    let address = format!("unix://{}", path.display());
+
+    // the server answers requests with the engine it was given
+    let mut engine = EngineBuilder::new().build().unwrap();
+    let _ = engine.add_item_to_stack("42".into());
+
+    let shutdown = ShutdownHandle::new();
+    let (response, result) = thread::scope(|scope| {
+        let serving = scope.spawn(|| {
+            start_server_with::<UnixSocket>(Some(&address), None, engine, shutdown.clone())
+        });
+
+        let mut client = None;
+        for _ in 0..100 {
+            match UnixStream::connect(&path) {
+                Ok(stream) => {
+                    client = Some(stream);
+                    break;
+                }
+                Err(_) => thread::sleep(Duration::from_millis(20)),
+            }
+        }
+        let response = client.map(|client| {
+            let _ = writeln!(
+                &client,
+                r#"{{"request_type": "input", "payload": "refresh"}}"#
+            );
+            BufReader::new(client).lines().next()
+        });
+
+        shutdown.shutdown();
+        (response, serving.join().unwrap())
+    });
+    assert!(result.is_ok());
+    assert!(response.unwrap().unwrap().unwrap().contains("42"));
+}
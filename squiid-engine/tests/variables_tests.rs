@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use squiid_engine::{
+    bucket::{Bucket, ConstantTypes, TypedBucket, TypedBucketKind},
+    engine::EngineBuilder,
+    error::ErrorKind,
+    protocol::{
+        client_request::{
+            ClientRequestMessage, RequestPayload, RequestType, VariablesActionType,
+            VariablesPayload,
+        },
+        server_response::{MessageAction, Notification, ResponsePayload},
+    },
+    server::Server,
+};
+
+/// Construct a variables payload
+fn payload(
+    action_type: VariablesActionType,
+    name: Option<&str>,
+    value: Option<TypedBucket>,
+) -> VariablesPayload {
+    VariablesPayload::new(action_type, name.map(String::from), value)
+}
+
+#[test]
+fn test_variables() {
+    let mut engine = EngineBuilder::new().build().unwrap();
+    engine
+        .variables
+        .insert("x".to_string(), Bucket::from_constant(ConstantTypes::PI));
+
+    let value = TypedBucket {
+        kind: TypedBucketKind::Float,
+        name: None,
+        value: Some("2.5".to_string()),
+    };
+    assert_eq!(
+        squiid_engine::handle_variables_data(
+            &mut engine,
+            payload(VariablesActionType::Set, Some("y"), Some(value))
+        ),
+        Ok(MessageAction::SendVariables(HashMap::from([(
+            "y".to_string(),
+            Bucket::from(2.5)
+        )])))
+    );
+    assert_eq!(
+        squiid_engine::handle_variables_data(
+            &mut engine,
+            payload(VariablesActionType::Get, Some("y"), None)
+        ),
+        Ok(MessageAction::SendVariables(HashMap::from([(
+            "y".to_string(),
+            Bucket::from(2.5)
+        )])))
+    );
+    assert_eq!(
+        squiid_engine::handle_variables_data(
+            &mut engine,
+            payload(VariablesActionType::List, None, None)
+        ),
+        Ok(MessageAction::SendVariables(engine.variables.clone()))
+    );
+    assert_eq!(engine.variables.len(), 2);
+
+    assert!(squiid_engine::handle_variables_data(
+        &mut engine,
+        payload(VariablesActionType::Delete, Some("x"), None)
+    )
+    .is_ok());
+    assert_eq!(engine.variables.get("x"), None);
+
+    // setting and deleting can be undone
+    let _ = engine.undo();
+    assert!(engine.variables.contains_key("x"));
+    let _ = engine.undo();
+    assert_eq!(engine.variables.get("y"), None);
+}
+
+#[test]
+fn test_variables_errors() {
+    let mut engine = EngineBuilder::new().build().unwrap();
+
+    let error = |data| {
+        squiid_engine::handle_variables_data(&mut EngineBuilder::new().build().unwrap(), data)
+            .unwrap_err()
+            .code
+    };
+    assert_eq!(
+        error(payload(VariablesActionType::Get, Some("x"), None)),
+        ErrorKind::UndefinedVariable
+    );
+    assert_eq!(
+        error(payload(VariablesActionType::Delete, Some("x"), None)),
+        ErrorKind::UndefinedVariable
+    );
+    assert_eq!(
+        error(payload(VariablesActionType::Get, Some("1x"), None)),
+        ErrorKind::InvalidName
+    );
+    assert_eq!(
+        error(payload(VariablesActionType::Get, None, None)),
+        ErrorKind::InvalidRequest
+    );
+    assert_eq!(
+        error(payload(VariablesActionType::Set, Some("x"), None)),
+        ErrorKind::InvalidRequest
+    );
+
+    // invalid values are rejected without changing the variables
+    let value = TypedBucket {
+        kind: TypedBucketKind::Constant,
+        name: Some("nothing".to_string()),
+        value: None,
+    };
+    assert!(squiid_engine::handle_variables_data(
+        &mut engine,
+        payload(VariablesActionType::Set, Some("x"), Some(value))
+    )
+    .is_err());
+    assert!(engine.variables.is_empty());
+}
+
+#[test]
+fn test_variables_changed_notification() {
+    let server = Server::new(EngineBuilder::new().build().unwrap());
+    let input = |data: &str| {
+        server
+            .handle_request(&ClientRequestMessage::new(
+                RequestType::Input,
+                RequestPayload::Input(data.into()),
+            ))
+            .unwrap()
+    };
+
+    let _ = input("5");
+    let _ = input("x");
+    assert_eq!(
+        input("store").notifications,
+        vec![Notification::VariablesChanged(vec!["x".to_string()])]
+    );
+    assert!(input("1").notifications.is_empty());
+
+    let request: ClientRequestMessage = serde_json::from_str(
+        r#"{"request_type": "variables", "payload": {"action_type": "list"}}"#,
+    )
+    .unwrap();
+    let response = server.handle_request(&request).unwrap();
+    assert!(response.notifications.is_empty());
+    assert!(matches!(
+        response.payload,
+        ResponsePayload::Variables(ref variables) if variables.get("x") == Some(&Bucket::from(5))
+    ));
+
+    // the values are sent with their types
+    let serialized = serde_json::to_string(&response).unwrap();
+    assert!(serialized.contains(r#""x":{"type":"float","value":"5"}"#));
+
+    let request: ClientRequestMessage = serde_json::from_str(
+        r#"{"request_type": "variables", "payload": {"action_type": "delete", "name": "x"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        server.handle_request(&request).unwrap().notifications,
+        vec![Notification::VariablesChanged(vec!["x".to_string()])]
+    );
+}
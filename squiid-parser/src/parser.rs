@@ -27,6 +27,10 @@ const RIGHT_SIDE_IMPLICIT: [Token; 8] = [
     LParen("_"),
 ];
 
+/// Functions whose first argument is an expression that is passed to the engine
+/// as an unevaluated program instead of being evaluated first
+const PROGRAM_FUNCTIONS: [&str; 2] = ["sum", "prod"];
+
 /// Parse whether this is a negative sign or a minus operator.
 /// It is a negative sign if:
 ///
@@ -117,6 +121,10 @@ pub fn shunting_yard_parser<'a>(tokens: Vec<Token<'a>>) -> Result<Vec<&'a str>,
     // this is used for all other tokens (insert directly after)
     let mut insert_chs = false;
 
+    // operator stack heights of the functions whose first argument is a program.
+    // when a comma brings the operator stack back to this height, the program is closed
+    let mut program_arguments: Vec<usize> = Vec::new();
+
     // using C as a reference can be helpful
     // http://www.eecs.northwestern.edu/~wkliao/op-prec.htm
     let precedence_map = HashMap::from([
@@ -143,9 +151,16 @@ pub fn shunting_yard_parser<'a>(tokens: Vec<Token<'a>>) -> Result<Vec<&'a str>,
         );
         match token {
             Token::Function(token_name) => {
-                operator_stack.push(token_name.trim_end_matches('('));
+                let function_name = token_name.trim_end_matches('(').trim_end();
+                operator_stack.push(function_name);
                 operator_stack.push("(");
 
+                // the first argument of this function is a program
+                if PROGRAM_FUNCTIONS.contains(&function_name) {
+                    output_queue.push("{");
+                    program_arguments.push(operator_stack.len());
+                }
+
                 // increment every element of insert_chs_function
                 for num in &mut chs_parenthesis {
                     *num += 1;
@@ -165,6 +180,7 @@ pub fn shunting_yard_parser<'a>(tokens: Vec<Token<'a>>) -> Result<Vec<&'a str>,
             | Token::ScientificNotation(token_name)
             | Token::Float(token_name)
             | Token::Int(token_name)
+            | Token::List(token_name)
             | Token::PrevAns(token_name) => {
                 output_queue.push(token_name);
             }
@@ -177,6 +193,12 @@ pub fn shunting_yard_parser<'a>(tokens: Vec<Token<'a>>) -> Result<Vec<&'a str>,
                         output_queue.push(operator);
                     }
                 }
+
+                // close the program if this comma ends the first argument of a program function
+                if program_arguments.last() == Some(&operator_stack.len()) {
+                    output_queue.push("}");
+                    program_arguments.pop();
+                }
             }
             Token::RParen(_) => {
                 // decrement every element of insert_chs_function
@@ -192,6 +214,14 @@ pub fn shunting_yard_parser<'a>(tokens: Vec<Token<'a>>) -> Result<Vec<&'a str>,
                     }
                 }
 
+                // a program function was closed before its expression argument was finished
+                if program_arguments
+                    .last()
+                    .is_some_and(|height| operator_stack.len() < *height)
+                {
+                    return Err("Missing arguments after expression".to_string());
+                }
+
                 // if there is an element which is 0, we are at the end of the
                 // function or parenthesis and we need to apply the chs function
                 for i in 0..chs_parenthesis.len() {
@@ -259,6 +289,10 @@ pub fn shunting_yard_parser<'a>(tokens: Vec<Token<'a>>) -> Result<Vec<&'a str>,
         }
     }
 
+    if !program_arguments.is_empty() {
+        return Err("Missing arguments after expression".to_string());
+    }
+
     while let Some(operator) = operator_stack.pop() {
         output_queue.push(operator);
     }
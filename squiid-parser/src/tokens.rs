@@ -35,6 +35,10 @@ pub enum Token<'a> {
     #[regex(r"[0-9]+", priority = 1)]
     Int(&'a str),
 
+    /// A list literal such as [1, -3, 2]
+    #[regex(r"\[[^\[\]]*\]")]
+    List(&'a str),
+
     /// An @ signifies the previous answer
     #[token("@")]
     PrevAns(&'a str),
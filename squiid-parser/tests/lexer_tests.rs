@@ -226,6 +226,7 @@ fn test_individual_tokenization() {
     tokenize_and_compare(">=", vec![Token::GreaterThanEqualTo(">=")]);
     tokenize_and_compare("<=", vec![Token::LessThanEqualTo("<=")]);
     tokenize_and_compare("==", vec![Token::EqualTo("==")]);
+    tokenize_and_compare("[1, -3, 2]", vec![Token::List("[1, -3, 2]")]);
 }
 
 #[test]
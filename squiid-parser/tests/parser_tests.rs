@@ -266,4 +266,26 @@ fn test_shunting_yard_parser() {
         "8e3 * ($B + 4.532 * -0.2) + $A",
         vec!["8e3", "$B", "4.532", "0.2", "chs", "*", "+", "*", "$A", "+"],
     );
+
+    parse_and_compare(
+        "polyval([1, -3, 2], 4) + 1",
+        vec!["[1, -3, 2]", "4", "polyval", "1", "+"],
+    );
+
+    // the expression argument of sum and prod is passed as a program
+    parse_and_compare(
+        "sum($k^2, k, 1, 100)",
+        vec!["{", "$k", "2", "^", "}", "k", "1", "100", "sum"],
+    );
+    parse_and_compare(
+        "2*prod(k+1, k, 1, 10)",
+        vec!["2", "{", "k", "1", "+", "}", "k", "1", "10", "prod", "*"],
+    );
+    parse_and_compare(
+        "sum(sum($j*$k, j, 1, 3), k, 1, 3)",
+        vec![
+            "{", "{", "$j", "$k", "*", "}", "j", "1", "3", "sum", "}", "k", "1", "3", "sum",
+        ],
+    );
+    assert!(parse("sum($k)").is_err());
 }
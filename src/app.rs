@@ -2,13 +2,11 @@ use std::{collections::HashMap, io};
 
 use lazy_static::lazy_static;
 use squiid_engine::{
-    extract_data,
-    protocol::server_response::{ResponsePayload, ResponseType, ServerResponseMessage},
+    client::{nng::NanoMsgTransport, Client, ClientError},
+    protocol::server_response::ResponsePayload,
 };
 use unicode_width::UnicodeWidthStr;
 
-use nng::Socket;
-
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 
 use ratatui::{
@@ -20,9 +18,9 @@ use ratatui::{
     Frame, Terminal,
 };
 
-use crate::{
-    config_utils,
-    utils::{current_char_index, input_buffer_is_sci_notate, send_input_data},
+use crate::utils::{
+    current_char_index, input_buffer_is_open_list, input_buffer_is_open_program,
+    input_buffer_is_sci_notate, load_algebraic_history, save_algebraic_history,
 };
 
 /// The input mode state of the application
@@ -36,6 +34,20 @@ enum InputMode {
     Rpn,
 }
 
+/// Commands that can change the statistics or the modulus shown by the frontend
+const PANEL_COMMANDS: [&str; 10] = [
+    "setmod",
+    "unsetmod",
+    "statadd",
+    "statdel",
+    "statclear",
+    "undo",
+    "redo",
+    "branch",
+    "restore",
+    "load",
+];
+
 lazy_static! {
     /// RPN symbols and their corresponding commands
     static ref RPN_SYMBOL_MAP: HashMap<KeyCode, &'static str> = [
@@ -125,8 +137,8 @@ impl StatefulTopPanel {
 pub struct App<'a> {
     /// Current value of the input box
     input: String,
-    /// Socket used to communicate with the backend
-    pub socket: &'a Socket,
+    /// Client used to communicate with the backend
+    pub client: &'a Client<NanoMsgTransport>,
     /// Current input mode
     input_mode: InputMode,
     /// History of recorded messages
@@ -135,6 +147,10 @@ pub struct App<'a> {
     info: Vec<String>,
     /// Stack for RPN mode
     stack: Vec<String>,
+    /// Accumulated (x, y) statistics pairs
+    statistics: Vec<(f64, f64)>,
+    /// Active modulus of modular arithmetic mode
+    modulus: Option<u64>,
     /// Most recent error message
     error: String,
     /// Current cursor offset
@@ -142,13 +158,17 @@ pub struct App<'a> {
     /// Stack selection state
     top_panel_state: StatefulTopPanel,
     quit_app: bool,
+    /// Whether the algebraic history is saved when quitting and restored on start
+    persist_session: bool,
+    /// Whether the statistics and modulus may have changed since they were last requested
+    panels_outdated: bool,
 }
 
 impl<'a> App<'a> {
-    pub fn new(socket: &'a Socket) -> App<'a> {
+    pub fn new(client: &'a Client<NanoMsgTransport>) -> App<'a> {
         App {
             input: String::new(),
-            socket,
+            client,
             input_mode: InputMode::None,
             history: Vec::new(),
             info: vec![
@@ -169,21 +189,36 @@ impl<'a> App<'a> {
                 env!("CARGO_PKG_REPOSITORY").to_string(),
             ],
             stack: Vec::new(),
+            statistics: Vec::new(),
+            modulus: None,
             error: String::new(),
             left_cursor_offset: 0,
             top_panel_state: StatefulTopPanel::with_items(vec![]),
             quit_app: false,
+            persist_session: false,
+            panels_outdated: true,
         }
     }
 }
 
 impl<'a> App<'a> {
+    /// Evaluate a command, noting if it can change the statistics or the modulus
+    fn exec(&mut self, command: &str) -> Result<ResponsePayload, ClientError> {
+        if PANEL_COMMANDS.contains(&command) {
+            self.panels_outdated = true;
+        }
+        self.client.exec(command)
+    }
+
     /// Get keybind from config file as string
     pub fn keybind_from_config(&mut self, keybind_name: &str) -> String {
-        config_utils::get_key(self, "keybinds", keybind_name)
-            .as_str()
-            .unwrap()
-            .to_string()
+        match self.client.get_config("keybinds", keybind_name) {
+            Ok(keybind) => keybind.as_str().unwrap_or_default().to_string(),
+            Err(error) => {
+                self.error = format!("Error: {}", error);
+                String::new()
+            }
+        }
     }
 
     /// Get keycode from config
@@ -205,41 +240,38 @@ impl<'a> App<'a> {
             "delete" => KeyCode::Delete,
             "insert" => KeyCode::Insert,
             "escape" => KeyCode::Esc,
+            // function keys such as f1 through f12
+            _ if keybind.starts_with('f') && keybind[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(keybind[1..].parse::<u8>().unwrap())
+            }
             _ if keybind.len() == 1 => KeyCode::Char(keybind.chars().next().unwrap()),
             _ => KeyCode::Null,
         }
     }
 }
 
-/// Update the stack if msg is not an error. If it is an error, display that error
-pub fn update_stack_or_error(msg: ServerResponseMessage, app: &mut App) {
+/// Update the stack if the response is not an error. If it is an error, display that error
+pub fn update_stack_or_error(response: Result<ResponsePayload, ClientError>, app: &mut App) {
     // TODO: make a seperate display for commands
-    match msg.response_type {
-        ResponseType::Stack => {
-            app.stack = extract_data!(msg.payload, ResponsePayload::Stack)
-                .iter()
-                .map(|item| item.to_string())
-                .collect();
-        }
-        ResponseType::Error => {
-            let error_message = extract_data!(msg.payload, ResponsePayload::Error);
-            app.error = format!("Error: {}", error_message);
+    match response {
+        Ok(ResponsePayload::Stack(stack)) => {
+            app.stack = stack.iter().map(|item| item.to_string()).collect();
         }
-        ResponseType::Commands => todo!(),
-        ResponseType::QuitSig => app.quit_app = true,
-        // configuration return is handeled elsewhere
-        ResponseType::Configuration | ResponseType::PrevAnswer => (),
+        Ok(ResponsePayload::Statistics(statistics)) => app.statistics = statistics,
+        Ok(ResponsePayload::Modulus(modulus)) => app.modulus = modulus,
+        Ok(ResponsePayload::QuitSig(_)) => app.quit_app = true,
+        // algebraic results are handled by algebraic_eval, and the other responses are
+        // not displayed
+        Ok(_) => (),
+        Err(error) => app.error = format!("Error: {}", error),
     }
 }
 
 /// Handle algebraic expressions
-fn algebraic_eval(app: &mut App, socket: &Socket) {
+fn algebraic_eval(app: &mut App) {
     // Get string from input box and empty it
     let entered_expression: String = app.input.drain(..).collect();
 
-    // Clear stack
-    _ = send_input_data(socket, "clear");
-
     // Special frontend commands
     if entered_expression.as_str() == "clear" {
         app.history = Vec::new();
@@ -248,65 +280,25 @@ fn algebraic_eval(app: &mut App, socket: &Socket) {
 
     // reset cursor offset
     app.left_cursor_offset = 0;
-    // Parse algebraic expression into postfix expression
-    let rpn_expression = match squiid_parser::parse(entered_expression.trim()) {
-        Ok(expr) => expr,
-        Err(e) => {
-            app.error = format!("Error: {}", e);
-            return;
-        }
-    };
 
-    // Commands that cannot be used in algebraic mode
-    let non_algebraic_commands = [
-        "invert", "drop", "swap", "dup", "rolldown", "rollup", "clear", "undo",
-    ];
-    // Iterate through the commands present in the expression
-    for command_raw in rpn_expression.iter() {
-        // Check if it is forbidden
-        if non_algebraic_commands.contains(command_raw) {
-            // Give error and stop trying to evaluate if the command is forbidden
-            app.error = format!("Error: {} is invalid in algebraic mode", command_raw);
-            return;
+    // Evaluate the whole expression in the engine as one command, which may call any command
+    app.panels_outdated = true;
+    let result = match app.client.eval_algebraic(entered_expression.trim()) {
+        Ok(result) => result.map(|result| result.to_string()).unwrap_or_default(),
+        Err(error) => {
+            update_stack_or_error(Err(error), app);
+            String::new()
         }
-    }
+    };
 
-    // Iterate through expression
-    for command_raw in rpn_expression.iter() {
-        // Convert operator symbols to engine commands
-        let command = match *command_raw {
-            "+" => "add",
-            "-" => "subtract",
-            "*" => "multiply",
-            "/" => "divide",
-            "%" => "mod",
-            "^" => "power",
-            "=" => "invstore",
-            "==" => "eq",
-            ">" => "gt",
-            "<" => "lt",
-            ">=" => "geq",
-            "<=" => "leq",
-            _ => command_raw,
-        };
-        // Send command to server
-        let msg = send_input_data(socket, command);
-        // Update stack
-        update_stack_or_error(msg, app);
-    }
-
-    // Empty placeholder result in case there is nothing on the stack
-    let mut result = "";
-    // Set result to last item in stack if there is one
-    if !app.stack.is_empty() {
-        result = app.stack.last().unwrap();
-    }
+    // Update stack display with the result of the expression
+    update_stack_or_error(app.client.exec("refresh"), app);
 
     // Combine entry and result into line to print
     let mut history_entry = entered_expression;
     if app.error.is_empty() && !result.is_empty() {
         history_entry.push_str(" = ");
-        history_entry.push_str(result);
+        history_entry.push_str(&result);
     } else if !app.error.is_empty() {
         history_entry.push_str(" : ");
         history_entry.push_str(app.error.as_str());
@@ -319,21 +311,19 @@ fn algebraic_eval(app: &mut App, socket: &Socket) {
 }
 
 /// Handle typing in RPN mode
-fn rpn_input(app: &mut App, socket: &Socket, c: char) {
+fn rpn_input(app: &mut App, c: char) {
     // Add character to input box
     let index = current_char_index(app.left_cursor_offset as usize, app.input.len());
     app.input.insert(index, c);
 
     // query engine for available commands
-    let binding = send_input_data(socket, "commands");
-    let commands = extract_data!(binding.payload, ResponsePayload::Commands);
+    let commands = app.client.commands().unwrap_or_default();
 
     // Check if input box contains a command, if so, automatically execute it
     if commands.contains(&app.input) {
-        // Send command
-        let msg = send_input_data(socket, app.input.as_str());
-        // Update stack display
-        update_stack_or_error(msg, app);
+        // Send command and update stack display
+        let command = app.input.clone();
+        update_stack_or_error(app.exec(&command), app);
         // Clear input
         app.input.drain(..);
         // reset cursor offset
@@ -342,32 +332,32 @@ fn rpn_input(app: &mut App, socket: &Socket, c: char) {
 }
 
 /// Handle RPN enter
-fn rpn_enter(app: &mut App, socket: &Socket) {
+fn rpn_enter(app: &mut App) {
     // Get command from input box and empty it
     let command: String = app.input.drain(..).collect();
     // reset cursor offset
     app.left_cursor_offset = 0;
     // Send command if there is one, otherwise duplicate last item in stack
-    let msg = if !command.is_empty() {
+    let response = if !command.is_empty() {
         // Send to backend and get response
-        send_input_data(socket, command.as_str())
+        app.exec(&command)
     } else {
         // Empty input, duplicate
-        send_input_data(socket, "dup")
+        app.exec("dup")
     };
     // Update stack display
-    update_stack_or_error(msg, app);
+    update_stack_or_error(response, app);
 }
 
 /// Handle RPN operators
-fn rpn_operator(app: &mut App, socket: &Socket, key: crate::event::KeyEvent) {
+fn rpn_operator(app: &mut App, key: crate::event::KeyEvent) {
     // Get operand from input box and empty it
     let command: String = app.input.drain(..).collect();
     // reset cursor offset
     app.left_cursor_offset = 0;
     // Send operand to backend if there is one
     if !command.is_empty() {
-        _ = send_input_data(socket, command.as_str());
+        let _ = app.exec(&command);
     }
 
     // Select operation
@@ -375,30 +365,38 @@ fn rpn_operator(app: &mut App, socket: &Socket, key: crate::event::KeyEvent) {
         _ if RPN_SYMBOL_MAP.contains_key(&key.code) => RPN_SYMBOL_MAP.get(&key.code).unwrap(),
         _ => "there is no way for this to occur",
     };
-    // Send operation
-    let msg = send_input_data(socket, operation);
-    // Update stack display
-    update_stack_or_error(msg, app);
+    // Send operation and update stack display
+    update_stack_or_error(app.exec(operation), app);
 }
 
 /// Create the main application and run it
-pub fn run_app<B: Backend>(
-    terminal: &mut Terminal<B>,
-    mut app: App,
-    socket: &Socket,
-) -> io::Result<()> {
+pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
     // set default start mode
-    let binding = config_utils::get_key(&mut app, "system", "start_mode");
-    let start_mode = binding.as_str().unwrap();
+    let start_mode = app.client.get_config("system", "start_mode");
 
-    app.input_mode = match start_mode {
-        "algebraic" => InputMode::Algebraic,
-        "rpn" => InputMode::Rpn,
+    app.input_mode = match start_mode.as_ref().ok().and_then(|mode| mode.as_str()) {
+        Some("algebraic") => InputMode::Algebraic,
+        Some("rpn") => InputMode::Rpn,
         _ => InputMode::None,
     };
 
+    // restore the algebraic history and show the stack of the restored engine session
+    app.persist_session = app
+        .client
+        .get_config("system", "persist_session")
+        .ok()
+        .and_then(|persist_session| persist_session.as_bool())
+        .unwrap_or(false);
+    if app.persist_session {
+        app.history = load_algebraic_history();
+    }
+    update_stack_or_error(app.client.exec("refresh"), &mut app);
+
     loop {
         if app.quit_app {
+            if app.persist_session {
+                save_algebraic_history(&app.history);
+            }
             return Ok(());
         }
 
@@ -419,7 +417,8 @@ pub fn run_app<B: Backend>(
                         app.input_mode = InputMode::Rpn;
                     }
                     _ if key.code == app.keycode_from_config("quit") => {
-                        return Ok(());
+                        // stop the engine so that it saves its session
+                        update_stack_or_error(app.exec("quit"), &mut app);
                     }
                     _ => {}
                 },
@@ -428,7 +427,7 @@ pub fn run_app<B: Backend>(
                     match key.code {
                         // Handle enter
                         _ if key.code == app.keycode_from_config("enter") => {
-                            send_input_data(socket, "update_previous_answer");
+                            let _ = app.exec("update_previous_answer");
 
                             if app.top_panel_state.currently_selecting() {
                                 // currently selecting, insert into text
@@ -451,49 +450,71 @@ pub fn run_app<B: Backend>(
 
                                 app.top_panel_state.deselect();
                             } else if app.input_mode == InputMode::Algebraic {
-                                algebraic_eval(&mut app, socket);
+                                algebraic_eval(&mut app);
                             } else {
-                                rpn_enter(&mut app, socket);
+                                rpn_enter(&mut app);
                             }
                         }
                         // Handle single character operators
                         _ if RPN_SYMBOL_MAP.contains_key(&key.code)
                             && app.input_mode == InputMode::Rpn
-                            && !input_buffer_is_sci_notate(&app.input) =>
+                            && !input_buffer_is_sci_notate(&app.input)
+                            && !input_buffer_is_open_list(&app.input)
+                            && !input_buffer_is_open_program(&app.input) =>
                         {
-                            rpn_operator(&mut app, socket, key);
+                            rpn_operator(&mut app, key);
                         }
 
                         _ if key.code == app.keycode_from_config("rpn_drop")
                             && app.input_mode == InputMode::Rpn =>
                         {
-                            update_stack_or_error(send_input_data(socket, "drop"), &mut app)
+                            update_stack_or_error(app.exec("drop"), &mut app)
                         }
 
                         _ if key.code == app.keycode_from_config("rpn_roll_up")
                             && app.input_mode == InputMode::Rpn =>
                         {
-                            update_stack_or_error(send_input_data(socket, "rollup"), &mut app)
+                            update_stack_or_error(app.exec("rollup"), &mut app)
                         }
                         _ if key.code == app.keycode_from_config("rpn_roll_down")
                             && app.input_mode == InputMode::Rpn =>
                         {
-                            update_stack_or_error(send_input_data(socket, "rolldown"), &mut app)
+                            update_stack_or_error(app.exec("rolldown"), &mut app)
                         }
                         _ if key.code == app.keycode_from_config("rpn_swap")
                             && app.input_mode == InputMode::Rpn =>
                         {
-                            update_stack_or_error(send_input_data(socket, "swap"), &mut app)
+                            update_stack_or_error(app.exec("swap"), &mut app)
+                        }
+                        _ if key.code == app.keycode_from_config("rpn_over")
+                            && app.input_mode == InputMode::Rpn =>
+                        {
+                            update_stack_or_error(app.exec("over"), &mut app)
+                        }
+                        _ if key.code == app.keycode_from_config("rpn_rot")
+                            && app.input_mode == InputMode::Rpn =>
+                        {
+                            update_stack_or_error(app.exec("rot"), &mut app)
+                        }
+                        _ if key.code == app.keycode_from_config("rpn_nip")
+                            && app.input_mode == InputMode::Rpn =>
+                        {
+                            update_stack_or_error(app.exec("nip"), &mut app)
+                        }
+                        _ if key.code == app.keycode_from_config("rpn_depth")
+                            && app.input_mode == InputMode::Rpn =>
+                        {
+                            update_stack_or_error(app.exec("depth"), &mut app)
                         }
                         _ if key.code == app.keycode_from_config("rpn_undo")
                             && app.input_mode == InputMode::Rpn =>
                         {
-                            update_stack_or_error(send_input_data(socket, "undo"), &mut app)
+                            update_stack_or_error(app.exec("undo"), &mut app)
                         }
                         _ if key.code == app.keycode_from_config("rpn_redo")
                             && app.input_mode == InputMode::Rpn =>
                         {
-                            update_stack_or_error(send_input_data(socket, "redo"), &mut app)
+                            update_stack_or_error(app.exec("redo"), &mut app)
                         }
                         // Handle typing characters
                         KeyCode::Char(c) => {
@@ -505,7 +526,7 @@ pub fn run_app<B: Backend>(
                                 );
                                 app.input.insert(index, c);
                             } else if app.input_mode == InputMode::Rpn {
-                                rpn_input(&mut app, socket, c);
+                                rpn_input(&mut app, c);
                             }
                         }
                         // Handle backspace
@@ -597,11 +618,15 @@ pub fn run_app<B: Backend>(
         }
         // Update stack if there is currently an error, since the last request will have gotten the error not the stack
         if !app.error.is_empty() {
-            let msg = send_input_data(socket, "refresh");
-            app.stack = extract_data!(msg.payload, ResponsePayload::Stack)
-                .iter()
-                .map(|item| item.to_string())
-                .collect();
+            if let Ok(stack) = app.client.stack() {
+                app.stack = stack.iter().map(|item| item.to_string()).collect();
+            }
+        }
+        // Update the statistics panel and modulus after commands that may have changed them
+        if app.panels_outdated {
+            app.panels_outdated = false;
+            update_stack_or_error(app.client.exec("statlist"), &mut app);
+            update_stack_or_error(app.client.exec("getmod"), &mut app);
         }
     }
 }
@@ -622,7 +647,7 @@ fn ui(f: &mut Frame, app: &mut App) {
         .split(f.area());
 
     // Set help message to display
-    let (msg, style) = match app.input_mode {
+    let (mut msg, style) = match app.input_mode {
         // Display error if there is one
         _ if !app.error.is_empty() => (
             vec![Span::styled(
@@ -721,6 +746,15 @@ fn ui(f: &mut Frame, app: &mut App) {
                     Style::default().add_modifier(Modifier::BOLD),
                 ),
                 Span::raw(": swap  "),
+                Span::styled(
+                    format!(
+                        "{}/{}",
+                        app.keybind_from_config("rpn_over").to_owned(),
+                        app.keybind_from_config("rpn_rot").to_owned()
+                    ),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(": over/rot  "),
                 Span::styled(
                     app.keybind_from_config("rpn_undo").to_owned(),
                     Style::default().add_modifier(Modifier::BOLD),
@@ -785,14 +819,52 @@ fn ui(f: &mut Frame, app: &mut App) {
         .highlight_symbol("> ")
         .direction(ListDirection::BottomToTop);
 
+    // Show the statistics data next to the top panel if there is any
+    let top_panel_area = if app.input_mode != InputMode::None && !app.statistics.is_empty() {
+        let top_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+            .split(chunks[0]);
+
+        let statistics_content: Vec<ListItem> = app
+            .statistics
+            .iter()
+            .rev()
+            .map(|(x, y)| ListItem::new(Line::from(Span::raw(format!("{}, {}", x, y)))))
+            .collect();
+        let statistics_panel = List::new(statistics_content)
+            .block(Block::default().borders(Borders::ALL).title("Statistics"))
+            .direction(ListDirection::BottomToTop);
+        f.render_widget(statistics_panel, top_chunks[1]);
+
+        top_chunks[0]
+    } else {
+        chunks[0]
+    };
+
     if app.top_panel_state.currently_selecting() {
         f.render_stateful_widget(
             top_panel.style(Style::default().fg(Color::Blue)),
-            chunks[0],
+            top_panel_area,
             &mut app.top_panel_state.state,
         );
     } else {
-        f.render_stateful_widget(top_panel, chunks[0], &mut app.top_panel_state.state);
+        f.render_stateful_widget(top_panel, top_panel_area, &mut app.top_panel_state.state);
+    }
+
+    // Show the active modulus at the start of the status line
+    if let Some(modulus) = app.modulus {
+        if app.input_mode != InputMode::None && app.error.is_empty() {
+            msg.insert(
+                0,
+                Span::styled(
+                    format!("mod {}  ", modulus),
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .fg(Color::Yellow),
+                ),
+            );
+        }
     }
 
     let mut text = Text::from(Line::from(msg));
@@ -1,13 +1,11 @@
 use std::{error::Error, io, thread, time::Duration};
 
 use clap::arg;
-use nng::{Protocol, Socket};
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 mod app;
 use app::{run_app, App};
 
-mod config_utils;
 mod utils;
 
 use crossterm::{
@@ -15,7 +13,10 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use squiid_engine::crash_reporter;
+use squiid_engine::{
+    client::{nng::NanoMsgTransport, Client},
+    crash_reporter,
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = clap::command!()
@@ -40,19 +41,33 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // start evaluation server
     let _ = thread::spawn(move || {
-        squiid_engine::start_server(Some(&format!("tcp://127.0.0.1:{}", port_num)));
+        // only this frontend knows the token, so other users cannot use its engine
+        if let Err(err) = squiid_engine::start_server::<squiid_engine::ipc::nng::NanoMsg>(
+            Some(&format!("tcp://127.0.0.1:{}", port_num)),
+            Some(utils::token()),
+        ) {
+            let _ = reset_terminal();
+            eprintln!("{:#}", err);
+            std::process::exit(1);
+        }
     });
 
     // Wait for server to start
     thread::sleep(Duration::from_millis(10));
 
-    // initiate nng connection
-    let socket = Socket::new(Protocol::Req0).unwrap();
-    assert!(socket
-        .dial(&format!("tcp://127.0.0.1:{}", port_num))
-        .is_ok());
+    // connect to the engine
+    let client = Client::new(NanoMsgTransport::connect(&format!(
+        "tcp://127.0.0.1:{}",
+        port_num
+    ))?)
+    .with_token(utils::token());
 
     std::panic::set_hook(Box::new(|panic| {
+        // panics while the server answers a request are sent back as errors
+        if thread::current().name() == Some(crash_reporter::SERVER_THREAD_NAME) {
+            return;
+        }
+
         reset_terminal().unwrap();
         crash_reporter::crash_report(panic, true);
         std::process::exit(1);
@@ -71,8 +86,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     print!("{}[2J", 27 as char);
 
     // create app and run it
-    let app = App::new(&socket);
-    let res = run_app(&mut terminal, app, &socket);
+    let app = App::new(&client);
+    let res = run_app(&mut terminal, app);
 
     reset_terminal()?;
 
@@ -1,42 +1,14 @@
-use std::{net::TcpListener, ops::Range};
+use std::{fs, net::TcpListener, ops::Range, path::PathBuf, sync::OnceLock};
 
-use nng::{Message, Socket};
-use squiid_engine::protocol::{
-    client_request::{ClientRequestMessage, RequestPayload, RequestType},
-    server_response::ServerResponseMessage,
-};
+use squiid_engine::{server, session};
 use squiid_parser::{lexer::lex, tokens::Token};
 
-/// Send input data to backend
-pub fn send_input_data(socket: &Socket, command: &str) -> ServerResponseMessage {
-    send_data(
-        socket,
-        RequestType::Input,
-        RequestPayload::Input(command.into()),
-    )
-}
-
-/// Lower level function for sending data to the server
-pub fn send_data(
-    socket: &Socket,
-    request_type: RequestType,
-    request_payload: RequestPayload,
-) -> ServerResponseMessage {
-    let serialized_data: String =
-        serde_json::to_string(&ClientRequestMessage::new(request_type, request_payload)).unwrap();
-
-    let _ = socket.send(serialized_data.as_bytes());
-    let msg = socket.recv().unwrap();
+/// Token that authenticates the requests of this frontend to the engine it started
+static TOKEN: OnceLock<String> = OnceLock::new();
 
-    deserialize_message(msg)
-}
-
-/// Deserialize a message from the server
-fn deserialize_message(msg: Message) -> ServerResponseMessage {
-    let msg_string = String::from_utf8(msg.to_vec()).unwrap();
-    let data: ServerResponseMessage = serde_json::from_str(&msg_string).unwrap();
-
-    data
+/// Get the token of this frontend, generating a random one on first use
+pub fn token() -> &'static str {
+    TOKEN.get_or_init(server::generate_token)
 }
 
 /// Get current character index based on cursor position and text length
@@ -48,6 +20,31 @@ pub fn current_char_index(left_cursor_offset: usize, input_len: usize) -> usize
     }
 }
 
+/// Determine the path of the file that the algebraic history is saved in
+fn algebraic_history_path() -> PathBuf {
+    session::data_directory().join("algebraic_history.json")
+}
+
+/// Read the algebraic history that was saved when quitting. Returns an empty
+/// history if there is none
+pub fn load_algebraic_history() -> Vec<String> {
+    fs::read_to_string(algebraic_history_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Save the algebraic history so that it can be restored on the next start
+pub fn save_algebraic_history(history: &[String]) {
+    let path = algebraic_history_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(contents) = serde_json::to_string(history) {
+        let _ = fs::write(path, contents);
+    }
+}
+
 /// Find the first available port in a provided range
 pub fn get_available_port(mut range: Range<u16>) -> Option<u16> {
     range.find(|port| port_is_available(*port))
@@ -80,3 +77,13 @@ pub fn input_buffer_is_sci_notate(buffer: &str) -> bool {
         Err(_) => false,
     }
 }
+
+/// Test if a str buffer contains a list literal that has not been closed yet
+pub fn input_buffer_is_open_list(buffer: &str) -> bool {
+    buffer.matches('[').count() > buffer.matches(']').count()
+}
+
+/// Test if a str buffer contains a program literal that has not been closed yet
+pub fn input_buffer_is_open_program(buffer: &str) -> bool {
+    buffer.matches('{').count() > buffer.matches('}').count()
+}